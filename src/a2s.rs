@@ -0,0 +1,367 @@
+use crate::a2s_packet::{INFO_PAYLOAD, SINGLE_HEADER, SPLIT_HEADER};
+use crate::attrs::ServerAttrs;
+use crate::packet_ext::WritePacketExt;
+use crate::service::{self, ServerHandle};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::io::{Cursor, Error, Result};
+use std::net::{SocketAddr, UdpSocket};
+use std::thread;
+use std::time::Duration;
+
+const A2S_INFO: u8 = 0x54;
+const A2S_PLAYER: u8 = 0x55;
+const A2S_RULES: u8 = 0x56;
+const S2C_CHALLENGE: u8 = 0x41;
+const S2A_INFO: u8 = 0x49;
+const S2A_PLAYER: u8 = 0x44;
+const S2A_RULES: u8 = 0x45;
+
+/// A2S responder - Simulates the query port of a game server
+///
+/// * Requires feature: `server` (Turned **off** by default)
+/// * Answers A2S_INFO, A2S_PLAYER and A2S_RULES requests from an in-memory
+///   description of the server: its [`ServerAttrs`], players and rules.
+/// * Requests are answered with a challenge first, unless turned off with
+///   [`challenge`](#method.challenge). Each address gets its own challenge.
+/// * Replies larger than the [`split_size`](#method.split_size) are sent
+///   as split packets.
+/// * Latency, loss and malformed replies can be simulated with
+///   [`latency`](#method.latency), [`loss`](#method.loss) and
+///   [`malformed`](#method.malformed).
+/// * Each started responder runs on its own thread and UDP socket, so
+///   start one per simulated game server.
+///
+/// # Quick Start
+/// ```rust
+/// use msq::{A2SResponder, ServerAttrs};
+/// use std::io::Result;
+/// use std::time::Duration;
+///
+/// fn main() -> Result<()> {
+///     let handle = A2SResponder::new(ServerAttrs::new().appid(240).map("de_dust2").players(2, 24))
+///         .game("Counter-Strike: Source")
+///         .player("alice", 12, Duration::from_secs(600))
+///         .player("bob", 3, Duration::from_secs(60))
+///         .rule("mp_friendlyfire", "0")
+///         .latency(Duration::from_millis(30))
+///         .start()?;
+///
+///     println!("Simulated game server on {}", handle.local_addr());
+///     Ok(())
+/// }
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct A2SResponder {
+    attrs: ServerAttrs,
+    game: String,
+    players: Vec<(String, i32, f32)>,
+    rules: Vec<(String, String)>,
+    challenge: bool,
+    split_size: usize,
+    latency: Option<Duration>,
+    loss: f64,
+    malformed: f64,
+}
+
+impl A2SResponder {
+    /// Returns a new A2SResponder for a server with the given attributes,
+    /// without any players or rules
+    ///
+    /// # Arguments
+    /// * `attrs` - The server's [`ServerAttrs`]
+    pub fn new(attrs: ServerAttrs) -> A2SResponder {
+        A2SResponder {
+            attrs,
+            game: String::from(""),
+            players: vec![],
+            rules: vec![],
+            challenge: true,
+            split_size: 1400,
+            latency: None,
+            loss: 0.0,
+            malformed: 0.0,
+        }
+    }
+
+    /// Set the game description of A2S_INFO replies (EX: `Counter-Strike: Source`)
+    ///
+    /// # Arguments
+    /// * `game` - Full name of the game
+    pub fn game(mut self, game: &str) -> A2SResponder {
+        self.game = String::from(game);
+        self
+    }
+
+    /// Add a player to A2S_PLAYER replies
+    ///
+    /// # Arguments
+    /// * `name` - The player's name
+    /// * `score` - The player's score
+    /// * `duration` - Time the player has been connected
+    pub fn player(mut self, name: &str, score: i32, duration: Duration) -> A2SResponder {
+        self.players.push((String::from(name), score, duration.as_secs_f32()));
+        self
+    }
+
+    /// Add a rule (console variable) to A2S_RULES replies
+    ///
+    /// # Arguments
+    /// * `name` - The rule's name (EX: `mp_friendlyfire`)
+    /// * `value` - The rule's value
+    pub fn rule(mut self, name: &str, value: &str) -> A2SResponder {
+        self.rules.push((String::from(name), String::from(value)));
+        self
+    }
+
+    /// Set whether requests have to be sent with a challenge (Defaults to `true`)
+    ///
+    /// # Arguments
+    /// * `required` - Reply with a challenge to requests without a valid one
+    pub fn challenge(mut self, required: bool) -> A2SResponder {
+        self.challenge = required;
+        self
+    }
+
+    /// Set the maximum size of a reply packet, larger replies are split
+    /// (Defaults to 1400 bytes)
+    ///
+    /// # Arguments
+    /// * `size` - Maximum size of a packet, including the split header
+    pub fn split_size(mut self, size: usize) -> A2SResponder {
+        // A split packet needs room for its header and at least one byte
+        self.split_size = size.max(13);
+        self
+    }
+
+    /// Delay every reply
+    ///
+    /// # Arguments
+    /// * `latency` - Time to wait before sending a reply
+    pub fn latency(mut self, latency: Duration) -> A2SResponder {
+        self.latency = Some(latency);
+        self
+    }
+
+    /// Drop replies
+    ///
+    /// # Arguments
+    /// * `probability` - Chance of not sending a reply, from `0.0` to `1.0`
+    pub fn loss(mut self, probability: f64) -> A2SResponder {
+        self.loss = probability;
+        self
+    }
+
+    /// Send truncated replies
+    ///
+    /// # Arguments
+    /// * `probability` - Chance of cutting a reply in half, from `0.0` to `1.0`
+    pub fn malformed(mut self, probability: f64) -> A2SResponder {
+        self.malformed = probability;
+        self
+    }
+
+    /// Start answering requests on a random port of `127.0.0.1`
+    pub fn start(self) -> Result<ServerHandle> {
+        self.bind("127.0.0.1:0")
+    }
+
+    /// Start answering requests on the given address on a new thread
+    ///
+    /// Invalid requests are ignored.
+    ///
+    /// # Arguments
+    /// * `addr` - The address to bind to (EX: `0.0.0.0:27015`)
+    pub fn bind(self, addr: &str) -> Result<ServerHandle> {
+        let mut state = A2SState {
+            responder: self,
+            hasher: RandomState::new(),
+            port: 0,
+            replies: 0,
+        };
+        service::spawn(addr, move |sock, packet, from| {
+            let _ = state.reply(sock, packet, from);
+        })
+    }
+}
+
+struct A2SState {
+    responder: A2SResponder,
+    hasher: RandomState,
+    port: u16,
+    replies: u32,
+}
+
+impl A2SState {
+    fn reply(&mut self, sock: &UdpSocket, packet: &[u8], from: SocketAddr) -> Result<()> {
+        if packet.len() < 5 || packet[..4] != SINGLE_HEADER {
+            return Ok(());
+        }
+        self.port = sock.local_addr()?.port();
+
+        let (kind, rest) = (packet[4], &packet[5..]);
+        let challenge = match kind {
+            A2S_INFO if rest.starts_with(INFO_PAYLOAD) => &rest[INFO_PAYLOAD.len()..],
+            A2S_PLAYER | A2S_RULES => rest,
+            _ => return Ok(()),
+        };
+
+        let reply = if self.responder.challenge && !self.valid_challenge(challenge, from) {
+            let mut reply = SINGLE_HEADER.to_vec();
+            reply.push(S2C_CHALLENGE);
+            reply.extend(self.challenge_for(from).to_le_bytes());
+            reply
+        } else {
+            match kind {
+                A2S_INFO => self.info()?,
+                A2S_PLAYER => self.players()?,
+                _ => self.rules()?,
+            }
+        };
+        self.send(sock, reply, from)
+    }
+
+    fn challenge_for(&self, from: SocketAddr) -> u32 {
+        self.hasher.hash_one(from) as u32
+    }
+
+    fn valid_challenge(&self, challenge: &[u8], from: SocketAddr) -> bool {
+        challenge.len() >= 4 && challenge[..4] == self.challenge_for(from).to_le_bytes()
+    }
+
+    // Returns true with the given probability, rolled from the seed
+    fn chance(&self, seed: u64, probability: f64) -> bool {
+        let roll = self.hasher.hash_one(seed) as f64 / u64::MAX as f64;
+        roll < probability
+    }
+
+    fn info(&self) -> Result<Vec<u8>> {
+        let attrs = &self.responder.attrs;
+        let server_type = if attrs.proxy {
+            b'p'
+        } else if attrs.dedicated {
+            b'd'
+        } else {
+            b'l'
+        };
+
+        let mut cursor: Cursor<Vec<u8>> = Cursor::new(SINGLE_HEADER.to_vec());
+        cursor.set_position(SINGLE_HEADER.len() as u64);
+        cursor.write_u8(S2A_INFO)?;
+        cursor.write_u8(17)?; // Protocol version
+        cursor.write_cstring(&attrs.name)?;
+        cursor.write_cstring(&attrs.map)?;
+        cursor.write_cstring(&attrs.gamedir)?;
+        cursor.write_cstring(&self.responder.game)?;
+        cursor.write_u16::<LittleEndian>(attrs.appid as u16)?;
+        cursor.write_u8(attrs.players.min(255) as u8)?;
+        cursor.write_u8(attrs.max_players.min(255) as u8)?;
+        cursor.write_u8(attrs.bots.min(255) as u8)?;
+        cursor.write_u8(server_type)?;
+        cursor.write_u8(if attrs.linux { b'l' } else { b'w' })?;
+        cursor.write_u8(attrs.password as u8)?;
+        cursor.write_u8(attrs.secure as u8)?;
+        cursor.write_cstring(&attrs.version)?;
+
+        // Extra data flag: game port, keywords and game id
+        let keywords = attrs.gametype.join(",");
+        let mut edf = 0x80 | 0x01;
+        if !keywords.is_empty() {
+            edf |= 0x20;
+        }
+        cursor.write_u8(edf)?;
+        cursor.write_u16::<LittleEndian>(self.port)?;
+        if !keywords.is_empty() {
+            cursor.write_cstring(&keywords)?;
+        }
+        cursor.write_u64::<LittleEndian>(attrs.appid as u64)?;
+        Ok(cursor.into_inner())
+    }
+
+    fn players(&self) -> Result<Vec<u8>> {
+        let players = &self.responder.players;
+        let mut cursor: Cursor<Vec<u8>> = Cursor::new(SINGLE_HEADER.to_vec());
+        cursor.set_position(SINGLE_HEADER.len() as u64);
+        cursor.write_u8(S2A_PLAYER)?;
+        cursor.write_u8(players.len().min(255) as u8)?;
+        for (index, (name, score, duration)) in players.iter().take(255).enumerate() {
+            cursor.write_u8(index as u8)?;
+            cursor.write_cstring(name)?;
+            cursor.write_i32::<LittleEndian>(*score)?;
+            cursor.write_f32::<LittleEndian>(*duration)?;
+        }
+        Ok(cursor.into_inner())
+    }
+
+    fn rules(&self) -> Result<Vec<u8>> {
+        let rules = &self.responder.rules;
+        let mut cursor: Cursor<Vec<u8>> = Cursor::new(SINGLE_HEADER.to_vec());
+        cursor.set_position(SINGLE_HEADER.len() as u64);
+        cursor.write_u8(S2A_RULES)?;
+        cursor.write_u16::<LittleEndian>(rules.len().min(u16::MAX as usize) as u16)?;
+        for (name, value) in rules.iter().take(u16::MAX as usize) {
+            cursor.write_cstring(name)?;
+            cursor.write_cstring(value)?;
+        }
+        Ok(cursor.into_inner())
+    }
+
+    fn send(&mut self, sock: &UdpSocket, mut reply: Vec<u8>, from: SocketAddr) -> Result<()> {
+        let id = self.replies;
+        self.replies = self.replies.wrapping_add(1);
+        if self.chance((id as u64) << 1, self.responder.loss) {
+            return Ok(());
+        }
+        if self.chance((id as u64) << 1 | 1, self.responder.malformed) {
+            reply.truncate(reply.len().div_ceil(2));
+        }
+
+        let packets = split(reply, id, self.responder.split_size)?;
+        match self.responder.latency {
+            Some(latency) => {
+                let sock = sock.try_clone()?;
+                thread::spawn(move || {
+                    thread::sleep(latency);
+                    for packet in packets {
+                        let _ = sock.send_to(&packet, from);
+                    }
+                });
+            }
+            None => {
+                for packet in packets {
+                    sock.send_to(&packet, from)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Split a reply into the packets of the Source split packet format
+fn split(reply: Vec<u8>, id: u32, split_size: usize) -> Result<Vec<Vec<u8>>> {
+    if reply.len() <= split_size {
+        return Ok(vec![reply]);
+    }
+
+    let chunks: Vec<&[u8]> = reply.chunks(split_size - 12).collect();
+    if chunks.len() > u8::MAX as usize {
+        return Err(Error::other("Reply too large to split"));
+    }
+    let mut packets = vec![];
+    for (number, chunk) in chunks.iter().enumerate() {
+        let mut cursor: Cursor<Vec<u8>> = Cursor::new(SPLIT_HEADER.to_vec());
+        cursor.set_position(SPLIT_HEADER.len() as u64);
+        // The highest bit of the id marks compressed payloads
+        cursor.write_u32::<LittleEndian>(id & 0x7FFF_FFFF)?;
+        cursor.write_u8(chunks.len() as u8)?;
+        cursor.write_u8(number as u8)?;
+        cursor.write_u16::<LittleEndian>(split_size as u16)?;
+        let mut packet = cursor.into_inner();
+        packet.extend_from_slice(chunk);
+        packets.push(packet);
+    }
+    Ok(packets)
+}
@@ -0,0 +1,177 @@
+use crate::a2s_packet::{decode_info, decode_players, decode_rules, A2SKind, Exchange, Step};
+use crate::a2s_packet::{PlayerInfo, ServerInfo, ServerRule};
+use crate::socks5::{encode_datagram, unwrap_relayed, Socks5Proxy};
+
+use std::io::{Error, ErrorKind, Result};
+use std::net::{SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+use tokio::net::{lookup_host, UdpSocket};
+
+/// A2S client (async) - Probes game servers with A2S queries
+///
+/// * Requires feature: `async` (Turned **on** by default)
+/// * Intended to be used with the addresses returned by
+///   [`MSQClient`](crate::MSQClient).
+/// * Answers challenges and reassembles split replies on its own.
+/// * Can probe through a SOCKS5 proxy with [`with_proxy`](#method.with_proxy).
+/// * The non-async/blocking version of this: [`A2SClientBlock`](crate::A2SClientBlock)
+///
+/// ## Quick Start
+/// ```rust,no_run
+/// use msq::A2SClient;
+/// use std::io::Result;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let mut client = A2SClient::new().await?;
+///     let info = client.info("216.52.143.114:27015").await?;
+///     println!("{} on {} ({}/{})", info.name, info.map, info.players, info.max_players);
+///     Ok(())
+/// }
+/// ```
+pub struct A2SClient {
+    sock: UdpSocket,
+    proxy: Option<Socks5Relay>,
+    timeout: Duration,
+}
+
+// The UDP association the socket's datagrams go through
+struct Socks5Relay {
+    _control: TcpStream,
+    relay: SocketAddr,
+}
+
+impl A2SClient {
+    /// Create a new A2SClient variable and binds the UDP socket to `0.0.0.0:0`
+    pub async fn new() -> Result<A2SClient> {
+        let sock = UdpSocket::bind("0.0.0.0:0").await?;
+        Ok(A2SClient {
+            sock,
+            proxy: None,
+            timeout: Duration::from_secs(3),
+        })
+    }
+
+    /// Create a new A2SClient variable sending its requests through a UDP
+    /// association of the given SOCKS5 proxy
+    ///
+    /// # Arguments
+    /// * `proxy` - [`Socks5Proxy`] to tunnel the requests through
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use msq::{A2SClient, Socks5Proxy};
+    /// use std::io::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let mut client = A2SClient::with_proxy(&Socks5Proxy::new("10.0.0.1:1080")).await?;
+    ///     let info = client.info("216.52.143.114:27015").await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn with_proxy(proxy: &Socks5Proxy) -> Result<A2SClient> {
+        let proxy = proxy.clone();
+        let (control, sock, relay) = tokio::task::spawn_blocking(move || proxy.associate())
+            .await
+            .map_err(Error::other)??
+            .into_parts();
+        sock.set_nonblocking(true)?;
+        Ok(A2SClient {
+            sock: UdpSocket::from_std(sock)?,
+            proxy: Some(Socks5Relay { _control: control, relay }),
+            timeout: Duration::from_secs(3),
+        })
+    }
+
+    /// Set how long to wait for the reply of a request (Defaults to 3 seconds)
+    ///
+    /// # Arguments
+    /// * `timeout` - Time until a request fails, including its challenge
+    pub fn timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Send an A2S_INFO request to a game server
+    ///
+    /// # Arguments
+    /// * `addr` - The game server's address (EX: `216.52.143.114:27015`)
+    pub async fn info(&mut self, addr: &str) -> Result<ServerInfo> {
+        let (payload, ping) = self.exchange(addr, A2SKind::Info).await?;
+        decode_info(payload, ping)
+    }
+
+    /// Send an A2S_PLAYER request to a game server
+    ///
+    /// # Arguments
+    /// * `addr` - The game server's address (EX: `216.52.143.114:27015`)
+    pub async fn players(&mut self, addr: &str) -> Result<Vec<PlayerInfo>> {
+        let (payload, _) = self.exchange(addr, A2SKind::Players).await?;
+        decode_players(payload)
+    }
+
+    /// Send an A2S_RULES request to a game server
+    ///
+    /// # Arguments
+    /// * `addr` - The game server's address (EX: `216.52.143.114:27015`)
+    pub async fn rules(&mut self, addr: &str) -> Result<Vec<ServerRule>> {
+        let (payload, _) = self.exchange(addr, A2SKind::Rules).await?;
+        decode_rules(payload)
+    }
+
+    async fn exchange(&mut self, addr: &str, kind: A2SKind) -> Result<(Vec<u8>, Duration)> {
+        let target: SocketAddr = lookup_host(addr)
+            .await?
+            .next()
+            .ok_or_else(|| Error::other(format!("Invalid server address: {}", addr)))?;
+        let deadline = Instant::now() + self.timeout;
+        let mut exchange = Exchange::new(kind);
+
+        self.send_to(&exchange.request(None), target).await?;
+        let mut sent = Instant::now();
+        let mut buf: [u8; 2048] = [0x00; 2048];
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let (len, from) = tokio::time::timeout(remaining, self.recv_from(&mut buf))
+                .await
+                .map_err(|_| Error::new(ErrorKind::TimedOut, "A2S request timed out"))??;
+            // Late replies of other servers
+            if from != target {
+                continue;
+            }
+
+            match exchange.handle(&buf[..len])? {
+                Step::Resend(request) => {
+                    self.send_to(&request, target).await?;
+                    sent = Instant::now();
+                }
+                Step::Wait => (),
+                Step::Done(payload) => return Ok((payload, sent.elapsed())),
+            }
+        }
+    }
+
+    async fn send_to(&self, buf: &[u8], target: SocketAddr) -> Result<usize> {
+        match &self.proxy {
+            Some(proxy) => {
+                self.sock.send_to(&encode_datagram(target, buf), proxy.relay).await?;
+                Ok(buf.len())
+            }
+            None => self.sock.send_to(buf, target).await,
+        }
+    }
+
+    async fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr)> {
+        let proxy = match &self.proxy {
+            Some(proxy) => proxy,
+            None => return self.sock.recv_from(buf).await,
+        };
+        let mut packet = [0u8; 2048];
+        loop {
+            let (len, from) = self.sock.recv_from(&mut packet).await?;
+            if let Some(received) = unwrap_relayed(&packet[..len], from, proxy.relay, buf) {
+                return Ok(received);
+            }
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use crate::a2s_packet::{decode_info, decode_players, decode_rules, A2SKind, Exchange, Step};
+use crate::a2s_packet::{PlayerInfo, ServerInfo, ServerRule};
+use crate::socks5::{Socks5Proxy, Socks5UdpSocket};
+
+use std::io::{Error, ErrorKind, Result};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+/// A2S client (non-async) - Probes game servers with A2S queries
+///
+/// * Requires feature: `non-async` (Turned **on** by default)
+/// * Intended to be used with the addresses returned by
+///   [`MSQClientBlock`](crate::MSQClientBlock).
+/// * Answers challenges and reassembles split replies on its own.
+/// * Can probe through a SOCKS5 proxy with [`with_proxy`](#method.with_proxy).
+/// * The async version of this: [`A2SClient`](crate::A2SClient)
+///
+/// ## Quick Start
+/// ```rust,no_run
+/// use msq::A2SClientBlock;
+/// use std::io::Result;
+///
+/// fn main() -> Result<()> {
+///     let mut client = A2SClientBlock::new()?;
+///     let info = client.info("216.52.143.114:27015")?;
+///     println!("{} on {} ({}/{})", info.name, info.map, info.players, info.max_players);
+///     Ok(())
+/// }
+/// ```
+pub struct A2SClientBlock {
+    sock: A2SSocket,
+    timeout: Duration,
+}
+
+enum A2SSocket {
+    Udp(UdpSocket),
+    Socks5(Socks5UdpSocket),
+}
+
+impl A2SSocket {
+    fn send_to(&self, buf: &[u8], target: SocketAddr) -> Result<usize> {
+        match self {
+            A2SSocket::Udp(sock) => sock.send_to(buf, target),
+            A2SSocket::Socks5(sock) => sock.send_to(buf, target),
+        }
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr)> {
+        match self {
+            A2SSocket::Udp(sock) => sock.recv_from(buf),
+            A2SSocket::Socks5(sock) => sock.recv_from(buf),
+        }
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        match self {
+            A2SSocket::Udp(sock) => sock.set_read_timeout(timeout),
+            A2SSocket::Socks5(sock) => sock.set_read_timeout(timeout),
+        }
+    }
+}
+
+impl A2SClientBlock {
+    /// Create a new A2SClientBlock variable and binds the UDP socket to `0.0.0.0:0`
+    pub fn new() -> Result<Self> {
+        let sock = UdpSocket::bind("0.0.0.0:0")?;
+        Ok(Self {
+            sock: A2SSocket::Udp(sock),
+            timeout: Duration::from_secs(3),
+        })
+    }
+
+    /// Create a new A2SClientBlock variable sending its requests through a
+    /// UDP association of the given SOCKS5 proxy
+    ///
+    /// # Arguments
+    /// * `proxy` - [`Socks5Proxy`] to tunnel the requests through
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use msq::{A2SClientBlock, Socks5Proxy};
+    /// use std::io::Result;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut client = A2SClientBlock::with_proxy(&Socks5Proxy::new("10.0.0.1:1080"))?;
+    ///     let info = client.info("216.52.143.114:27015")?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_proxy(proxy: &Socks5Proxy) -> Result<Self> {
+        Ok(Self {
+            sock: A2SSocket::Socks5(proxy.associate()?),
+            timeout: Duration::from_secs(3),
+        })
+    }
+
+    /// Set how long to wait for the reply of a request (Defaults to 3 seconds)
+    ///
+    /// # Arguments
+    /// * `timeout` - Time until a request fails, including its challenge
+    pub fn timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Send an A2S_INFO request to a game server
+    ///
+    /// # Arguments
+    /// * `addr` - The game server's address (EX: `216.52.143.114:27015`)
+    pub fn info(&mut self, addr: &str) -> Result<ServerInfo> {
+        let (payload, ping) = self.exchange(addr, A2SKind::Info)?;
+        decode_info(payload, ping)
+    }
+
+    /// Send an A2S_PLAYER request to a game server
+    ///
+    /// # Arguments
+    /// * `addr` - The game server's address (EX: `216.52.143.114:27015`)
+    pub fn players(&mut self, addr: &str) -> Result<Vec<PlayerInfo>> {
+        let (payload, _) = self.exchange(addr, A2SKind::Players)?;
+        decode_players(payload)
+    }
+
+    /// Send an A2S_RULES request to a game server
+    ///
+    /// # Arguments
+    /// * `addr` - The game server's address (EX: `216.52.143.114:27015`)
+    pub fn rules(&mut self, addr: &str) -> Result<Vec<ServerRule>> {
+        let (payload, _) = self.exchange(addr, A2SKind::Rules)?;
+        decode_rules(payload)
+    }
+
+    fn exchange(&mut self, addr: &str, kind: A2SKind) -> Result<(Vec<u8>, Duration)> {
+        let target: SocketAddr = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| Error::other(format!("Invalid server address: {}", addr)))?;
+        let deadline = Instant::now() + self.timeout;
+        let mut exchange = Exchange::new(kind);
+
+        self.sock.send_to(&exchange.request(None), target)?;
+        let mut sent = Instant::now();
+        let mut buf: [u8; 2048] = [0x00; 2048];
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(Error::new(ErrorKind::TimedOut, "A2S request timed out"));
+            }
+            self.sock.set_read_timeout(Some(remaining))?;
+            let (len, from) = match self.sock.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => continue,
+                Err(e) => return Err(e),
+            };
+            // Late replies of other servers
+            if from != target {
+                continue;
+            }
+
+            match exchange.handle(&buf[..len])? {
+                Step::Resend(request) => {
+                    self.sock.send_to(&request, target)?;
+                    sent = Instant::now();
+                }
+                Step::Wait => (),
+                Step::Done(payload) => return Ok((payload, sent.elapsed())),
+            }
+        }
+    }
+}
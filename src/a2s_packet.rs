@@ -0,0 +1,312 @@
+#[cfg(any(feature = "async", feature = "non-async"))]
+use crate::packet_ext::ReadPacketExt;
+
+#[cfg(any(feature = "async", feature = "non-async"))]
+use byteorder::{LittleEndian, ReadBytesExt};
+#[cfg(any(feature = "async", feature = "non-async"))]
+use std::collections::BTreeMap;
+#[cfg(any(feature = "async", feature = "non-async"))]
+use std::io::{Cursor, Error, Result};
+use std::time::Duration;
+
+#[cfg(any(feature = "async", feature = "non-async", feature = "server"))]
+pub(crate) const SINGLE_HEADER: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+#[cfg(any(feature = "async", feature = "non-async", feature = "server"))]
+pub(crate) const SPLIT_HEADER: [u8; 4] = [0xFE, 0xFF, 0xFF, 0xFF];
+#[cfg(any(feature = "async", feature = "non-async", feature = "server"))]
+pub(crate) const INFO_PAYLOAD: &[u8] = b"Source Engine Query\0";
+#[cfg(any(feature = "async", feature = "non-async"))]
+const CHALLENGE_RETRIES: u8 = 3;
+
+/// Reply of an A2S_INFO request
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerInfo {
+    /// Protocol version of the server
+    pub protocol: u8,
+    /// The server's hostname
+    pub name: String,
+    /// The map the server runs (EX: `de_dust2`)
+    pub map: String,
+    /// The modification the server runs (EX: `cstrike`)
+    pub folder: String,
+    /// Full name of the game (EX: `Counter-Strike: Source`)
+    pub game: String,
+    /// Appid of the game, from the game id if the server sent one
+    pub appid: u32,
+    /// Amount of players on the server
+    pub players: u8,
+    /// Maximum amount of players on the server
+    pub max_players: u8,
+    /// Amount of bots on the server
+    pub bots: u8,
+    /// Server is dedicated
+    pub dedicated: bool,
+    /// Server is a spectator proxy
+    pub proxy: bool,
+    /// Server runs on Linux
+    pub linux: bool,
+    /// Server is password protected
+    pub password: bool,
+    /// Server uses anti-cheat technology
+    pub secure: bool,
+    /// The server's version
+    pub version: String,
+    /// The server's game port, if it sent one
+    pub port: Option<u16>,
+    /// Tags of the server in sv_tags
+    pub keywords: Vec<String>,
+    /// Time between sending the request and receiving the reply
+    pub ping: Duration,
+}
+
+/// A player in the reply of an A2S_PLAYER request
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerInfo {
+    /// Index of the player in the reply
+    pub index: u8,
+    /// The player's name
+    pub name: String,
+    /// The player's score
+    pub score: i32,
+    /// Time the player has been connected
+    pub duration: Duration,
+}
+
+/// A rule (console variable) in the reply of an A2S_RULES request
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerRule {
+    /// The rule's name (EX: `mp_friendlyfire`)
+    pub name: String,
+    /// The rule's value
+    pub value: String,
+}
+
+#[cfg(any(feature = "async", feature = "non-async"))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum A2SKind {
+    Info,
+    Players,
+    Rules,
+}
+
+#[cfg(any(feature = "async", feature = "non-async"))]
+impl A2SKind {
+    fn reply_type(&self) -> u8 {
+        match self {
+            Self::Info => 0x49,
+            Self::Players => 0x44,
+            Self::Rules => 0x45,
+        }
+    }
+}
+
+/// What to do after receiving a datagram of an A2S exchange
+#[cfg(any(feature = "async", feature = "non-async"))]
+pub(crate) enum Step {
+    /// Send the request again, with the challenge of the server
+    Resend(Vec<u8>),
+    /// Keep waiting for the rest of the reply
+    Wait,
+    /// The payload of the complete reply, after its type byte
+    Done(Vec<u8>),
+}
+
+/// State of a single A2S request/reply exchange, shared by both A2S clients
+#[cfg(any(feature = "async", feature = "non-async"))]
+pub(crate) struct Exchange {
+    kind: A2SKind,
+    challenges: u8,
+    split_id: Option<u32>,
+    parts: BTreeMap<u8, Vec<u8>>,
+}
+
+#[cfg(any(feature = "async", feature = "non-async"))]
+impl Exchange {
+    pub fn new(kind: A2SKind) -> Exchange {
+        Exchange {
+            kind,
+            challenges: 0,
+            split_id: None,
+            parts: BTreeMap::new(),
+        }
+    }
+
+    /// Encode the request, with the given challenge or without one
+    pub fn request(&self, challenge: Option<[u8; 4]>) -> Vec<u8> {
+        let mut packet = SINGLE_HEADER.to_vec();
+        match self.kind {
+            A2SKind::Info => {
+                packet.push(0x54);
+                packet.extend(INFO_PAYLOAD);
+                if let Some(challenge) = challenge {
+                    packet.extend(challenge);
+                }
+            }
+            A2SKind::Players | A2SKind::Rules => {
+                packet.push(if self.kind == A2SKind::Players { 0x55 } else { 0x56 });
+                packet.extend(challenge.unwrap_or([0xFF; 4]));
+            }
+        }
+        packet
+    }
+
+    pub fn handle(&mut self, datagram: &[u8]) -> Result<Step> {
+        if datagram.len() < 5 {
+            return Ok(Step::Wait);
+        }
+        if datagram[..4] == SPLIT_HEADER {
+            return match self.reassemble(datagram)? {
+                Some(reply) => self.handle(&reply),
+                None => Ok(Step::Wait),
+            };
+        }
+        if datagram[..4] != SINGLE_HEADER {
+            return Err(Error::other("Mismatched starting sequence"));
+        }
+
+        match datagram[4] {
+            0x41 if datagram.len() >= 9 => {
+                self.challenges += 1;
+                if self.challenges > CHALLENGE_RETRIES {
+                    return Err(Error::other("Too many challenges"));
+                }
+                let challenge = [datagram[5], datagram[6], datagram[7], datagram[8]];
+                Ok(Step::Resend(self.request(Some(challenge))))
+            }
+            kind if kind == self.kind.reply_type() => Ok(Step::Done(datagram[5..].to_vec())),
+            0x6D => Err(Error::other("GoldSrc info replies are not supported")),
+            // Some other reply, such as a late one of an earlier request
+            _ => Ok(Step::Wait),
+        }
+    }
+
+    // Collect the parts of a Source split reply, returns the whole reply once
+    // every part arrived
+    fn reassemble(&mut self, datagram: &[u8]) -> Result<Option<Vec<u8>>> {
+        if datagram.len() < 12 {
+            return Err(Error::other("Truncated split packet"));
+        }
+        let id = u32::from_le_bytes([datagram[4], datagram[5], datagram[6], datagram[7]]);
+        if id & 0x8000_0000 != 0 {
+            return Err(Error::other("Compressed split packets are not supported"));
+        }
+        let (total, number) = (datagram[8], datagram[9]);
+        if number >= total {
+            return Err(Error::other("Split packet number out of range"));
+        }
+        if self.split_id != Some(id) {
+            self.split_id = Some(id);
+            self.parts.clear();
+        }
+        self.parts.insert(number, datagram[12..].to_vec());
+
+        if !(0..total).all(|number| self.parts.contains_key(&number)) {
+            return Ok(None);
+        }
+        let reply = std::mem::take(&mut self.parts).into_values().flatten().collect();
+        self.split_id = None;
+        Ok(Some(reply))
+    }
+}
+
+#[cfg(any(feature = "async", feature = "non-async"))]
+pub(crate) fn decode_info(payload: Vec<u8>, ping: Duration) -> Result<ServerInfo> {
+    let mut cursor = Cursor::new(payload);
+    let protocol = cursor.read_u8()?;
+    let name = cursor.read_cstring()?;
+    let map = cursor.read_cstring()?;
+    let folder = cursor.read_cstring()?;
+    let game = cursor.read_cstring()?;
+    let id = cursor.read_u16::<LittleEndian>()?;
+    let players = cursor.read_u8()?;
+    let max_players = cursor.read_u8()?;
+    let bots = cursor.read_u8()?;
+    let server_type = cursor.read_u8()?;
+    let environment = cursor.read_u8()?;
+    let password = cursor.read_u8()? != 0;
+    let secure = cursor.read_u8()? != 0;
+    let version = cursor.read_cstring()?;
+
+    let mut info = ServerInfo {
+        protocol,
+        name,
+        map,
+        folder,
+        game,
+        appid: id as u32,
+        players,
+        max_players,
+        bots,
+        dedicated: server_type == b'd',
+        proxy: server_type == b'p',
+        linux: environment == b'l',
+        password,
+        secure,
+        version,
+        port: None,
+        keywords: vec![],
+        ping,
+    };
+
+    // Extra data flag
+    let edf = match cursor.read_u8() {
+        Ok(edf) => edf,
+        Err(_) => return Ok(info),
+    };
+    if edf & 0x80 != 0 {
+        info.port = Some(cursor.read_u16::<LittleEndian>()?);
+    }
+    if edf & 0x10 != 0 {
+        cursor.read_u64::<LittleEndian>()?; // SteamID
+    }
+    if edf & 0x40 != 0 {
+        cursor.read_u16::<LittleEndian>()?; // SourceTV port
+        cursor.read_cstring()?; // SourceTV name
+    }
+    if edf & 0x20 != 0 {
+        info.keywords = cursor
+            .read_cstring()?
+            .split(',')
+            .filter(|k| !k.is_empty())
+            .map(String::from)
+            .collect();
+    }
+    if edf & 0x01 != 0 {
+        // The lower 24 bits of the game id are the appid
+        info.appid = (cursor.read_u64::<LittleEndian>()? & 0xFF_FFFF) as u32;
+    }
+    Ok(info)
+}
+
+#[cfg(any(feature = "async", feature = "non-async"))]
+pub(crate) fn decode_players(payload: Vec<u8>) -> Result<Vec<PlayerInfo>> {
+    let mut cursor = Cursor::new(payload);
+    let count = cursor.read_u8()?;
+    let mut players = vec![];
+    for _ in 0..count {
+        let index = cursor.read_u8()?;
+        let name = cursor.read_cstring()?;
+        let score = cursor.read_i32::<LittleEndian>()?;
+        let duration = cursor.read_f32::<LittleEndian>()?;
+        players.push(PlayerInfo {
+            index,
+            name,
+            score,
+            duration: Duration::try_from_secs_f32(duration).unwrap_or_default(),
+        });
+    }
+    Ok(players)
+}
+
+#[cfg(any(feature = "async", feature = "non-async"))]
+pub(crate) fn decode_rules(payload: Vec<u8>) -> Result<Vec<ServerRule>> {
+    let mut cursor = Cursor::new(payload);
+    let count = cursor.read_u16::<LittleEndian>()?;
+    let mut rules = vec![];
+    for _ in 0..count {
+        let name = cursor.read_cstring()?;
+        let value = cursor.read_cstring()?;
+        rules.push(ServerRule { name, value });
+    }
+    Ok(rules)
+}
@@ -0,0 +1,187 @@
+use crate::region::Region;
+
+/// Attributes of a game server, as known by a master server
+///
+/// * Intended to be used with: [`MockMasterServer`](crate::MockMasterServer),
+///   [`MasterServer`](crate::MasterServer) and [`A2SResponder`](crate::A2SResponder)
+/// * Built the same way as a [`Filter`](crate::Filter), each method sets
+///   one attribute. Attributes that are not set keep their default.
+///
+/// # Quick Start
+/// ```rust
+/// use msq::{ServerAttrs, Region};
+///
+/// let attrs = ServerAttrs::new()
+///         .appid(240)
+///         .region(Region::Europe)
+///         .map("de_dust2")
+///         .players(12, 24)
+///         .gametype(&["friendlyfire", "alltalk"]);
+/// ```
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerAttrs {
+    /// Region the server is in, defaults to [`Region::All`]
+    pub region: Region,
+    /// Appid of the game the server runs
+    pub appid: u32,
+    /// The modification the server runs (EX: `cstrike`)
+    pub gamedir: String,
+    /// The map the server runs (EX: `de_dust2`)
+    pub map: String,
+    /// The server's hostname
+    pub name: String,
+    /// The server's version
+    pub version: String,
+    /// Amount of players on the server
+    pub players: u32,
+    /// Maximum amount of players on the server
+    pub max_players: u32,
+    /// Amount of bots on the server
+    pub bots: u32,
+    /// Server is dedicated
+    pub dedicated: bool,
+    /// Server uses anti-cheat technology
+    pub secure: bool,
+    /// Server runs on Linux
+    pub linux: bool,
+    /// Server is password protected
+    pub password: bool,
+    /// Server is a spectator proxy
+    pub proxy: bool,
+    /// Server is whitelisted
+    pub whitelisted: bool,
+    /// Tags of the server in sv_tags
+    pub gametype: Vec<String>,
+    /// Hidden tags of the server (L4D2)
+    pub gamedata: Vec<String>,
+}
+
+impl Default for ServerAttrs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ServerAttrs {
+    /// Returns a new ServerAttrs struct, in [`Region::All`] with every other
+    /// attribute empty/zero/`false`
+    pub fn new() -> ServerAttrs {
+        ServerAttrs {
+            region: Region::All,
+            appid: 0,
+            gamedir: String::from(""),
+            map: String::from(""),
+            name: String::from(""),
+            version: String::from(""),
+            players: 0,
+            max_players: 0,
+            bots: 0,
+            dedicated: false,
+            secure: false,
+            linux: false,
+            password: false,
+            proxy: false,
+            whitelisted: false,
+            gametype: vec![],
+            gamedata: vec![],
+        }
+    }
+
+    /// Set the region the server is in
+    pub fn region(mut self, region: Region) -> ServerAttrs {
+        self.region = region;
+        self
+    }
+
+    /// Set the appid of the game the server runs
+    pub fn appid(mut self, appid: u32) -> ServerAttrs {
+        self.appid = appid;
+        self
+    }
+
+    /// Set the modification the server runs (EX: `cstrike`)
+    pub fn gamedir(mut self, gamedir: &str) -> ServerAttrs {
+        self.gamedir = String::from(gamedir);
+        self
+    }
+
+    /// Set the map the server runs (EX: `de_dust2`)
+    pub fn map(mut self, map: &str) -> ServerAttrs {
+        self.map = String::from(map);
+        self
+    }
+
+    /// Set the server's hostname
+    pub fn name(mut self, name: &str) -> ServerAttrs {
+        self.name = String::from(name);
+        self
+    }
+
+    /// Set the server's version
+    pub fn version(mut self, version: &str) -> ServerAttrs {
+        self.version = String::from(version);
+        self
+    }
+
+    /// Set the amount of players and maximum amount of players
+    pub fn players(mut self, players: u32, max_players: u32) -> ServerAttrs {
+        self.players = players;
+        self.max_players = max_players;
+        self
+    }
+
+    /// Set the amount of bots
+    pub fn bots(mut self, bots: u32) -> ServerAttrs {
+        self.bots = bots;
+        self
+    }
+
+    /// Set if the server is dedicated
+    pub fn dedicated(mut self, is_dedicated: bool) -> ServerAttrs {
+        self.dedicated = is_dedicated;
+        self
+    }
+
+    /// Set if the server uses anti-cheat technology
+    pub fn secure(mut self, hasac: bool) -> ServerAttrs {
+        self.secure = hasac;
+        self
+    }
+
+    /// Set if the server runs on Linux
+    pub fn linux(mut self, runslinux: bool) -> ServerAttrs {
+        self.linux = runslinux;
+        self
+    }
+
+    /// Set if the server is password protected
+    pub fn password(mut self, protected: bool) -> ServerAttrs {
+        self.password = protected;
+        self
+    }
+
+    /// Set if the server is a spectator proxy
+    pub fn proxy(mut self, specprox: bool) -> ServerAttrs {
+        self.proxy = specprox;
+        self
+    }
+
+    /// Set if the server is whitelisted
+    pub fn whitelisted(mut self, white: bool) -> ServerAttrs {
+        self.whitelisted = white;
+        self
+    }
+
+    /// Set the tags of the server in sv_tags
+    pub fn gametype(mut self, tags: &[&str]) -> ServerAttrs {
+        self.gametype = tags.iter().map(|t| String::from(*t)).collect();
+        self
+    }
+
+    /// Set the hidden tags of the server (L4D2)
+    pub fn gamedata(mut self, tags: &[&str]) -> ServerAttrs {
+        self.gamedata = tags.iter().map(|t| String::from(*t)).collect();
+        self
+    }
+}
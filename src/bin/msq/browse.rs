@@ -0,0 +1,453 @@
+//! `msq browse` - Terminal server browser
+//!
+//! * Requires feature: `tui` (Turned **off** by default)
+
+use crate::output::Row;
+use crate::{probe_all, search, BrowseArgs};
+
+use msq::{A2SClient, PlayerInfo, ServerInfo, ServerRule};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row as TableRow, Table, TableState};
+use ratatui::Frame;
+use std::cmp::Ordering;
+use std::io::Result;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+const KEYS: &str = "/ filter  s sort  S reverse  Enter details  r refresh  q quit";
+
+enum Message {
+    Key(KeyEvent),
+    Refreshed(Result<Vec<Row>>),
+    Detail(String, Fetched<Vec<PlayerInfo>>, Fetched<Vec<ServerRule>>),
+}
+
+// Players or rules of the detail pane, with the error message if they failed
+type Fetched<T> = std::result::Result<T, String>;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SortColumn {
+    Name,
+    Map,
+    Players,
+    Ping,
+}
+
+impl SortColumn {
+    fn next(self) -> SortColumn {
+        match self {
+            SortColumn::Name => SortColumn::Map,
+            SortColumn::Map => SortColumn::Players,
+            SortColumn::Players => SortColumn::Ping,
+            SortColumn::Ping => SortColumn::Name,
+        }
+    }
+
+    fn compare(self, a: &ServerInfo, b: &ServerInfo) -> Ordering {
+        match self {
+            SortColumn::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            SortColumn::Map => a.map.to_lowercase().cmp(&b.map.to_lowercase()),
+            // Most players first
+            SortColumn::Players => b.players.cmp(&a.players),
+            SortColumn::Ping => a.ping.cmp(&b.ping),
+        }
+    }
+}
+
+struct Detail {
+    addr: String,
+    players: Option<Fetched<Vec<PlayerInfo>>>,
+    rules: Option<Fetched<Vec<ServerRule>>>,
+}
+
+struct Browser {
+    rows: Vec<Row>,
+    // Indices of the shown rows, filtered and sorted
+    view: Vec<usize>,
+    table: TableState,
+    sort: SortColumn,
+    reverse: bool,
+    filter: String,
+    editing: bool,
+    detail: Option<Detail>,
+    refreshing: bool,
+    refreshed: Option<Instant>,
+    error: Option<String>,
+}
+
+/// Run the browser until it is quit
+pub async fn run(args: BrowseArgs) -> Result<()> {
+    let (sender, mut messages) = unbounded_channel();
+    let input = sender.clone();
+    std::thread::spawn(move || read_keys(input));
+
+    let mut terminal = ratatui::init();
+    let mut browser = Browser::new();
+    browser.refresh(&args, &sender);
+    let mut tick = tokio::time::interval(Duration::from_secs(1));
+    let mut next_refresh = refresh_deadline(&args);
+
+    let result = loop {
+        if let Err(e) = terminal.draw(|frame| browser.draw(frame)) {
+            break Err(e);
+        }
+        tokio::select! {
+            message = messages.recv() => match message {
+                Some(Message::Key(key)) => {
+                    if !browser.key(key, &args, &sender) {
+                        break Ok(());
+                    }
+                }
+                Some(Message::Refreshed(rows)) => {
+                    browser.refreshed(rows);
+                    next_refresh = refresh_deadline(&args);
+                }
+                Some(Message::Detail(addr, players, rules)) => browser.detailed(&addr, players, rules),
+                None => break Ok(()),
+            },
+            _ = tick.tick() => {
+                if next_refresh.is_some_and(|deadline| Instant::now() >= deadline) {
+                    browser.refresh(&args, &sender);
+                    next_refresh = None;
+                }
+            }
+        }
+    };
+    ratatui::restore();
+    result
+}
+
+fn refresh_deadline(args: &BrowseArgs) -> Option<Instant> {
+    (args.refresh > 0).then(|| Instant::now() + Duration::from_secs(args.refresh))
+}
+
+// Forward key presses until the browser is gone
+fn read_keys(sender: UnboundedSender<Message>) {
+    while !sender.is_closed() {
+        match event::poll(Duration::from_millis(200)) {
+            Ok(true) => {
+                if let Ok(Event::Key(key)) = event::read() {
+                    if key.kind == KeyEventKind::Press && sender.send(Message::Key(key)).is_err() {
+                        return;
+                    }
+                }
+            }
+            Ok(false) => (),
+            Err(_) => return,
+        }
+    }
+}
+
+impl Browser {
+    fn new() -> Browser {
+        Browser {
+            rows: vec![],
+            view: vec![],
+            table: TableState::default(),
+            sort: SortColumn::Players,
+            reverse: false,
+            filter: String::new(),
+            editing: false,
+            detail: None,
+            refreshing: false,
+            refreshed: None,
+            error: None,
+        }
+    }
+
+    fn refresh(&mut self, args: &BrowseArgs, sender: &UnboundedSender<Message>) {
+        if self.refreshing {
+            return;
+        }
+        self.refreshing = true;
+        let search_args = args.search.clone();
+        let probe_options = args.probe_options.clone();
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            let rows = match search(&search_args).await {
+                Ok(servers) => Ok(probe_all(servers, &probe_options).await),
+                Err(e) => Err(e),
+            };
+            let _ = sender.send(Message::Refreshed(rows));
+        });
+    }
+
+    fn refreshed(&mut self, rows: Result<Vec<Row>>) {
+        self.refreshing = false;
+        match rows {
+            Ok(rows) => {
+                let selected = self.selected().map(|row| row.addr.clone());
+                self.rows = rows;
+                self.refreshed = Some(Instant::now());
+                self.error = None;
+                self.update_view(selected);
+            }
+            // Keep showing the last results
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+
+    fn selected(&self) -> Option<&Row> {
+        self.table
+            .selected()
+            .and_then(|i| self.view.get(i))
+            .map(|i| &self.rows[*i])
+    }
+
+    // Filter and sort the rows, keeping the selected server selected
+    fn update_view(&mut self, selected: Option<String>) {
+        let filter = self.filter.to_lowercase();
+        let mut view: Vec<usize> = (0..self.rows.len())
+            .filter(|i| {
+                let row = &self.rows[*i];
+                filter.is_empty()
+                    || row.addr.contains(&filter)
+                    || row.info().is_some_and(|info| {
+                        info.name.to_lowercase().contains(&filter)
+                            || info.map.to_lowercase().contains(&filter)
+                    })
+            })
+            .collect();
+        view.sort_by(|a, b| {
+            // Servers that failed their probe go last
+            match (self.rows[*a].info(), self.rows[*b].info()) {
+                (Some(a), Some(b)) => match self.reverse {
+                    true => self.sort.compare(b, a),
+                    false => self.sort.compare(a, b),
+                },
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        });
+        self.view = view;
+
+        let position = selected.and_then(|addr| self.view.iter().position(|i| self.rows[*i].addr == addr));
+        self.table.select(match (position, self.view.is_empty()) {
+            (Some(position), _) => Some(position),
+            (None, true) => None,
+            (None, false) => Some(0),
+        });
+    }
+
+    fn update_view_keep_selection(&mut self) {
+        let selected = self.selected().map(|row| row.addr.clone());
+        self.update_view(selected);
+    }
+
+    // Returns false to quit
+    fn key(&mut self, key: KeyEvent, args: &BrowseArgs, sender: &UnboundedSender<Message>) -> bool {
+        if self.editing {
+            match key.code {
+                KeyCode::Enter => self.editing = false,
+                KeyCode::Esc => {
+                    self.editing = false;
+                    self.filter.clear();
+                }
+                KeyCode::Backspace => {
+                    self.filter.pop();
+                }
+                KeyCode::Char(c) => self.filter.push(c),
+                _ => return true,
+            }
+            self.update_view_keep_selection();
+            return true;
+        }
+
+        match key.code {
+            KeyCode::Char('q') => return false,
+            KeyCode::Esc if self.detail.is_some() => self.detail = None,
+            KeyCode::Esc => return false,
+            KeyCode::Down | KeyCode::Char('j') => self.table.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => self.table.select_previous(),
+            KeyCode::PageDown => self.table.scroll_down_by(20),
+            KeyCode::PageUp => self.table.scroll_up_by(20),
+            KeyCode::Home | KeyCode::Char('g') => self.table.select_first(),
+            KeyCode::End | KeyCode::Char('G') => self.table.select_last(),
+            KeyCode::Char('/') => self.editing = true,
+            KeyCode::Char('s') => {
+                self.sort = self.sort.next();
+                self.update_view_keep_selection();
+            }
+            KeyCode::Char('S') => {
+                self.reverse = !self.reverse;
+                self.update_view_keep_selection();
+            }
+            KeyCode::Char('r') => self.refresh(args, sender),
+            KeyCode::Enter => self.open_detail(args, sender),
+            _ => (),
+        }
+        true
+    }
+
+    fn open_detail(&mut self, args: &BrowseArgs, sender: &UnboundedSender<Message>) {
+        let Some(addr) = self.selected().map(|row| row.addr.clone()) else {
+            return;
+        };
+        self.detail = Some(Detail {
+            addr: addr.clone(),
+            players: None,
+            rules: None,
+        });
+        let timeout = Duration::from_millis(args.probe_options.probe_timeout);
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            let (players, rules) = match A2SClient::new().await {
+                Ok(mut client) => {
+                    client.timeout(timeout);
+                    let players = client.players(&addr).await.map_err(|e| e.to_string());
+                    let rules = client.rules(&addr).await.map_err(|e| e.to_string());
+                    (players, rules)
+                }
+                Err(e) => (Err(e.to_string()), Err(e.to_string())),
+            };
+            let _ = sender.send(Message::Detail(addr, players, rules));
+        });
+    }
+
+    fn detailed(&mut self, addr: &str, players: Fetched<Vec<PlayerInfo>>, rules: Fetched<Vec<ServerRule>>) {
+        // The detail pane may show another server by now
+        if let Some(detail) = self.detail.as_mut().filter(|d| d.addr == addr) {
+            detail.players = Some(players);
+            detail.rules = Some(rules);
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let detail_height = if self.detail.is_some() { 14 } else { 0 };
+        let [table_area, detail_area, status_area] = Layout::vertical([
+            Constraint::Min(3),
+            Constraint::Length(detail_height),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        self.draw_table(frame, table_area);
+        if self.detail.is_some() {
+            self.draw_detail(frame, detail_area);
+        }
+        frame.render_widget(Paragraph::new(self.status()), status_area);
+    }
+
+    fn draw_table(&mut self, frame: &mut Frame, area: Rect) {
+        let arrow = if self.reverse { " ^" } else { " v" };
+        let header = [
+            ("Name", Some(SortColumn::Name)),
+            ("Map", Some(SortColumn::Map)),
+            ("Players", Some(SortColumn::Players)),
+            ("Ping", Some(SortColumn::Ping)),
+            ("Address", None),
+        ]
+        .map(|(title, column)| match column == Some(self.sort) {
+            true => Cell::from(format!("{}{}", title, arrow)),
+            false => Cell::from(title),
+        });
+
+        let rows = self.view.iter().map(|i| {
+            let row = &self.rows[*i];
+            let cells = match (row.info(), &row.probe) {
+                (Some(info), _) => vec![
+                    info.name.clone(),
+                    info.map.clone(),
+                    format!("{}/{}", info.players, info.max_players),
+                    format!("{} ms", info.ping.as_millis()),
+                ],
+                (None, Some(Err(e))) => vec![format!("error: {}", e), String::new(), String::new(), String::new()],
+                (None, _) => vec![String::new(); 4],
+            };
+            TableRow::new(cells.into_iter().chain([row.addr.clone()]))
+        });
+
+        let title = match self.refreshing {
+            true => " msq - refreshing... ",
+            false => " msq ",
+        };
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Fill(3),
+                Constraint::Fill(1),
+                Constraint::Length(9),
+                Constraint::Length(8),
+                Constraint::Length(21),
+            ],
+        )
+        .header(TableRow::new(header).style(Style::new().add_modifier(Modifier::BOLD)))
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+        .block(Block::new().borders(Borders::ALL).title(title));
+        frame.render_stateful_widget(table, area, &mut self.table);
+    }
+
+    fn draw_detail(&self, frame: &mut Frame, area: Rect) {
+        let Some(detail) = &self.detail else {
+            return;
+        };
+        let info = self
+            .rows
+            .iter()
+            .find(|row| row.addr == detail.addr)
+            .and_then(|row| row.info());
+        let title = match info {
+            Some(info) => format!(
+                " {} - {} {} ({}, {} bots{}{}) ",
+                info.name,
+                info.game,
+                info.version,
+                detail.addr,
+                info.bots,
+                if info.password { ", password" } else { "" },
+                if info.secure { ", secure" } else { "" },
+            ),
+            None => format!(" {} ", detail.addr),
+        };
+        let block = Block::new().borders(Borders::ALL).title(title);
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        let [players_area, rules_area] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(inner);
+
+        let players: Vec<Line> = match &detail.players {
+            None => vec![Line::from("Loading players...")],
+            Some(Err(e)) => vec![Line::from(format!("Players unavailable: {}", e))],
+            Some(Ok(players)) if players.is_empty() => vec![Line::from("No players")],
+            Some(Ok(players)) => players
+                .iter()
+                .map(|p| {
+                    let minutes = p.duration.as_secs() / 60;
+                    Line::from(format!("{:<24} {:>5}  {:>3}:{:02}", p.name, p.score, minutes / 60, minutes % 60))
+                })
+                .collect(),
+        };
+        let rules: Vec<Line> = match &detail.rules {
+            None => vec![Line::from("Loading rules...")],
+            Some(Err(e)) => vec![Line::from(format!("Rules unavailable: {}", e))],
+            Some(Ok(rules)) if rules.is_empty() => vec![Line::from("No rules")],
+            Some(Ok(rules)) => rules
+                .iter()
+                .map(|r| Line::from(format!("{} = {}", r.name, r.value)))
+                .collect(),
+        };
+        frame.render_widget(Paragraph::new(players).block(Block::new().title("Players")), players_area);
+        frame.render_widget(Paragraph::new(rules).block(Block::new().title("Rules")), rules_area);
+    }
+
+    fn status(&self) -> String {
+        if self.editing {
+            return format!("/{}_", self.filter);
+        }
+        let mut status = format!("{} of {} servers", self.view.len(), self.rows.len());
+        if !self.filter.is_empty() {
+            status += &format!(" matching \"{}\"", self.filter);
+        }
+        if let Some(refreshed) = self.refreshed {
+            status += &format!(" | refreshed {}s ago", refreshed.elapsed().as_secs());
+        }
+        if let Some(error) = &self.error {
+            status += &format!(" | refresh failed: {}", error);
+        }
+        format!("{} | {}", status, KEYS)
+    }
+}
@@ -0,0 +1,350 @@
+//! `msq` command-line tool - Query master servers and probe game servers
+//!
+//! * Requires feature: `cli` (Turned **off** by default)
+//!
+//! ```text
+//! msq query --region eu --appid 240 --map de_dust2 --probe --format table
+//! msq query --filter '\appid\440\empty\1' --max-servers 1000 --format csv
+//! msq probe 216.52.143.114:27015 216.52.143.114:27016 --format json
+//! msq browse --region eu --appid 440 --refresh 30
+//! ```
+
+#[cfg(feature = "tui")]
+mod browse;
+mod output;
+
+use crate::output::{Format, Row};
+
+use clap::{Args, Parser, Subcommand};
+use msq::{A2SClient, Filter, MSQClient, Region, ServerInfo};
+use std::io::{BufRead, Error, ErrorKind, Result};
+use std::process::ExitCode;
+use std::time::Duration;
+use tokio::task::JoinSet;
+
+#[derive(Parser)]
+#[command(name = "msq", version, about = "Query master servers and probe game servers")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Query a master server for game servers
+    Query(Box<QueryArgs>),
+    /// Probe game servers with A2S_INFO (reads addresses from stdin if none are given)
+    Probe(ProbeArgs),
+    /// Browse servers interactively, with live refresh and server details
+    #[cfg(feature = "tui")]
+    Browse(Box<BrowseArgs>),
+}
+
+#[derive(Args)]
+struct QueryArgs {
+    #[command(flatten)]
+    search: SearchArgs,
+
+    /// Probe each server with A2S_INFO
+    #[arg(long)]
+    probe: bool,
+
+    #[command(flatten)]
+    probe_options: ProbeOptions,
+
+    /// Output format
+    #[arg(short, long, value_enum, default_value = "plain")]
+    format: Format,
+}
+
+#[cfg(feature = "tui")]
+#[derive(Args)]
+struct BrowseArgs {
+    #[command(flatten)]
+    search: SearchArgs,
+
+    #[command(flatten)]
+    probe_options: ProbeOptions,
+
+    /// Time between refreshes, in seconds (0 only refreshes on demand)
+    #[arg(long, default_value_t = 60)]
+    refresh: u64,
+}
+
+/// The master server query
+#[derive(Args, Clone)]
+struct SearchArgs {
+    /// Master server address
+    #[arg(long, default_value = "hl2master.steampowered.com:27011")]
+    master: String,
+
+    /// Region to query (EX: eu, us-east, asia, all)
+    #[arg(short, long, default_value = "all")]
+    region: Region,
+
+    /// Raw filter string, instead of the filter flags (EX: '\appid\240\map\de_dust2')
+    #[arg(long, conflicts_with_all = [
+        "appid", "napp", "gamedir", "map", "name", "version", "gameaddr", "tags",
+        "dedicated", "secure", "linux", "password", "full", "empty", "proxy", "whitelisted",
+        "collapse_addr_hash",
+    ])]
+    filter: Option<String>,
+
+    /// Servers running the appid
+    #[arg(long)]
+    appid: Option<u32>,
+
+    /// Servers not running the appid
+    #[arg(long)]
+    napp: Option<u32>,
+
+    /// Servers running the modification (EX: cstrike)
+    #[arg(long)]
+    gamedir: Option<String>,
+
+    /// Servers running the map
+    #[arg(long)]
+    map: Option<String>,
+
+    /// Servers with a matching hostname (* is a wildcard)
+    #[arg(long)]
+    name: Option<String>,
+
+    /// Servers with a matching version (* is a wildcard)
+    #[arg(long)]
+    version: Option<String>,
+
+    /// Servers on the IP address (port optional)
+    #[arg(long)]
+    gameaddr: Option<String>,
+
+    /// Servers with all of the tags in sv_tags, comma separated
+    #[arg(long)]
+    tags: Option<String>,
+
+    /// Dedicated servers (true) or listen servers (false)
+    #[arg(long)]
+    dedicated: Option<bool>,
+
+    /// Servers using anti-cheat (true) or not (false)
+    #[arg(long)]
+    secure: Option<bool>,
+
+    /// Servers running on Linux (true) or not (false)
+    #[arg(long)]
+    linux: Option<bool>,
+
+    /// Password protected servers (true) or not (false)
+    #[arg(long)]
+    password: Option<bool>,
+
+    /// Full servers (true) or not full servers (false)
+    #[arg(long)]
+    full: Option<bool>,
+
+    /// Empty servers (true) or servers with players (false)
+    #[arg(long)]
+    empty: Option<bool>,
+
+    /// Spectator proxies (true) or not (false)
+    #[arg(long)]
+    proxy: Option<bool>,
+
+    /// Whitelisted servers (true) or not (false)
+    #[arg(long)]
+    whitelisted: Option<bool>,
+
+    /// Return only one server for each IP address
+    #[arg(long)]
+    collapse_addr_hash: bool,
+
+    /// Maximum amount of servers to query
+    #[arg(short = 'n', long, default_value_t = 256)]
+    max_servers: usize,
+
+    /// Time until the master server query fails, in milliseconds
+    #[arg(long, default_value_t = 10000)]
+    timeout: u64,
+}
+
+#[derive(Args)]
+struct ProbeArgs {
+    /// Game server addresses (EX: 216.52.143.114:27015)
+    addrs: Vec<String>,
+
+    #[command(flatten)]
+    probe_options: ProbeOptions,
+
+    /// Output format
+    #[arg(short, long, value_enum, default_value = "plain")]
+    format: Format,
+}
+
+#[derive(Args, Clone)]
+struct ProbeOptions {
+    /// Time until a probe fails, in milliseconds
+    #[arg(long, default_value_t = 3000)]
+    probe_timeout: u64,
+
+    /// Maximum amount of probes in flight
+    #[arg(long, default_value_t = 64)]
+    concurrency: usize,
+
+    /// Maximum amount of probes started per second
+    #[arg(long)]
+    rate: Option<u32>,
+}
+
+impl SearchArgs {
+    fn filter_string(&self) -> String {
+        if let Some(filter) = &self.filter {
+            return filter.clone();
+        }
+
+        let mut filter = Filter::new();
+        if let Some(appid) = self.appid {
+            filter = filter.appid(appid);
+        }
+        if let Some(napp) = self.napp {
+            filter = filter.napp(napp);
+        }
+        if let Some(gamedir) = &self.gamedir {
+            filter = filter.gamedir(gamedir);
+        }
+        if let Some(map) = &self.map {
+            filter = filter.map(map);
+        }
+        if let Some(name) = &self.name {
+            filter = filter.name_match(name);
+        }
+        if let Some(version) = &self.version {
+            filter = filter.version_match(version);
+        }
+        if let Some(gameaddr) = &self.gameaddr {
+            filter = filter.gameaddr(gameaddr);
+        }
+        if let Some(tags) = &self.tags {
+            filter = filter.gametype(&tags.split(',').collect());
+        }
+        let flags = [
+            (self.dedicated, Filter::dedicated as fn(Filter, bool) -> Filter),
+            (self.secure, Filter::secure),
+            (self.linux, Filter::linux),
+            (self.password, Filter::password),
+            (self.full, Filter::full),
+            (self.empty, Filter::empty),
+            (self.proxy, Filter::proxy),
+            (self.whitelisted, Filter::whitelisted),
+        ];
+        for (value, set) in flags {
+            if let Some(value) = value {
+                filter = set(filter, value);
+            }
+        }
+        if self.collapse_addr_hash {
+            filter = filter.collapse_addr_hash(true);
+        }
+        filter.as_string()
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Query(args) => query(args).await,
+        Command::Probe(args) => probe(args).await,
+        #[cfg(feature = "tui")]
+        Command::Browse(args) => browse::run(*args).await,
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("msq: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn query(args: Box<QueryArgs>) -> Result<()> {
+    let servers = search(&args.search).await?;
+    let rows = if args.probe {
+        probe_all(servers, &args.probe_options).await
+    } else {
+        servers.into_iter().map(Row::new).collect()
+    };
+    output::write(&rows, args.format, args.probe)
+}
+
+// Run the master server query
+async fn search(args: &SearchArgs) -> Result<Vec<String>> {
+    let filter = args.filter_string();
+    tokio::time::timeout(Duration::from_millis(args.timeout), async {
+        let mut client = MSQClient::new().await?;
+        client.connect(&args.master).await?;
+        client.max_servers_on_query(args.max_servers);
+        client.query_raw(args.region.as_u8(), &filter).await
+    })
+    .await
+    .map_err(|_| Error::new(ErrorKind::TimedOut, "Master server query timed out"))?
+}
+
+async fn probe(args: ProbeArgs) -> Result<()> {
+    let addrs = if args.addrs.is_empty() {
+        let stdin = std::io::stdin();
+        let mut addrs = vec![];
+        for line in stdin.lock().lines() {
+            let line = line?;
+            let addr = line.trim();
+            if !addr.is_empty() {
+                addrs.push(String::from(addr));
+            }
+        }
+        addrs
+    } else {
+        args.addrs
+    };
+
+    let rows = probe_all(addrs, &args.probe_options).await;
+    output::write(&rows, args.format, true)
+}
+
+// Probe every address with A2S_INFO, keeping the order of the addresses
+async fn probe_all(addrs: Vec<String>, options: &ProbeOptions) -> Vec<Row> {
+    let timeout = Duration::from_millis(options.probe_timeout);
+    let mut interval = options
+        .rate
+        .filter(|rate| *rate > 0)
+        // Past a billion per second the period rounds down to zero
+        .map(|rate| tokio::time::interval((Duration::from_secs(1) / rate).max(Duration::from_nanos(1))));
+
+    let mut rows: Vec<Row> = vec![];
+    let mut tasks = JoinSet::new();
+    for (i, addr) in addrs.into_iter().enumerate() {
+        while tasks.len() >= options.concurrency.max(1) {
+            finish(&mut tasks, &mut rows).await;
+        }
+        if let Some(interval) = &mut interval {
+            interval.tick().await;
+        }
+        rows.push(Row::new(addr.clone()));
+        tasks.spawn(async move {
+            let info = async {
+                let mut client = A2SClient::new().await?;
+                client.timeout(timeout);
+                client.info(&addr).await
+            };
+            (i, info.await)
+        });
+    }
+    while !tasks.is_empty() {
+        finish(&mut tasks, &mut rows).await;
+    }
+    rows
+}
+
+async fn finish(tasks: &mut JoinSet<(usize, Result<ServerInfo>)>, rows: &mut [Row]) {
+    if let Some(Ok((i, info))) = tasks.join_next().await {
+        rows[i].probe = Some(info);
+    }
+}
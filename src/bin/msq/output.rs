@@ -0,0 +1,178 @@
+use clap::ValueEnum;
+use msq::ServerInfo;
+use std::io::{stdout, BufWriter, Result, Write};
+
+/// Output format of the results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// One server per line, probe results separated by tabs
+    Plain,
+    /// Aligned columns with a header
+    Table,
+    /// JSON array of servers
+    Json,
+    /// CSV with a header
+    Csv,
+}
+
+/// A game server address, with the result of its probe if it was probed
+pub struct Row {
+    pub addr: String,
+    pub probe: Option<Result<ServerInfo>>,
+}
+
+impl Row {
+    pub fn new(addr: String) -> Row {
+        Row { addr, probe: None }
+    }
+
+    pub fn info(&self) -> Option<&ServerInfo> {
+        self.probe.as_ref().and_then(|p| p.as_ref().ok())
+    }
+
+    fn error(&self) -> Option<String> {
+        match &self.probe {
+            Some(Err(e)) => Some(e.to_string()),
+            _ => None,
+        }
+    }
+
+    // Columns of the probe results, empty if the probe failed
+    fn columns(&self) -> [String; 5] {
+        match self.info() {
+            Some(info) => [
+                info.name.clone(),
+                info.map.clone(),
+                format!("{}/{}", info.players, info.max_players),
+                info.bots.to_string(),
+                info.ping.as_millis().to_string(),
+            ],
+            None => Default::default(),
+        }
+    }
+}
+
+const HEADER: [&str; 7] = ["address", "name", "map", "players", "bots", "ping_ms", "error"];
+
+/// Write the rows to stdout, with the probe columns if `probed` is set
+pub fn write(rows: &[Row], format: Format, probed: bool) -> Result<()> {
+    let mut out = BufWriter::new(stdout().lock());
+    match format {
+        Format::Plain => plain(&mut out, rows, probed)?,
+        Format::Table => table(&mut out, rows, probed)?,
+        Format::Json => json(&mut out, rows, probed)?,
+        Format::Csv => csv(&mut out, rows, probed)?,
+    }
+    out.flush()
+}
+
+fn plain<W: Write>(out: &mut W, rows: &[Row], probed: bool) -> Result<()> {
+    for row in rows {
+        if !probed {
+            writeln!(out, "{}", row.addr)?;
+        } else if let Some(error) = row.error() {
+            writeln!(out, "{}\terror: {}", row.addr, error)?;
+        } else {
+            writeln!(out, "{}\t{}", row.addr, row.columns().join("\t"))?;
+        }
+    }
+    Ok(())
+}
+
+fn table<W: Write>(out: &mut W, rows: &[Row], probed: bool) -> Result<()> {
+    let width = if probed { HEADER.len() } else { 1 };
+    let mut lines: Vec<Vec<String>> = vec![HEADER[..width].iter().map(|h| h.to_uppercase()).collect()];
+    for row in rows {
+        let mut line = vec![row.addr.clone()];
+        if probed {
+            line.extend(row.columns());
+            line.push(row.error().unwrap_or_default());
+        }
+        lines.push(line);
+    }
+
+    let mut widths = vec![0; width];
+    for line in &lines {
+        for (w, cell) in widths.iter_mut().zip(line) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+    for line in lines {
+        let cells: Vec<String> = line
+            .iter()
+            .zip(&widths)
+            .map(|(cell, w)| format!("{:<w$}", cell, w = w))
+            .collect();
+        writeln!(out, "{}", cells.join("  ").trim_end())?;
+    }
+    Ok(())
+}
+
+fn json<W: Write>(out: &mut W, rows: &[Row], probed: bool) -> Result<()> {
+    writeln!(out, "[")?;
+    for (i, row) in rows.iter().enumerate() {
+        let mut fields = vec![format!("\"address\": {}", json_string(&row.addr))];
+        if probed {
+            match row.info() {
+                Some(info) => {
+                    fields.push(format!("\"name\": {}", json_string(&info.name)));
+                    fields.push(format!("\"map\": {}", json_string(&info.map)));
+                    fields.push(format!("\"game\": {}", json_string(&info.game)));
+                    fields.push(format!("\"appid\": {}", info.appid));
+                    fields.push(format!("\"players\": {}", info.players));
+                    fields.push(format!("\"max_players\": {}", info.max_players));
+                    fields.push(format!("\"bots\": {}", info.bots));
+                    fields.push(format!("\"version\": {}", json_string(&info.version)));
+                    fields.push(format!("\"ping_ms\": {}", info.ping.as_millis()));
+                }
+                None => fields.push(format!(
+                    "\"error\": {}",
+                    json_string(&row.error().unwrap_or_default())
+                )),
+            }
+        }
+        let comma = if i + 1 < rows.len() { "," } else { "" };
+        writeln!(out, "  {{{}}}{}", fields.join(", "), comma)?;
+    }
+    writeln!(out, "]")
+}
+
+fn csv<W: Write>(out: &mut W, rows: &[Row], probed: bool) -> Result<()> {
+    let width = if probed { HEADER.len() } else { 1 };
+    writeln!(out, "{}", HEADER[..width].join(","))?;
+    for row in rows {
+        let mut cells = vec![row.addr.clone()];
+        if probed {
+            cells.extend(row.columns());
+            cells.push(row.error().unwrap_or_default());
+        }
+        let cells: Vec<String> = cells.iter().map(|c| csv_field(c)).collect();
+        writeln!(out, "{}", cells.join(","))?;
+    }
+    Ok(())
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        String::from(s)
+    }
+}
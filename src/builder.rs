@@ -0,0 +1,386 @@
+use crate::socket::SocketOptions;
+use crate::transport::Transport;
+
+use std::fmt;
+use std::io::{Error, Result};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+use crate::client_async::MSQClient;
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+use crate::runtime::AsyncUdpSocket;
+#[cfg(feature = "non-async")]
+use crate::client_blocking::MSQClientBlock;
+
+/// How a client retries a request the master server did not reply to
+///
+/// * Intended to be used with: [`MSQClientBuilder::retry`]
+/// * Only applies when a [`timeout`](MSQClientBuilder::timeout) is set.
+/// * The request is sent again after `backoff`, doubled after each attempt.
+///
+/// # Example
+/// ```rust
+/// use msq::RetryPolicy;
+/// use std::time::Duration;
+///
+/// // Retry up to 3 times, waiting 250ms, 500ms and 1s
+/// let policy = RetryPolicy::new(3).backoff(Duration::from_millis(250));
+/// assert_eq!(policy.delay(3), Duration::from_secs(1));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RetryPolicy {
+    attempts: u32,
+    backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Create a new RetryPolicy retrying up to `attempts` times without waiting
+    ///
+    /// # Arguments
+    /// * `attempts` - Maximum amount of retries per request, `0` to never retry
+    pub fn new(attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            attempts,
+            backoff: Duration::ZERO,
+        }
+    }
+
+    /// Set how long to wait before the first retry
+    ///
+    /// # Arguments
+    /// * `backoff` - Wait before the first retry, doubled after each attempt
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Returns the maximum amount of retries per request
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Returns how long to wait before the given retry (starting at `1`)
+    ///
+    /// # Arguments
+    /// * `attempt` - The retry
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+    }
+}
+
+/// Something that happened while a client queried a master server
+///
+/// * Intended to be used with: [`MSQClientBuilder::on_event`]
+/// * `master` is the master server's address as it was given to the client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryEvent {
+    /// A request was sent to the master server
+    Request {
+        master: String,
+        region_code: u8,
+        seed: String,
+    },
+    /// A batch of servers was received
+    Reply { master: String, servers: usize },
+    /// No reply came in time, the request is sent again
+    Retry { master: String, attempt: u32 },
+    /// The master server failed, the query starts over on the next one
+    Failover { from: String, to: String },
+}
+
+type Hook = Arc<dyn Fn(&QueryEvent) + Send + Sync>;
+
+/// Query settings shared by [`MSQClient`] and [`MSQClientBlock`]
+#[derive(Clone, Default)]
+pub(crate) struct ClientConfig {
+    pub(crate) masters: Vec<String>,
+    pub(crate) master: usize,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) retry: RetryPolicy,
+    pub(crate) rate_limit: Option<Duration>,
+    pub(crate) pool_size: Option<usize>,
+    // Shared by the clients of a shared client, so they take turns
+    last_request: Arc<Mutex<Option<Instant>>>,
+    hooks: Vec<Hook>,
+}
+
+impl ClientConfig {
+    /// The address of the master server in use
+    pub(crate) fn master(&self) -> String {
+        self.masters.get(self.master).cloned().unwrap_or_default()
+    }
+
+    /// Switch to the next master server, returning its address
+    pub(crate) fn next_master(&mut self) -> String {
+        let from = self.master();
+        self.master = (self.master + 1) % self.masters.len().max(1);
+        let to = self.master();
+        self.emit(QueryEvent::Failover { from, to: to.clone() });
+        to
+    }
+
+    /// How long to wait before the next request to respect the rate limit,
+    /// reserving its turn
+    pub(crate) fn pacing(&self) -> Option<Duration> {
+        let interval = self.rate_limit?;
+        let mut last_request = self.last_request.lock().unwrap();
+        let now = Instant::now();
+        let turn = match *last_request {
+            Some(last) => (last + interval).max(now),
+            None => now,
+        };
+        *last_request = Some(turn);
+        Some(turn - now).filter(|wait| !wait.is_zero())
+    }
+
+    /// The deadline of a reply expected from now on
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.timeout.map(|timeout| Instant::now() + timeout)
+    }
+
+    pub(crate) fn sent(&self, region_code: u8, seed: &str) {
+        self.emit(QueryEvent::Request {
+            master: self.master(),
+            region_code,
+            seed: seed.to_string(),
+        });
+    }
+
+    pub(crate) fn emit(&self, event: QueryEvent) {
+        for hook in &self.hooks {
+            hook(&event);
+        }
+    }
+}
+
+/// Builder of [`MSQClient`] and [`MSQClientBlock`]
+///
+/// * Produces a client connected to its master server, ready to query.
+/// * With several master servers, the first one that resolves is used, and
+///   a query that times out (after its retries) starts over on the next.
+/// * Defaults match the clients' `new()`: bound to `0.0.0.0:0`, no timeout,
+///   no retries, no rate limit and 64 servers per query.
+///
+/// # Quick Start
+/// ```rust,no_run
+/// use msq::{MSQClientBuilder, RetryPolicy, Region, Filter};
+/// use std::io::Result;
+/// use std::time::Duration;
+///
+/// fn main() -> Result<()> {
+///     let mut client = MSQClientBuilder::new()
+///         .masters(&["hl2master.steampowered.com:27011", "hl1master.steampowered.com:27011"])
+///         .timeout(Duration::from_secs(2))
+///         .retry(RetryPolicy::new(3).backoff(Duration::from_millis(500)))
+///         .rate_limit(Duration::from_secs(1))
+///         .max_servers(1000)
+///         .recv_buffer_size(1 << 20)
+///         .on_event(|event| println!("{:?}", event))
+///         .build_block()?;
+///
+///     let servers = client.query(Region::Europe, Filter::new().appid(240))?;
+///     Ok(())
+/// }
+/// ```
+///
+pub struct MSQClientBuilder {
+    masters: Vec<String>,
+    socket: SocketOptions,
+    transport: Option<Box<dyn Transport>>,
+    max_servers: usize,
+    config: ClientConfig,
+}
+
+impl Default for MSQClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for MSQClientBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MSQClientBuilder")
+            .field("masters", &self.masters)
+            .field("socket", &self.socket)
+            .field("transport", &self.transport.is_some())
+            .field("max_servers", &self.max_servers)
+            .field("timeout", &self.config.timeout)
+            .field("retry", &self.config.retry)
+            .field("rate_limit", &self.config.rate_limit)
+            .field("pool_size", &self.config.pool_size)
+            .field("hooks", &self.config.hooks.len())
+            .finish()
+    }
+}
+
+impl MSQClientBuilder {
+    /// Create a new MSQClientBuilder variable with the default settings
+    pub fn new() -> MSQClientBuilder {
+        MSQClientBuilder {
+            masters: vec![],
+            socket: SocketOptions::new(),
+            transport: None,
+            max_servers: 64,
+            config: ClientConfig::default(),
+        }
+    }
+
+    /// Add a master server to connect to
+    ///
+    /// # Arguments
+    /// * `addr` - The master server's hostname/ip address
+    pub fn master(mut self, addr: &str) -> Self {
+        self.masters.push(addr.to_string());
+        self
+    }
+
+    /// Add master servers to connect to, in order of preference
+    ///
+    /// # Arguments
+    /// * `addrs` - The master servers' hostname/ip addresses
+    pub fn masters(mut self, addrs: &[&str]) -> Self {
+        self.masters.extend(addrs.iter().map(|addr| addr.to_string()));
+        self
+    }
+
+    /// Bind the UDP socket to the given local address
+    ///
+    /// # Arguments
+    /// * `addr` - The local address, IPv4 or IPv6 (EX: `[::]:27005`)
+    pub fn bind(mut self, addr: SocketAddr) -> Self {
+        self.socket = self.socket.ip(addr.ip()).port(addr.port());
+        self
+    }
+
+    /// Bind the UDP socket with the given [`SocketOptions`]
+    ///
+    /// # Arguments
+    /// * `options` - [`SocketOptions`] replacing any set before
+    pub fn socket(mut self, options: SocketOptions) -> Self {
+        self.socket = options;
+        self
+    }
+
+    /// Set the size of the UDP socket's receive buffer in bytes
+    ///
+    /// # Arguments
+    /// * `size` - Size in bytes, the system may round or cap it
+    pub fn recv_buffer_size(mut self, size: usize) -> Self {
+        self.socket = self.socket.recv_buffer_size(size);
+        self
+    }
+
+    /// Send and receive through the given [`Transport`] instead of a UDP socket
+    ///
+    /// # Arguments
+    /// * `transport` - [`Transport`] to use, the socket options are ignored
+    pub fn transport<T: Transport + 'static>(mut self, transport: T) -> Self {
+        self.transport = Some(Box::new(transport));
+        self
+    }
+
+    /// Set the maximum amount of servers in a query (Defaults to 64)
+    ///
+    /// # Arguments
+    /// * `max_servers` - Maximum amount of servers in a query
+    pub fn max_servers(mut self, max_servers: usize) -> Self {
+        self.max_servers = max_servers;
+        self
+    }
+
+    /// Set how long to wait for each reply of the master server
+    ///
+    /// # Arguments
+    /// * `timeout` - Time until a request fails or is retried
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = Some(timeout);
+        self
+    }
+
+    /// Set how requests that time out are retried (Defaults to never)
+    ///
+    /// # Arguments
+    /// * `retry` - [`RetryPolicy`]
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.config.retry = retry;
+        self
+    }
+
+    /// Set the minimum time between two requests to the master server
+    ///
+    /// # Arguments
+    /// * `interval` - Minimum time between requests (EX: 1 second to stay
+    ///   under the master server's throttling)
+    pub fn rate_limit(mut self, interval: Duration) -> Self {
+        self.config.rate_limit = Some(interval);
+        self
+    }
+
+    /// Set how many idle sockets a shared client keeps for its next queries
+    /// (Defaults to 8)
+    ///
+    /// * Only applies to the shared clients made with `into_shared`, such as
+    ///   [`MSQClientBlock::into_shared`].
+    ///
+    /// # Arguments
+    /// * `size` - Maximum amount of idle sockets
+    pub fn pool_size(mut self, size: usize) -> Self {
+        self.config.pool_size = Some(size);
+        self
+    }
+
+    /// Call the given function on every [`QueryEvent`] of the client
+    ///
+    /// # Arguments
+    /// * `hook` - Function called with each event, on the querying thread/task
+    pub fn on_event<F: Fn(&QueryEvent) + Send + Sync + 'static>(mut self, hook: F) -> Self {
+        self.config.hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// Build a non-async [`MSQClientBlock`]
+    ///
+    /// * Requires feature: `non-async` (Turned **on** by default)
+    #[cfg(feature = "non-async")]
+    pub fn build_block(self) -> Result<MSQClientBlock> {
+        let mut client = match self.transport {
+            Some(transport) => MSQClientBlock::with_transport(transport),
+            None => MSQClientBlock::with_options(&self.socket)?,
+        };
+        client.max_servers_on_query(self.max_servers);
+        client.configure(self.config, self.masters)?;
+        Ok(client)
+    }
+
+    /// Build an async [`MSQClient`] on the [`DefaultUdpSocket`](crate::DefaultUdpSocket)
+    ///
+    /// * Requires feature: `async` (Turned **on** by default), or one of
+    ///   `async-std` and `smol`
+    #[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+    pub async fn build(self) -> Result<MSQClient> {
+        self.build_on().await
+    }
+
+    /// Build an async [`MSQClient`] on the [`AsyncUdpSocket`] `S`
+    ///
+    /// * Requires feature: `async` (Turned **on** by default), or one of
+    ///   `async-std` and `smol`
+    #[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+    pub async fn build_on<S: AsyncUdpSocket>(self) -> Result<MSQClient<S>> {
+        let mut client = match self.transport {
+            Some(transport) => MSQClient::<S>::create_with_transport(transport),
+            None => MSQClient::<S>::create_with_options(&self.socket).await?,
+        };
+        client.max_servers_on_query(self.max_servers);
+        client.configure(self.config, self.masters).await?;
+        Ok(client)
+    }
+}
+
+/// The error of a client that could connect to none of its master servers
+pub(crate) fn no_master(last: Option<Error>) -> Error {
+    last.unwrap_or_else(|| Error::other("No master server to connect to"))
+}
@@ -0,0 +1,162 @@
+use std::fmt;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::Waker;
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// How long to wait for the replies left over by a query that did not finish
+pub(crate) const DRAIN_GRACE: Duration = Duration::from_millis(5);
+
+/// How often a transport that cannot be woken up checks for a cancellation
+pub(crate) const CANCEL_POLL: Duration = Duration::from_millis(20);
+
+/// Token to stop the queries of a client from anywhere
+///
+/// * Intended to be used with: `query_with_cancel` of
+///   [`MSQClient`](crate::MSQClient::query_with_cancel) and
+///   [`MSQClientBlock`](crate::MSQClientBlock::query_with_cancel)
+/// * Clones share the same state: cancelling one cancels them all.
+/// * A cancelled query fails with [`ErrorKind::Interrupted`] and a
+///   [`PartialQuery`] of how far it got.
+///
+/// # Quick Start
+/// ```rust,no_run
+/// use msq::{MSQClientBlock, CancelToken, PartialQuery, Region, Filter};
+/// use std::io::Result;
+/// use std::time::Duration;
+///
+/// fn main() -> Result<()> {
+///     let mut client = MSQClientBlock::new()?;
+///     client.connect("hl2master.steampowered.com:27011")?;
+///     client.max_servers_on_query(10000);
+///
+///     let cancel = CancelToken::new();
+///     let timer = cancel.clone();
+///     std::thread::spawn(move || {
+///         std::thread::sleep(Duration::from_secs(5));
+///         timer.cancel();
+///     });
+///     match client.query_with_cancel(Region::All, Filter::new().appid(240), &cancel) {
+///         Ok(servers) => println!("{} servers", servers.len()),
+///         Err(e) => match PartialQuery::from_error(&e) {
+///             Some(partial) => println!("Stopped with {} servers", partial.servers.len()),
+///             None => return Err(e),
+///         },
+///     }
+///     Ok(())
+/// }
+/// ```
+///
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    inner: Arc<CancelState>,
+}
+
+#[derive(Debug, Default)]
+struct CancelState {
+    cancelled: AtomicBool,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl CancelToken {
+    /// Create a new CancelToken that is not cancelled
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    /// Cancel the queries using this token, now and in the future
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        for waker in self.inner.wakers.lock().unwrap().drain(..) {
+            waker.wake();
+        }
+    }
+
+    /// Returns whether the token was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    // Ready once cancelled, waking the task when it happens
+    #[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+    pub(crate) fn poll_cancelled(&self, cx: &mut Context<'_>) -> Poll<()> {
+        if self.is_cancelled() {
+            return Poll::Ready(());
+        }
+        let mut wakers = self.inner.wakers.lock().unwrap();
+        if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            wakers.push(cx.waker().clone());
+        }
+        // Cancelled while registering
+        match self.is_cancelled() {
+            true => Poll::Ready(()),
+            false => Poll::Pending,
+        }
+    }
+}
+
+/// How far a query got before it was stopped by its deadline or a
+/// [`CancelToken`], or before a sub-query of a sharded query failed
+///
+/// * Carried by the error of the query, get it with
+///   [`from_error`](#method.from_error).
+/// * The query can be picked up where it stopped by sending the last server
+///   as the seed of the next request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialQuery {
+    /// The servers received before the query stopped
+    pub servers: Vec<String>,
+    /// The amount of reply batches received, or of sub-queries completed
+    /// for a sharded query
+    pub pages: usize,
+}
+
+impl PartialQuery {
+    /// Returns the PartialQuery of the error of a stopped query
+    ///
+    /// # Arguments
+    /// * `err` - The error returned by the query
+    pub fn from_error(err: &Error) -> Option<&PartialQuery> {
+        err.get_ref()?.downcast_ref::<PartialQuery>()
+    }
+}
+
+impl fmt::Display for PartialQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Query stopped after {} pages ({} servers)", self.pages, self.servers.len())
+    }
+}
+
+impl std::error::Error for PartialQuery {}
+
+/// When a query has to stop, besides finishing
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Stop<'a> {
+    pub(crate) deadline: Option<Instant>,
+    pub(crate) cancel: Option<&'a CancelToken>,
+}
+
+impl Stop<'_> {
+    /// Fail with the servers received so far if the query has to stop
+    pub(crate) fn check(&self, servers: &[String], pages: usize) -> Result<()> {
+        let kind = match (self.cancel, self.deadline) {
+            (Some(cancel), _) if cancel.is_cancelled() => ErrorKind::Interrupted,
+            (_, Some(deadline)) if Instant::now() >= deadline => ErrorKind::TimedOut,
+            _ => return Ok(()),
+        };
+        Err(Error::new(kind, PartialQuery { servers: servers.to_vec(), pages }))
+    }
+
+    /// How long to wait for a reply due by `reply`, polling for a
+    /// cancellation if `poll` is set
+    pub(crate) fn wait(&self, reply: Option<Instant>, poll: bool) -> Option<Instant> {
+        let poll = match (poll, self.cancel) {
+            (true, Some(_)) => Some(Instant::now() + CANCEL_POLL),
+            _ => None,
+        };
+        [reply, self.deadline, poll].into_iter().flatten().min()
+    }
+}
@@ -0,0 +1,441 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Result, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::transport::Transport;
+
+const TEXT_HEADER: &str = "# msq capture v1";
+const PCAP_MAGIC: u32 = 0xA1B2C3D4;
+// LINKTYPE_RAW: packets start with their IPv4/IPv6 header
+const PCAP_LINKTYPE_RAW: u32 = 101;
+// LINKTYPE_LINUX_SLL: packets start with a Linux cooked capture header,
+// which tells whether they were sent or received
+const PCAP_LINKTYPE_SLL: u32 = 113;
+const SLL_HEADER_LEN: usize = 16;
+const SLL_HOST: u16 = 0;
+const SLL_OUTGOING: u16 = 4;
+// Largest packet a record may hold, whatever the snapshot length claims
+const PCAP_SNAPLEN: u32 = 65535;
+
+/// File format of a [`Recorder`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureFormat {
+    /// Plain text, one datagram per line (see [`Recorder`])
+    Text,
+    /// pcap file of IP/UDP packets in Linux cooked capture headers, which
+    /// keep the direction of each packet, readable by Wireshark or tcpdump
+    Pcap,
+}
+
+/// Direction of a captured datagram, as seen by the client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Direction {
+    Send,
+    Recv,
+}
+
+/// Recorder - Writes every datagram sent and received by a client
+///
+/// * Intended to be used with: [`MSQClient::record`](crate::MSQClient::record)
+///   and [`MSQClientBlock::record`](crate::MSQClientBlock::record)
+/// * Cloning a Recorder writes to the same file.
+/// * Recorded sessions can be fed back into a client with [`Replay`].
+///
+/// The text format starts with a `# msq capture v1` line, followed by one
+/// line per datagram with space separated fields:
+///
+/// ```text
+/// <unix time in microseconds> <send|recv> <local address> <peer address> <payload in hex>
+/// ```
+///
+/// Lines starting with `#` are comments.
+///
+/// # Quick Start
+/// ```rust,no_run
+/// use msq::{MSQClientBlock, Recorder, CaptureFormat, Region, Filter};
+/// use std::io::Result;
+///
+/// fn main() -> Result<()> {
+///     let mut client = MSQClientBlock::new()?;
+///     client.connect("hl2master.steampowered.com:27011")?;
+///     client.record(Recorder::create("session.pcap", CaptureFormat::Pcap)?);
+///     let servers = client.query(Region::Europe, Filter::new().appid(240))?;
+///     Ok(())
+/// }
+/// ```
+///
+#[derive(Clone)]
+pub struct Recorder {
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+    format: CaptureFormat,
+}
+
+impl Recorder {
+    /// Create (or truncate) the file at the given path and record into it
+    ///
+    /// # Arguments
+    /// * `path` - Path of the capture file
+    /// * `format` - [`CaptureFormat`] of the capture file
+    pub fn create<P: AsRef<Path>>(path: P, format: CaptureFormat) -> Result<Recorder> {
+        Self::new(File::create(path)?, format)
+    }
+
+    /// Record into the given writer
+    ///
+    /// # Arguments
+    /// * `writer` - Where to write the capture to
+    /// * `format` - [`CaptureFormat`] of the capture
+    pub fn new<W: Write + Send + 'static>(mut writer: W, format: CaptureFormat) -> Result<Recorder> {
+        match format {
+            CaptureFormat::Text => writeln!(writer, "{}", TEXT_HEADER)?,
+            CaptureFormat::Pcap => {
+                let mut header = vec![];
+                header.extend(PCAP_MAGIC.to_le_bytes());
+                header.extend(2u16.to_le_bytes()); // Version 2.4
+                header.extend(4u16.to_le_bytes());
+                header.extend(0i32.to_le_bytes()); // Timezone
+                header.extend(0u32.to_le_bytes()); // Timestamp accuracy
+                header.extend(PCAP_SNAPLEN.to_le_bytes()); // Snapshot length
+                header.extend(PCAP_LINKTYPE_SLL.to_le_bytes());
+                writer.write_all(&header)?;
+            }
+        }
+        writer.flush()?;
+        Ok(Recorder {
+            writer: Arc::new(Mutex::new(Box::new(writer))),
+            format,
+        })
+    }
+
+    pub(crate) fn record(&self, direction: Direction, local: SocketAddr, peer: SocketAddr, data: &[u8]) -> Result<()> {
+        let micros = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_micros() as u64)
+            .unwrap_or(0);
+
+        let entry = match self.format {
+            CaptureFormat::Text => {
+                let direction = match direction {
+                    Direction::Send => "send",
+                    Direction::Recv => "recv",
+                };
+                let hex: String = data.iter().map(|b| format!("{:02x}", b)).collect();
+                format!("{} {} {} {} {}\n", micros, direction, local, peer, hex).into_bytes()
+            }
+            CaptureFormat::Pcap => {
+                let (src, dst) = match direction {
+                    Direction::Send => (local, peer),
+                    Direction::Recv => (peer, local),
+                };
+                let packet_type = match direction {
+                    Direction::Send => SLL_OUTGOING,
+                    Direction::Recv => SLL_HOST,
+                };
+                let ip = ip_packet(src, dst, data);
+                let mut packet = sll_header(packet_type, &ip);
+                packet.extend(ip);
+                let mut entry = vec![];
+                entry.extend(((micros / 1_000_000) as u32).to_le_bytes());
+                entry.extend(((micros % 1_000_000) as u32).to_le_bytes());
+                entry.extend((packet.len() as u32).to_le_bytes());
+                entry.extend((packet.len() as u32).to_le_bytes());
+                entry.extend(packet);
+                entry
+            }
+        };
+
+        let mut writer = self.writer.lock().unwrap();
+        writer.write_all(&entry)?;
+        writer.flush()
+    }
+}
+
+/// Replay - Feeds a recorded session back into a client
+///
+/// * Intended to be used with: [`MSQClient::replay`](crate::MSQClient::replay)
+///   and [`MSQClientBlock::replay`](crate::MSQClientBlock::replay)
+/// * Implements [`Transport`]: replies are returned immediately, deadlines
+///   are ignored and connecting is a no-op.
+/// * Reads both [`CaptureFormat`]s. pcap files of raw IP packets, without
+///   the direction of each packet, are read as well: the sender of the
+///   first packet is taken as the client.
+/// * Every datagram the client sends has to match the next recorded one,
+///   otherwise the replay has diverged and the client gets an error. Each
+///   receive returns the next recorded reply, so a replayed query returns
+///   exactly what the recorded one did, without any network access.
+///
+/// # Quick Start
+/// ```rust,no_run
+/// use msq::{MSQClientBlock, Replay, Region, Filter};
+/// use std::io::Result;
+///
+/// fn main() -> Result<()> {
+///     let mut client = MSQClientBlock::new()?;
+///     client.replay(Replay::open("session.pcap")?);
+///     let servers = client.query(Region::Europe, Filter::new().appid(240))?;
+///     Ok(())
+/// }
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct Replay {
+    events: VecDeque<(Direction, Vec<u8>)>,
+}
+
+impl Replay {
+    /// Read a recorded session from the file at the given path
+    ///
+    /// # Arguments
+    /// * `path` - Path of the capture file
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Replay> {
+        Self::from_reader(File::open(path)?)
+    }
+
+    /// Read a recorded session from the given reader
+    ///
+    /// # Arguments
+    /// * `reader` - Where to read the capture from
+    pub fn from_reader<R: Read>(reader: R) -> Result<Replay> {
+        let mut reader = BufReader::new(reader);
+        let start = reader.fill_buf()?;
+        let is_pcap = start.len() >= 4
+            && (start[..4] == PCAP_MAGIC.to_le_bytes() || start[..4] == PCAP_MAGIC.to_be_bytes());
+        let events = if is_pcap {
+            read_pcap(reader)?
+        } else {
+            read_text(reader)?
+        };
+        Ok(Replay { events })
+    }
+
+    /// Returns the amount of datagrams left to replay
+    pub fn remaining(&self) -> usize {
+        self.events.len()
+    }
+}
+
+impl Transport for Replay {
+    fn connect(&mut self, _addr: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn send(&mut self, data: &[u8], _deadline: Option<Instant>) -> Result<usize> {
+        match self.events.front() {
+            Some((Direction::Send, recorded)) if recorded == data => {
+                self.events.pop_front();
+                Ok(data.len())
+            }
+            Some((Direction::Send, _)) => Err(Error::other("Replay diverged: sent a different datagram")),
+            Some((Direction::Recv, _)) => Err(Error::other("Replay diverged: sent before all replies were received")),
+            None => Err(Error::other("Replay diverged: sent past the end of the session")),
+        }
+    }
+
+    fn recv(&mut self, buf: &mut [u8], _deadline: Option<Instant>) -> Result<usize> {
+        match self.events.front() {
+            Some((Direction::Recv, _)) => {
+                let (_, data) = self.events.pop_front().unwrap();
+                let len = data.len().min(buf.len());
+                buf[..len].copy_from_slice(&data[..len]);
+                Ok(len)
+            }
+            // The recorded client did not get a reply here either
+            _ => Err(Error::new(ErrorKind::TimedOut, "No recorded reply")),
+        }
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(SocketAddr::from(([0, 0, 0, 0], 0)))
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        Ok(SocketAddr::from(([0, 0, 0, 0], 0)))
+    }
+}
+
+fn read_text<R: BufRead>(reader: R) -> Result<VecDeque<(Direction, Vec<u8>)>> {
+    let mut events = VecDeque::new();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = || Error::new(ErrorKind::InvalidData, format!("Invalid capture line: {}", line));
+
+        let fields: Vec<&str> = line.split(' ').collect();
+        if fields.len() != 5 || !fields[4].len().is_multiple_of(2) {
+            return Err(invalid());
+        }
+        let direction = match fields[1] {
+            "send" => Direction::Send,
+            "recv" => Direction::Recv,
+            _ => return Err(invalid()),
+        };
+        let data = (0..fields[4].len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&fields[4][i..i + 2], 16))
+            .collect::<std::result::Result<Vec<u8>, _>>()
+            .map_err(|_| invalid())?;
+        events.push_back((direction, data));
+    }
+    Ok(events)
+}
+
+fn read_pcap<R: Read>(mut reader: R) -> Result<VecDeque<(Direction, Vec<u8>)>> {
+    let mut header = [0u8; 24];
+    reader.read_exact(&mut header)?;
+    let little = header[..4] == PCAP_MAGIC.to_le_bytes();
+    let u32_at = |buf: &[u8], i: usize| {
+        let bytes = [buf[i], buf[i + 1], buf[i + 2], buf[i + 3]];
+        if little {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        }
+    };
+    let cooked = match u32_at(&header, 20) {
+        PCAP_LINKTYPE_RAW => false,
+        PCAP_LINKTYPE_SLL => true,
+        _ => return Err(Error::new(ErrorKind::InvalidData, "Unsupported pcap link type")),
+    };
+    let snaplen = match u32_at(&header, 16) {
+        0 => PCAP_SNAPLEN,
+        snaplen => snaplen.min(PCAP_SNAPLEN),
+    };
+
+    let mut events = VecDeque::new();
+    let mut client = None;
+    let mut record = [0u8; 16];
+    loop {
+        match reader.read_exact(&mut record) {
+            Ok(()) => (),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+        let len = u32_at(&record, 8);
+        if len > snaplen {
+            return Err(Error::new(ErrorKind::InvalidData, "Pcap record larger than the snapshot length"));
+        }
+        let mut packet = vec![0u8; len as usize];
+        reader.read_exact(&mut packet)?;
+
+        let (direction, payload) = match cooked {
+            true if packet.len() < SLL_HEADER_LEN => {
+                return Err(Error::new(ErrorKind::InvalidData, "Invalid pcap packet"))
+            }
+            true => {
+                let direction = match u16::from_be_bytes([packet[0], packet[1]]) {
+                    SLL_OUTGOING => Direction::Send,
+                    _ => Direction::Recv,
+                };
+                (direction, udp_payload(&packet[SLL_HEADER_LEN..])?.1)
+            }
+            false => {
+                let (src, payload) = udp_payload(&packet)?;
+                let direction = match src == *client.get_or_insert(src) {
+                    true => Direction::Send,
+                    false => Direction::Recv,
+                };
+                (direction, payload)
+            }
+        };
+        events.push_back((direction, payload.to_vec()));
+    }
+    Ok(events)
+}
+
+// Returns the source address and payload of a raw IPv4/IPv6 UDP packet
+fn udp_payload(packet: &[u8]) -> Result<(SocketAddr, &[u8])> {
+    let invalid = || Error::new(ErrorKind::InvalidData, "Invalid pcap packet");
+    let (ip, header_len): (IpAddr, usize) = match packet.first().map(|b| b >> 4) {
+        Some(4) if packet.len() >= 20 && packet[9] == 17 => {
+            let ip: [u8; 4] = packet[12..16].try_into().unwrap();
+            (IpAddr::from(ip), ((packet[0] & 0x0F) as usize) * 4)
+        }
+        Some(6) if packet.len() >= 40 && packet[6] == 17 => {
+            let ip: [u8; 16] = packet[8..24].try_into().unwrap();
+            (IpAddr::from(ip), 40)
+        }
+        _ => return Err(invalid()),
+    };
+    if packet.len() < header_len + 8 {
+        return Err(invalid());
+    }
+    let port = u16::from_be_bytes([packet[header_len], packet[header_len + 1]]);
+    Ok((SocketAddr::new(ip, port), &packet[header_len + 8..]))
+}
+
+// Linux cooked capture header of a raw IPv4/IPv6 packet
+fn sll_header(packet_type: u16, ip: &[u8]) -> Vec<u8> {
+    let protocol: u16 = match ip[0] >> 4 {
+        4 => 0x0800,
+        _ => 0x86DD,
+    };
+    let mut header = vec![];
+    header.extend(packet_type.to_be_bytes());
+    header.extend(0xFFFEu16.to_be_bytes()); // ARPHRD_NONE, no link-layer header
+    header.extend(0u16.to_be_bytes()); // No link-layer address
+    header.extend([0u8; 8]);
+    header.extend(protocol.to_be_bytes());
+    header
+}
+
+// Build a raw IPv4/IPv6 UDP packet around the payload
+fn ip_packet(src: SocketAddr, dst: SocketAddr, data: &[u8]) -> Vec<u8> {
+    let udp_len = (8 + data.len()) as u16;
+    let mut packet = vec![];
+    match (ipv4(src.ip()), ipv4(dst.ip())) {
+        (Some(src_ip), Some(dst_ip)) => {
+            packet.extend([0x45, 0x00]);
+            packet.extend((20 + udp_len).to_be_bytes());
+            packet.extend([0x00, 0x00, 0x40, 0x00, 0x40, 17, 0x00, 0x00]);
+            packet.extend(src_ip.octets());
+            packet.extend(dst_ip.octets());
+            let checksum = ipv4_checksum(&packet);
+            packet[10..12].copy_from_slice(&checksum.to_be_bytes());
+        }
+        _ => {
+            packet.extend([0x60, 0x00, 0x00, 0x00]);
+            packet.extend(udp_len.to_be_bytes());
+            packet.extend([17, 64]);
+            packet.extend(ipv6_octets(src.ip()));
+            packet.extend(ipv6_octets(dst.ip()));
+        }
+    }
+    packet.extend(src.port().to_be_bytes());
+    packet.extend(dst.port().to_be_bytes());
+    packet.extend(udp_len.to_be_bytes());
+    packet.extend([0x00, 0x00]); // No UDP checksum
+    packet.extend(data);
+    packet
+}
+
+fn ipv4(ip: IpAddr) -> Option<Ipv4Addr> {
+    match ip {
+        IpAddr::V4(ip) => Some(ip),
+        IpAddr::V6(ip) => ip.to_ipv4_mapped(),
+    }
+}
+
+fn ipv6_octets(ip: IpAddr) -> [u8; 16] {
+    match ip {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped().octets(),
+        IpAddr::V6(ip) => ip.octets(),
+    }
+}
+
+fn ipv4_checksum(header: &[u8]) -> u16 {
+    let mut sum: u32 = header
+        .chunks(2)
+        .map(|c| u16::from_be_bytes([c[0], c[1]]) as u32)
+        .sum();
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
@@ -1,21 +1,46 @@
+use crate::builder::{no_master, ClientConfig, QueryEvent};
+use crate::cancel::{CancelToken, PartialQuery, Stop, DRAIN_GRACE};
+use crate::capture::{Direction, Recorder, Replay};
+use crate::fanout::RegionResults;
 use crate::filter::Filter;
 use crate::region::Region;
+use crate::runtime::{join_all, until, AsyncUdpSocket, DefaultUdpSocket};
+use crate::shard::{ShardQueue, ShardStrategy};
+use crate::shared::{SharedMSQClient, Template};
+use crate::socket::SocketOptions;
+use crate::transport::{Transport, TransportThread};
 
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use crate::packet_ext::{ReadPacketExt, WritePacketExt};
-use std::io::{Cursor, Error, ErrorKind, Result};
-use tokio::net::UdpSocket;
+use byteorder::{BigEndian, ReadBytesExt};
+use crate::packet_ext::ReadPacketExt;
+use crate::query_packet::{encode_request, REPLY_HEADER};
+use std::io::{Cursor, Error, ErrorKind, Read, Result};
+use std::net::SocketAddr;
+use std::time::Instant;
 
 /// The primary MSQ client driver (async)
 ///
-/// * Requires feature: `async` (Turned **on** by default)
+/// * Requires feature: `async` (Turned **on** by default), or one of
+///   `async-std` and `smol`
 /// * Intended to be used with [`Filter`] and [`Region`].
-/// * This uses the [`tokio`] asynchronous UDP Socket to achieve an
-/// async MSQ client driver.
+/// * Generic over the [`AsyncUdpSocket`] of an async runtime, so it runs on
+///   any executor without spawning tasks. [`new`](#method.new) uses the
+///   [`DefaultUdpSocket`](crate::DefaultUdpSocket), and
+///   [`create`](#method.create) any other, such as
+///   `MSQClient::<SmolUdpSocket>::create()`.
+/// * Can also send and receive through any [`Transport`] given to
+///   [`with_transport`](#method.with_transport).
+/// * Timeouts, retries, rate limits, several master servers and hooks are
+///   set up with [`MSQClientBuilder`](crate::MSQClientBuilder).
+/// * Queries take `&mut self`. To run several at once from cloned handles,
+///   see [`into_shared`](#method.into_shared).
+/// * Queries are cancellation safe: the next query after one whose future
+///   was dropped runs on a new socket, which the replies left behind cannot
+///   reach. On a fixed port, a socket given to the client or a
+///   [`Transport`], they are discarded instead.
 /// * The non-async/blocking version of this: [`MSQClientBlock`](crate::MSQClientBlock)
 ///
 /// ## Quick Start
-/// ```rust
+/// ```rust,no_run
 /// use msq::{MSQClient, Region, Filter};
 /// use std::io::Result;
 ///
@@ -37,28 +62,258 @@ use tokio::net::UdpSocket;
 ///     Ok(())
 /// }
 /// ```
-pub struct MSQClient {
-    sock: UdpSocket,
+pub struct MSQClient<S: AsyncUdpSocket = DefaultUdpSocket> {
+    sock: Socket<S>,
+    options: SocketOptions,
     max_servers: usize,
+    recorder: Option<Recorder>,
+    config: ClientConfig,
+    unfinished: bool,
+    // Whether the last query was stopped before its end, by a deadline, a
+    // cancellation or its future being dropped, with requests still out
+    abandoned: bool,
+    // Whether the client bound its socket with its options, so that it can
+    // bind another one like it
+    bound: bool,
+}
+
+// Where the datagrams of a client go
+enum Socket<S> {
+    Udp(S),
+    Transport(TransportThread),
 }
 
 impl MSQClient {
     /// Create a new MSQClient variable and binds the UDP socket to `0.0.0.0:0`
     pub async fn new() -> Result<MSQClient> {
-        let sock = UdpSocket::bind("0.0.0.0:0").await?;
+        Self::create().await
+    }
+
+    /// Create a new MSQClient variable and binds its UDP socket with the
+    /// given [`SocketOptions`]
+    ///
+    /// # Arguments
+    /// * `options` - [`SocketOptions`] such as the local address and port
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use msq::{MSQClient, SocketOptions};
+    /// use std::io::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let options = SocketOptions::new().ip("::".parse().unwrap()).port(27005);
+    ///     let mut client = MSQClient::with_options(&options).await?;
+    ///     client.connect("hl2master.steampowered.com:27011").await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn with_options(options: &SocketOptions) -> Result<MSQClient> {
+        Self::create_with_options(options).await
+    }
+
+    /// Create a new MSQClient variable on an existing UDP socket
+    ///
+    /// # Arguments
+    /// * `sock` - A bound [`std`] UDP socket
+    pub async fn from_socket(sock: std::net::UdpSocket) -> Result<MSQClient> {
+        Self::create_from_socket(sock).await
+    }
+
+    /// Create a new MSQClient variable sending and receiving through the
+    /// given [`Transport`] instead of an [`AsyncUdpSocket`]
+    ///
+    /// The transport is driven on a thread of its own, so waiting for it
+    /// never blocks the runtime, and the timeouts and cancellations of the
+    /// client apply to it as to a UDP socket.
+    ///
+    /// # Arguments
+    /// * `transport` - [`Transport`] to use instead of a UDP socket
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use msq::{MSQClient, UdpTransport, Region, Filter};
+    /// use std::io::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let mut client = MSQClient::with_transport(UdpTransport::bind("0.0.0.0:0")?);
+    ///     client.connect("hl2master.steampowered.com:27011").await?;
+    ///     let servers = client.query(Region::Europe, Filter::new().appid(240)).await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_transport<T: Transport + 'static>(transport: T) -> MSQClient {
+        Self::create_with_transport(transport)
+    }
+
+    /// Do a single query in one function
+    ///
+    /// # Arguments
+    /// * `master_server` - The address of the master server to fetch the query from
+    /// * `max_servers` - The maximum amount of servers to query
+    /// * `region` - [`Region`] enum (`Region::USEast` - `Region::Africa` / `Region::All`)
+    /// * `filter` - [`Filter`] builder (EX: `Filter::new().appid(240).map("de_dust2")`)
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use msq::{MSQClient, Region, Filter};
+    /// use std::io::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let servers_list = MSQClient::single_query(
+    ///             "hl2master.steampowered.com:27011",
+    ///             256,
+    ///             Region::Europe,
+    ///             Filter::new().appid(240)).await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn single_query(master_server: &str, max_servers: usize, region: Region, filter: Filter) -> Result<Vec<String>> {
+        let mut client = Self::new().await?;
+        client.connect(master_server).await?;
+        client.max_servers_on_query(max_servers);
+        client.query(region, filter).await
+    }
+}
+
+impl<S: AsyncUdpSocket> MSQClient<S> {
+    /// Create a new MSQClient variable on the [`AsyncUdpSocket`] `S` and
+    /// binds it to `0.0.0.0:0`
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// use msq::{MSQClient, SmolUdpSocket, Region, Filter};
+    ///
+    /// smol::block_on(async {
+    ///     let mut client = MSQClient::<SmolUdpSocket>::create().await?;
+    ///     client.connect("hl2master.steampowered.com:27011").await?;
+    ///     client.query(Region::Europe, Filter::new().appid(240)).await
+    /// });
+    /// ```
+    pub async fn create() -> Result<MSQClient<S>> {
+        let sock = S::bind("0.0.0.0:0").await?;
         Ok(MSQClient {
-            sock: sock,
+            sock: Socket::Udp(sock),
+            options: SocketOptions::new(),
+            max_servers: 64,
+            recorder: None,
+            config: ClientConfig::default(),
+            unfinished: false,
+            abandoned: false,
+            bound: true,
+        })
+    }
+
+    /// Create a new MSQClient variable on the [`AsyncUdpSocket`] `S` and
+    /// binds it with the given [`SocketOptions`]
+    ///
+    /// # Arguments
+    /// * `options` - [`SocketOptions`] such as the local address and port
+    pub async fn create_with_options(options: &SocketOptions) -> Result<MSQClient<S>> {
+        let mut client = Self::create_from_socket(options.bind()?).await?;
+        client.options = options.clone();
+        client.bound = true;
+        Ok(client)
+    }
+
+    /// Create a new MSQClient variable on the [`AsyncUdpSocket`] `S` wrapping
+    /// an existing UDP socket
+    ///
+    /// # Arguments
+    /// * `sock` - A bound [`std`] UDP socket
+    pub async fn create_from_socket(sock: std::net::UdpSocket) -> Result<MSQClient<S>> {
+        let options = SocketOptions::new().ip(sock.local_addr()?.ip());
+        Ok(MSQClient {
+            sock: Socket::Udp(S::from_std(sock)?),
+            options,
+            max_servers: 64,
+            recorder: None,
+            config: ClientConfig::default(),
+            unfinished: false,
+            abandoned: false,
+            bound: false,
+        })
+    }
+
+    /// Create a new MSQClient variable on the [`AsyncUdpSocket`] `S` sending
+    /// and receiving through the given [`Transport`] instead
+    ///
+    /// # Arguments
+    /// * `transport` - [`Transport`] to use instead of a UDP socket
+    pub fn create_with_transport<T: Transport + 'static>(transport: T) -> MSQClient<S> {
+        MSQClient {
+            sock: Socket::Transport(TransportThread::spawn(Box::new(transport))),
+            options: SocketOptions::new(),
             max_servers: 64,
+            recorder: None,
+            config: ClientConfig::default(),
+            unfinished: false,
+            abandoned: false,
+            bound: false,
+        }
+    }
+
+    // A client like this one on a socket of its own
+    pub(crate) async fn from_template(template: &Template) -> Result<MSQClient<S>> {
+        let mut client = Self::create_with_options(&template.options).await?;
+        client.connect_sock(&template.master).await?;
+        client.config = template.config.clone();
+        client.max_servers = template.max_servers;
+        Ok(client)
+    }
+
+    fn template(&self) -> Result<Template> {
+        if matches!(self.sock, Socket::Transport(_)) {
+            return Err(Error::new(ErrorKind::Unsupported, "Only a client on a UDP socket can be shared"));
+        }
+        Ok(Template {
+            options: self.options.clone().port(0),
+            master: self.peer_addr()?.to_string(),
+            config: self.config.clone(),
+            max_servers: self.max_servers,
         })
     }
 
+    /// Turn the client into a [`SharedMSQClient`], which can be cloned and
+    /// run any amount of queries at once
+    ///
+    /// The client has to be connected to its master server. It runs the
+    /// first query, and the next ones run on new sockets bound with the same
+    /// [`SocketOptions`] on a random port. A client sending through a
+    /// [`Transport`] or a [`Replay`] cannot open more of them, and fails with
+    /// [`ErrorKind::Unsupported`].
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use msq::{MSQClient, Region, Filter};
+    /// use std::io::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let mut client = MSQClient::new().await?;
+    ///     client.connect("hl2master.steampowered.com:27011").await?;
+    ///     let client = client.into_shared()?;
+    ///     let (css, tf2) = tokio::join!(
+    ///         client.query(Region::Europe, Filter::new().appid(240)),
+    ///         client.query(Region::Europe, Filter::new().appid(440)),
+    ///     );
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn into_shared(self) -> Result<SharedMSQClient<S>> {
+        let template = self.template()?;
+        Ok(SharedMSQClient::new(self, template))
+    }
+
     /// Connect the client to the given master server address/hostname
     ///
     /// # Arguments
     /// * `master_server_addr` - The master server's hostname/ip address
     ///
     /// # Example
-    /// ```
+    /// ```rust,no_run
     /// use msq::MSQClient;
     /// use std::io::Result;
     ///
@@ -70,18 +325,90 @@ impl MSQClient {
     /// }
     /// ```
     pub async fn connect(&mut self, master_server_addr: &str) -> Result<()> {
-        self.sock.connect(master_server_addr).await?;
+        self.connect_sock(master_server_addr).await?;
+        self.config.masters = vec![master_server_addr.to_string()];
+        self.config.master = 0;
         Ok(())
     }
 
+    async fn connect_sock(&mut self, master_server_addr: &str) -> Result<()> {
+        match &mut self.sock {
+            Socket::Udp(sock) => sock.connect(master_server_addr).await,
+            Socket::Transport(transport) => transport.connect(master_server_addr).await,
+        }
+    }
+
+    // Apply the settings of MSQClientBuilder, connecting to the first
+    // master server that can be connected to
+    pub(crate) async fn configure(&mut self, config: ClientConfig, masters: Vec<String>) -> Result<()> {
+        self.config = config;
+        let mut last = None;
+        for (i, master) in masters.iter().enumerate() {
+            match self.connect_sock(master).await {
+                Ok(()) => {
+                    self.config.master = i;
+                    self.config.masters = masters;
+                    return Ok(());
+                }
+                Err(e) => last = Some(e),
+            }
+        }
+        match masters.is_empty() {
+            true => Ok(()),
+            false => Err(no_master(last)),
+        }
+    }
+
+    /// Record every datagram the client sends and receives
+    ///
+    /// # Arguments
+    /// * `recorder` - [`Recorder`] writing to a capture file
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use msq::{MSQClient, Recorder, CaptureFormat};
+    /// use std::io::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let mut client = MSQClient::new().await?;
+    ///     client.connect("hl2master.steampowered.com:27011").await?;
+    ///     client.record(Recorder::create("session.txt", CaptureFormat::Text)?);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn record(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    /// Replay a recorded session instead of using the network
+    ///
+    /// # Arguments
+    /// * `replay` - [`Replay`] of a capture file
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use msq::{MSQClient, Replay};
+    /// use std::io::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let mut client = MSQClient::new().await?;
+    ///     client.replay(Replay::open("session.txt")?);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn replay(&mut self, replay: Replay) {
+        self.sock = Socket::Transport(TransportThread::spawn(Box::new(replay)));
+    }
+
     /// Query with raw bytes
     ///
     /// # Arguments
     /// * `region_code` - Region code in u8 (`0x00 - 0x07 / 0xFF`)
     /// * `filter_str` - Filter in plain string (EX: `\\appid\\240\\map\\de_dust2`)
     pub async fn query_raw(&mut self, region_code: u8, filter_str: &str) -> Result<Vec<String>> {
-        self.send(region_code, filter_str, "0.0.0.0:0").await?; // First Packet
-        Ok(self.recv(region_code, filter_str).await?)
+        self.query_until(region_code, filter_str, self.max_servers, Stop::default()).await
     }
 
     /// Query with specified Region and Filter
@@ -92,89 +419,376 @@ impl MSQClient {
     /// * `region` - [`Region`] enum (`Region::USEast` - `Region::Africa` / `Region::All`)
     /// * `filter` - [`Filter`] builder (EX: `Filter::new().appid(240).map("de_dust2")`)
     pub async fn query(&mut self, region: Region, filter: Filter) -> Result<Vec<String>> {
-        Ok(self.query_raw(region.as_u8(), &filter.as_string()).await?)
+        self.query_raw(region.as_u8(), &filter.as_string()).await
     }
 
-    /// Do a single query in one function
+    /// Query with specified Region and Filter, stopping at the given deadline
+    ///
+    /// A query still running at the deadline fails with
+    /// [`ErrorKind::TimedOut`], carrying a [`PartialQuery`] of the servers
+    /// received so far. Timeouts and retries of the replies still apply.
+    ///
+    /// # Arguments
+    /// * `region` - [`Region`] enum (`Region::USEast` - `Region::Africa` / `Region::All`)
+    /// * `filter` - [`Filter`] builder (EX: `Filter::new().appid(240).map("de_dust2")`)
+    /// * `deadline` - When to stop the query
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use msq::{MSQClient, Region, Filter};
+    /// use std::io::Result;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let mut client = MSQClient::new().await?;
+    ///     client.connect("hl2master.steampowered.com:27011").await?;
+    ///     let deadline = Instant::now() + Duration::from_secs(5);
+    ///     let servers = client
+    ///         .query_with_deadline(Region::Europe, Filter::new().appid(240), deadline).await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn query_with_deadline(&mut self, region: Region, filter: Filter, deadline: Instant) -> Result<Vec<String>> {
+        let stop = Stop { deadline: Some(deadline), cancel: None };
+        self.query_until(region.as_u8(), &filter.as_string(), self.max_servers, stop).await
+    }
+
+    /// Query with specified Region and Filter, stopping once the given
+    /// [`CancelToken`] is cancelled
+    ///
+    /// A cancelled query fails with [`ErrorKind::Interrupted`], carrying a
+    /// [`PartialQuery`] of the servers received so far.
     ///
     /// # Arguments
-    /// * `master_server` - The address of the master server to fetch the query from
-    /// * `max_servers` - The maximum amount of servers to query
     /// * `region` - [`Region`] enum (`Region::USEast` - `Region::Africa` / `Region::All`)
     /// * `filter` - [`Filter`] builder (EX: `Filter::new().appid(240).map("de_dust2")`)
+    /// * `cancel` - [`CancelToken`] to stop the query with
     ///
     /// # Example
+    /// ```rust,no_run
+    /// use msq::{MSQClient, CancelToken, Region, Filter};
+    /// use std::io::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let mut client = MSQClient::new().await?;
+    ///     client.connect("hl2master.steampowered.com:27011").await?;
+    ///     let cancel = CancelToken::new();
+    ///     let query = client.query_with_cancel(Region::Europe, Filter::new().appid(240), &cancel);
+    ///     let (servers, _) = tokio::join!(query, async { cancel.cancel() });
+    ///     Ok(())
+    /// }
     /// ```
+    pub async fn query_with_cancel(&mut self, region: Region, filter: Filter, cancel: &CancelToken) -> Result<Vec<String>> {
+        let stop = Stop { deadline: None, cancel: Some(cancel) };
+        self.query_until(region.as_u8(), &filter.as_string(), self.max_servers, stop).await
+    }
+
+    /// Query each of the given regions with the same Filter
+    ///
+    /// Returns the servers of all regions deduplicated and tagged with the
+    /// [`Region`] they came from, along with per-region counts and errors.
+    /// A failing region does not fail the whole query.
+    ///
+    /// Each region is queried concurrently on its own socket, bound with the
+    /// same [`SocketOptions`] as this client on a random port and connected
+    /// to the same master server. Clients that are recording or
+    /// using a [`Transport`] (such as a [`Replay`]) query the regions one
+    /// after another instead, so a recorded session can be replayed in the
+    /// same order.
+    ///
+    /// # Arguments
+    /// * `regions` - [`Region`]s to query (EX: `&[Region::Europe, Region::Asia]`)
+    /// * `filter` - [`Filter`] builder (EX: `Filter::new().appid(240).map("de_dust2")`)
+    ///
+    /// # Example
+    /// ```rust,no_run
     /// use msq::{MSQClient, Region, Filter};
     /// use std::io::Result;
     ///
     /// #[tokio::main]
     /// async fn main() -> Result<()> {
-    ///     let servers_list = MSQClient::single_query(
-    ///             "hl2master.steampowered.com:27011",
-    ///             256,
-    ///             Region::Europe,
-    ///             Filter::new().appid(240)).await?;
+    ///     let mut client = MSQClient::new().await?;
+    ///     client.connect("hl2master.steampowered.com:27011").await?;
+    ///     client.max_servers_on_query(256);
+    ///
+    ///     let results = client
+    ///         .query_regions(&[Region::Europe, Region::Asia], Filter::new().appid(240)).await?;
+    ///     for server in results.servers() {
+    ///         println!("{:?} {}", server.region, server.addr);
+    ///     }
     ///     Ok(())
     /// }
     /// ```
-    pub async fn single_query(master_server: &str, max_servers: usize, region: Region, filter: Filter) -> Result<Vec<String>> {
-        let mut client = Self::new().await?;
-        client.connect(master_server).await?;
-        client.max_servers_on_query(max_servers);
-        client.query(region, filter).await
+    pub async fn query_regions(&mut self, regions: &[Region], filter: Filter) -> Result<RegionResults> {
+        let filter_str = filter.as_string();
+        if self.recorder.is_some() || matches!(self.sock, Socket::Transport(_)) {
+            let mut results = RegionResults::new();
+            for region in regions {
+                let servers = self.query_raw(region.as_u8(), &filter_str).await;
+                results.push(*region, servers);
+            }
+            return Ok(results);
+        }
+
+        let template = self.template()?;
+        let queries = regions.iter().map(|region| {
+            let filter_str = filter_str.clone();
+            let template = &template;
+            async move {
+                let mut client = Self::from_template(template).await?;
+                client.query_raw(region.as_u8(), &filter_str).await
+            }
+        });
+
+        let mut results = RegionResults::new();
+        for (region, servers) in regions.iter().zip(join_all(queries.collect()).await) {
+            results.push(*region, servers);
+        }
+        Ok(results)
     }
 
-    async fn send(&mut self, region_code: u8, filter_str: &str, address: &str) -> Result<()> {
-        let mut cursor: Cursor<Vec<u8>> = Cursor::new(vec![]);
-        cursor.write_u8(0x31)?;
-        cursor.write_u8(region_code)?;
-        cursor.write_cstring(address)?;
-        cursor.write_cstring(filter_str)?;
-        self.sock.send(cursor.get_ref()).await?;
+    /// Query with specified Region and Filter, split into as many
+    /// sub-queries as needed to get past the master server's result cap
+    ///
+    /// Sub-queries that return [`ShardStrategy::shard_limit`] servers are
+    /// split further according to the [`ShardStrategy`]. The results are
+    /// merged and deduplicated. The limit set by
+    /// [`max_servers_on_query`](#method.max_servers_on_query) does not apply
+    /// to sharded queries.
+    ///
+    /// A sub-query that fails stops the sharded query with its error kind,
+    /// carrying a [`PartialQuery`] of the servers of the sub-queries before
+    /// it, where `pages` is the amount of sub-queries that completed.
+    ///
+    /// # Arguments
+    /// * `region` - [`Region`] enum (`Region::USEast` - `Region::Africa` / `Region::All`)
+    /// * `filter` - [`Filter`] builder (EX: `Filter::new().appid(240).map("de_dust2")`)
+    /// * `strategy` - [`ShardStrategy`] describing how to split the query
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use msq::{MSQClient, Region, Filter, ShardStrategy};
+    /// use std::io::Result;
+    /// use std::time::Duration;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let mut client = MSQClient::new().await?;
+    ///     client.connect("hl2master.steampowered.com:27011").await?;
+    ///
+    ///     let strategy = ShardStrategy::new()
+    ///         .regions()
+    ///         .dedicated()
+    ///         .gameaddr()
+    ///         .pacing(Duration::from_millis(500));
+    ///     let servers = client
+    ///         .query_sharded(Region::All, Filter::new().appid(440), &strategy).await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn query_sharded(&mut self, region: Region, filter: Filter, strategy: &ShardStrategy) -> Result<Vec<String>> {
+        let mut queue = ShardQueue::new(strategy, region.as_u8(), filter);
+        let limit = strategy.get_shard_limit();
+        while let Some((shard, paced)) = queue.next() {
+            if paced {
+                S::sleep(strategy.get_pacing()).await;
+            }
+            let servers = match self.query_until(shard.region_code, &shard.filter_string(), limit, Stop::default()).await {
+                Ok(servers) => servers,
+                // Keep the servers of the shards queried before this one
+                Err(e) => {
+                    let pages = queue.completed();
+                    let partial = PartialQuery { servers: queue.into_servers(), pages };
+                    return Err(Error::new(e.kind(), partial));
+                }
+            };
+            queue.complete(shard, servers);
+        }
+        Ok(queue.into_servers())
+    }
+
+    async fn query_until(&mut self, region_code: u8, filter_str: &str, max_servers: usize, stop: Stop<'_>) -> Result<Vec<String>> {
+        if self.unfinished {
+            self.reset().await?;
+        }
+        self.unfinished = true;
+        self.abandoned = true;
+        let mut failovers = 0;
+        loop {
+            self.send(region_code, filter_str, "0.0.0.0:0").await?; // First Packet
+            match self.recv(region_code, filter_str, max_servers, stop).await {
+                // Start over on the next master server
+                Err(e) if e.kind() == ErrorKind::TimedOut
+                    && PartialQuery::from_error(&e).is_none()
+                    && failovers + 1 < self.config.masters.len() =>
+                {
+                    failovers += 1;
+                    let master = self.config.next_master();
+                    self.connect_sock(&master).await?;
+                }
+                result => {
+                    self.unfinished = result.is_err();
+                    self.abandoned = matches!(&result, Err(e) if PartialQuery::from_error(e).is_some());
+                    return result;
+                }
+            }
+        }
+    }
+
+    // Leave the replies to a query that was stopped behind, on a new socket
+    // unless the port is fixed, as they would reach it all the same, or the
+    // socket was not bound by the client. Otherwise drain them.
+    async fn reset(&mut self) -> Result<()> {
+        let peer = match &self.sock {
+            Socket::Udp(sock) if self.abandoned && self.bound && self.options.local_addr().port() == 0 => {
+                sock.peer_addr()?
+            }
+            _ => {
+                self.drain().await;
+                return Ok(());
+            }
+        };
+        let sock = S::from_std(self.options.bind()?)?;
+        sock.connect(&peer.to_string()).await?;
+        self.sock = Socket::Udp(sock);
         Ok(())
     }
 
-    async fn recv(&mut self, region_code: u8, filter_str: &str) -> Result<Vec<String>> {
+    // Discard the replies left over by a query that did not finish
+    async fn drain(&mut self) {
+        let mut buf: [u8; 2048] = [0x00; 2048];
+        loop {
+            let deadline = Some(Instant::now() + DRAIN_GRACE);
+            let received = match &mut self.sock {
+                Socket::Udp(sock) => until::<S, _, _>(deadline, None, sock.recv(&mut buf)).await,
+                Socket::Transport(transport) => transport.recv(&mut buf, deadline).await,
+            };
+            if received.is_err() {
+                break;
+            }
+        }
+    }
+
+    async fn send(&mut self, region_code: u8, filter_str: &str, address: &str) -> Result<()> {
+        if let Some(wait) = self.config.pacing() {
+            S::sleep(wait).await;
+        }
+        let packet = encode_request(region_code, address, filter_str)?;
+        match &mut self.sock {
+            Socket::Udp(sock) => sock.send(&packet).await?,
+            Socket::Transport(transport) => transport.send(&packet, None).await?,
+        };
+        self.config.sent(region_code, address);
+        self.capture(Direction::Send, &packet)
+    }
+
+    async fn recv(&mut self, region_code: u8, filter_str: &str, max_servers: usize, stop: Stop<'_>) -> Result<Vec<String>> {
         let mut buf: [u8; 2048] = [0x00; 2048];
         let mut servers: Vec<String> = vec![];
         let mut end_of_list = false;
+        let mut attempt = 0;
+        let mut pages = 0;
+        let mut last_batch: Option<String> = None;
+        let mut reply = self.config.deadline();
         while !end_of_list {
-            let len = self.sock.recv(&mut buf).await?;
+            stop.check(&servers, pages)?;
+            let wait = stop.wait(reply, false);
+            let received = match &mut self.sock {
+                Socket::Udp(sock) => until::<S, _, _>(wait, stop.cancel, sock.recv(&mut buf)).await,
+                Socket::Transport(transport) => {
+                    until::<S, _, _>(wait, stop.cancel, transport.recv(&mut buf, wait)).await
+                }
+            };
+            let len = match received {
+                Ok(len) => len,
+                Err(e) => {
+                    stop.check(&servers, pages)?;
+                    match e.kind() {
+                        ErrorKind::TimedOut if attempt < self.config.retry.attempts() => (),
+                        _ => return Err(e),
+                    }
+                    attempt += 1;
+                    self.config.emit(QueryEvent::Retry { master: self.config.master(), attempt });
+                    S::sleep(self.config.retry.delay(attempt)).await;
+                    let seed = servers.last().cloned().unwrap_or_else(|| "0.0.0.0:0".to_string());
+                    self.send(region_code, filter_str, &seed).await?;
+                    reply = self.config.deadline();
+                    continue;
+                }
+            };
+            attempt = 0;
+            self.capture(Direction::Recv, &buf[..len])?;
             let mut cursor = Cursor::new(buf[..len].to_vec());
 
-            if cursor.read_u8_veccheck(&vec![0xFF, 0xFF, 0xFF, 0xFF, 0x66, 0x0A])? {
+            let mut batch: Vec<String> = vec![];
+            if cursor.read_u8_veccheck(&REPLY_HEADER)? {
                 let end = cursor.get_ref().len() as u64;
                 while cursor.position() < end {
                     let mut addr: [u8; 4] = [0; 4];
-                    for i in 0..=3 {
-                        addr[i] = cursor.read_u8()?;
-                    }
+                    cursor.read_exact(&mut addr)?;
                     let port = cursor.read_u16::<BigEndian>()?;
-                    let addr_str =
-                        format!("{}.{}.{}.{}:{}", addr[0], addr[1], addr[2], addr[3], port);
+                    batch.push(format!("{}.{}.{}.{}:{}", addr[0], addr[1], addr[2], addr[3], port));
+                }
+            } else {
+                return Err(Error::other("Mismatched starting sequence"));
+            }
 
-                    // If end of IP list
-                    if servers.len() >= self.max_servers || addr_str == "0.0.0.0:0" {
-                        end_of_list = true;
-                        break;
-                    }
+            // A duplicated or late reply to an earlier request
+            if !batch.is_empty() && batch.first() == last_batch.as_ref() {
+                continue;
+            }
+            last_batch = batch.first().cloned();
+            pages += 1;
+            self.config.emit(QueryEvent::Reply { master: self.config.master(), servers: batch.len() });
 
-                    servers.push(addr_str);
+            for addr_str in batch {
+                // If end of IP list
+                if servers.len() >= max_servers || addr_str == "0.0.0.0:0" {
+                    end_of_list = true;
+                    break;
                 }
-            } else {
-                return Err(Error::new(ErrorKind::Other, "Mismatched starting sequence"));
+
+                servers.push(addr_str);
             }
 
-            if !end_of_list && servers.len() > 0 {
-                self.send(region_code, filter_str, &servers.last().unwrap())
+            if !end_of_list && !servers.is_empty() {
+                self.send(region_code, filter_str, servers.last().unwrap())
                     .await?;
+                reply = self.config.deadline();
             }
         }
 
         Ok(servers)
     }
 
+    fn capture(&self, direction: Direction, data: &[u8]) -> Result<()> {
+        match &self.recorder {
+            Some(recorder) => {
+                let unknown = SocketAddr::from(([0, 0, 0, 0], 0));
+                let local = self.local_addr().unwrap_or(unknown);
+                let peer = self.peer_addr().unwrap_or(unknown);
+                recorder.record(direction, local, peer, data)
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        match &self.sock {
+            Socket::Udp(sock) => sock.local_addr(),
+            Socket::Transport(transport) => transport.local_addr(),
+        }
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        match &self.sock {
+            Socket::Udp(sock) => sock.peer_addr(),
+            Socket::Transport(transport) => transport.peer_addr(),
+        }
+    }
+
     /// Set maximum amount of servers in a given query
     ///
     /// # Arguments
@@ -1,21 +1,36 @@
+use crate::builder::{no_master, ClientConfig, QueryEvent};
+use crate::cancel::{CancelToken, PartialQuery, Stop, DRAIN_GRACE};
+use crate::capture::{Direction, Recorder, Replay};
+use crate::fanout::RegionResults;
 use crate::filter::Filter;
 use crate::region::Region;
+use crate::shard::{ShardQueue, ShardStrategy};
+use crate::shared::{SharedMSQClientBlock, Template};
+use crate::socket::SocketOptions;
+use crate::transport::{Transport, UdpTransport};
 
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use crate::packet_ext::{ReadPacketExt, WritePacketExt};
-use std::io::{Cursor, Error, ErrorKind, Result};
-use std::net::UdpSocket;
+use byteorder::{BigEndian, ReadBytesExt};
+use crate::packet_ext::ReadPacketExt;
+use crate::query_packet::{encode_request, REPLY_HEADER};
+use std::io::{Cursor, Error, ErrorKind, Read, Result};
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Instant;
 
 /// The primary MSQ client driver (non-async)
 ///
 /// * Requires feature: `non-async` (Turned **on** by default)
 /// * Intended to be used with [`Filter`] and [`Region`].
 /// * This uses the [`std`] non-asynchronous UDP Socket to
-/// achieve an non-async MSQ client driver.
+///   achieve an non-async MSQ client driver, or any other [`Transport`]
+///   given to [`with_transport`](#method.with_transport).
+/// * Timeouts, retries, rate limits, several master servers and hooks are
+///   set up with [`MSQClientBuilder`](crate::MSQClientBuilder).
+/// * Queries take `&mut self`. To run several at once from cloned handles,
+///   see [`into_shared`](#method.into_shared).
 /// * The async version of this: [`MSQClient`](crate::MSQClient)
 ///
 /// ## Quick Start
-/// ```rust
+/// ```rust,no_run
 /// use msq::{MSQClientBlock, Region, Filter};
 /// use std::io::Result;
 ///
@@ -37,18 +52,154 @@ use std::net::UdpSocket;
 /// }
 /// ```
 pub struct MSQClientBlock {
-    sock: UdpSocket,
+    sock: Box<dyn Transport>,
+    options: SocketOptions,
     max_servers: usize,
+    recorder: Option<Recorder>,
+    config: ClientConfig,
+    unfinished: bool,
+    // Whether the last query was stopped before its end, by a deadline or a
+    // cancellation, with requests still out
+    abandoned: bool,
+    // Whether the client is on a UDP socket of its own, rather than on a
+    // transport, so that more clients like it can be made
+    udp: bool,
+    // Whether the client bound its socket with its options, so that it can
+    // bind another one like it
+    bound: bool,
 }
 
 impl MSQClientBlock {
     /// Create a new MSQClient variable and binds the UDP socket to `0.0.0.0:0`
     pub fn new() -> Result<Self> {
-        let sock = UdpSocket::bind("0.0.0.0:0")?;
-        Ok(Self {
-            sock: sock,
+        let mut client = Self::with_transport(UdpTransport::bind("0.0.0.0:0")?);
+        client.udp = true;
+        client.bound = true;
+        Ok(client)
+    }
+
+    /// Create a new MSQClient variable and binds its UDP socket with the
+    /// given [`SocketOptions`]
+    ///
+    /// # Arguments
+    /// * `options` - [`SocketOptions`] such as the local address and port
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use msq::{MSQClientBlock, SocketOptions};
+    /// use std::io::Result;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let options = SocketOptions::new().ip("::".parse().unwrap()).port(27005);
+    ///     let mut client = MSQClientBlock::with_options(&options)?;
+    ///     client.connect("hl2master.steampowered.com:27011")?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_options(options: &SocketOptions) -> Result<Self> {
+        let mut client = Self::from_socket(options.bind()?);
+        client.options = options.clone();
+        client.bound = true;
+        Ok(client)
+    }
+
+    /// Create a new MSQClient variable on an existing UDP socket
+    ///
+    /// # Arguments
+    /// * `sock` - A bound [`std`] UDP socket
+    pub fn from_socket(sock: UdpSocket) -> Self {
+        let local = sock.local_addr();
+        let mut client = Self::with_transport(UdpTransport::from(sock));
+        client.udp = true;
+        if let Ok(local) = local {
+            client.options = SocketOptions::new().ip(local.ip());
+        }
+        client
+    }
+
+    /// Create a new MSQClient variable sending and receiving through the
+    /// given [`Transport`]
+    ///
+    /// # Arguments
+    /// * `transport` - [`Transport`] to use instead of a UDP socket
+    ///
+    /// # Example
+    /// ```rust
+    /// use msq::{MSQClientBlock, ChannelTransport, Transport, Region, Filter};
+    /// use std::io::Result;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let (transport, mut master) = ChannelTransport::pair();
+    ///     // A reply with a single server, 10.0.0.1:27015, and the end of the list
+    ///     master.send(b"\xFF\xFF\xFF\xFF\x66\x0A\x0A\x00\x00\x01\x69\x87\x00\x00\x00\x00\x00\x00", None)?;
+    ///
+    ///     let mut client = MSQClientBlock::with_transport(transport);
+    ///     let servers = client.query(Region::All, Filter::new().appid(240))?;
+    ///     assert_eq!(servers, vec!["10.0.0.1:27015"]);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_transport<T: Transport + 'static>(transport: T) -> Self {
+        Self {
+            sock: Box::new(transport),
+            options: SocketOptions::new(),
             max_servers: 64,
-        })
+            recorder: None,
+            config: ClientConfig::default(),
+            unfinished: false,
+            abandoned: false,
+            udp: false,
+            bound: false,
+        }
+    }
+
+    // A client like this one on a socket of its own
+    pub(crate) fn from_template(template: &Template) -> Result<Self> {
+        let mut client = Self::with_options(&template.options)?;
+        client.sock.connect(&template.master)?;
+        client.config = template.config.clone();
+        client.max_servers = template.max_servers;
+        Ok(client)
+    }
+
+    /// Turn the client into a [`SharedMSQClientBlock`], which can be cloned,
+    /// sent to other threads and run any amount of queries at once
+    ///
+    /// The client has to be connected to its master server. It runs the
+    /// first query, and the next ones run on new sockets bound with the same
+    /// [`SocketOptions`] on a random port. A client sending through a
+    /// [`Transport`] or a [`Replay`] cannot open more of them, and fails with
+    /// [`ErrorKind::Unsupported`].
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use msq::{MSQClientBlock, Region, Filter};
+    /// use std::io::Result;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut client = MSQClientBlock::new()?;
+    ///     client.connect("hl2master.steampowered.com:27011")?;
+    ///     let client = client.into_shared()?;
+    ///     let tf2 = std::thread::spawn({
+    ///         let client = client.clone();
+    ///         move || client.query(Region::Europe, Filter::new().appid(440))
+    ///     });
+    ///     let css = client.query(Region::Europe, Filter::new().appid(240))?;
+    ///     let tf2 = tf2.join().unwrap()?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn into_shared(self) -> Result<SharedMSQClientBlock> {
+        if !self.udp {
+            return Err(Error::new(ErrorKind::Unsupported, "Only a client on a UDP socket can be shared"));
+        }
+        let template = Template {
+            options: self.options.clone().port(0),
+            master: self.sock.peer_addr()?.to_string(),
+            config: self.config.clone(),
+            max_servers: self.max_servers,
+        };
+        Ok(SharedMSQClientBlock::new(self, template))
     }
 
     /// Connect the client to the given master server address/hostname
@@ -57,7 +208,7 @@ impl MSQClientBlock {
     /// * `master_server_addr` - The master server's hostname/ip address
     ///
     /// # Example
-    /// ```
+    /// ```rust,no_run
     /// use msq::MSQClientBlock;
     /// use std::io::Result;
     ///
@@ -69,17 +220,82 @@ impl MSQClientBlock {
     /// ```
     pub fn connect(&mut self, master_server_addr: &str) -> Result<()> {
         self.sock.connect(master_server_addr)?;
+        self.config.masters = vec![master_server_addr.to_string()];
+        self.config.master = 0;
         Ok(())
     }
 
+    // Apply the settings of MSQClientBuilder, connecting to the first
+    // master server that can be connected to
+    pub(crate) fn configure(&mut self, config: ClientConfig, masters: Vec<String>) -> Result<()> {
+        self.config = config;
+        let mut last = None;
+        for (i, master) in masters.iter().enumerate() {
+            match self.sock.connect(master) {
+                Ok(()) => {
+                    self.config.master = i;
+                    self.config.masters = masters;
+                    return Ok(());
+                }
+                Err(e) => last = Some(e),
+            }
+        }
+        match masters.is_empty() {
+            true => Ok(()),
+            false => Err(no_master(last)),
+        }
+    }
+
+    /// Record every datagram the client sends and receives
+    ///
+    /// # Arguments
+    /// * `recorder` - [`Recorder`] writing to a capture file
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use msq::{MSQClientBlock, Recorder, CaptureFormat};
+    /// use std::io::Result;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut client = MSQClientBlock::new()?;
+    ///     client.connect("hl2master.steampowered.com:27011")?;
+    ///     client.record(Recorder::create("session.txt", CaptureFormat::Text)?);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn record(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    /// Replay a recorded session instead of using the network
+    ///
+    /// # Arguments
+    /// * `replay` - [`Replay`] of a capture file
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use msq::{MSQClientBlock, Replay};
+    /// use std::io::Result;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut client = MSQClientBlock::new()?;
+    ///     client.replay(Replay::open("session.txt")?);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn replay(&mut self, replay: Replay) {
+        self.sock = Box::new(replay);
+        self.udp = false;
+        self.bound = false;
+    }
+
     /// Query with raw bytes
     ///
     /// # Arguments
     /// * `region_code` - Region code in u8 (`0x00 - 0x07 / 0xFF`)
     /// * `filter_str` - Filter in plain string (EX: `\\appid\\240\\map\\de_dust2`)
     pub fn query_raw(&mut self, region_code: u8, filter_str: &str) -> Result<Vec<String>> {
-        self.send(region_code, filter_str, "0.0.0.0:0")?; // First Packet
-        Ok(self.recv(region_code, filter_str)?)
+        self.query_until(region_code, filter_str, self.max_servers, Stop::default())
     }
 
     /// Query with specified Region and Filter
@@ -90,7 +306,168 @@ impl MSQClientBlock {
     /// * `region` - [`Region`] enum (`Region::USEast` - `Region::Africa` / `Region::All`)
     /// * `filter` - [`Filter`] builder (EX: `Filter::new().appid(240).map("de_dust2")`)
     pub fn query(&mut self, region: Region, filter: Filter) -> Result<Vec<String>> {
-        Ok(self.query_raw(region.as_u8(), &filter.as_string())?)
+        self.query_raw(region.as_u8(), &filter.as_string())
+    }
+
+    /// Query with specified Region and Filter, stopping at the given deadline
+    ///
+    /// A query still running at the deadline fails with
+    /// [`ErrorKind::TimedOut`], carrying a [`PartialQuery`] of the servers
+    /// received so far. Timeouts and retries of the replies still apply.
+    ///
+    /// # Arguments
+    /// * `region` - [`Region`] enum (`Region::USEast` - `Region::Africa` / `Region::All`)
+    /// * `filter` - [`Filter`] builder (EX: `Filter::new().appid(240).map("de_dust2")`)
+    /// * `deadline` - When to stop the query
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use msq::{MSQClientBlock, Region, Filter};
+    /// use std::io::Result;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut client = MSQClientBlock::new()?;
+    ///     client.connect("hl2master.steampowered.com:27011")?;
+    ///     let deadline = Instant::now() + Duration::from_secs(5);
+    ///     let servers = client
+    ///         .query_with_deadline(Region::Europe, Filter::new().appid(240), deadline)?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn query_with_deadline(&mut self, region: Region, filter: Filter, deadline: Instant) -> Result<Vec<String>> {
+        let stop = Stop { deadline: Some(deadline), cancel: None };
+        self.query_until(region.as_u8(), &filter.as_string(), self.max_servers, stop)
+    }
+
+    /// Query with specified Region and Filter, stopping once the given
+    /// [`CancelToken`] is cancelled
+    ///
+    /// A cancelled query fails with [`ErrorKind::Interrupted`], carrying a
+    /// [`PartialQuery`] of the servers received so far.
+    ///
+    /// # Arguments
+    /// * `region` - [`Region`] enum (`Region::USEast` - `Region::Africa` / `Region::All`)
+    /// * `filter` - [`Filter`] builder (EX: `Filter::new().appid(240).map("de_dust2")`)
+    /// * `cancel` - [`CancelToken`] to stop the query with
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use msq::{MSQClientBlock, CancelToken, Region, Filter};
+    /// use std::io::Result;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut client = MSQClientBlock::new()?;
+    ///     client.connect("hl2master.steampowered.com:27011")?;
+    ///     let cancel = CancelToken::new();
+    ///     let stop = cancel.clone();
+    ///     std::thread::spawn(move || stop.cancel());
+    ///     let servers = client.query_with_cancel(Region::Europe, Filter::new().appid(240), &cancel);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn query_with_cancel(&mut self, region: Region, filter: Filter, cancel: &CancelToken) -> Result<Vec<String>> {
+        let stop = Stop { deadline: None, cancel: Some(cancel) };
+        self.query_until(region.as_u8(), &filter.as_string(), self.max_servers, stop)
+    }
+
+    /// Query each of the given regions with the same Filter
+    ///
+    /// Returns the servers of all regions deduplicated and tagged with the
+    /// [`Region`] they came from, along with per-region counts and errors.
+    /// A failing region does not fail the whole query.
+    ///
+    /// Each region is queried one after another.
+    ///
+    /// # Arguments
+    /// * `regions` - [`Region`]s to query (EX: `&[Region::Europe, Region::Asia]`)
+    /// * `filter` - [`Filter`] builder (EX: `Filter::new().appid(240).map("de_dust2")`)
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use msq::{MSQClientBlock, Region, Filter};
+    /// use std::io::Result;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut client = MSQClientBlock::new()?;
+    ///     client.connect("hl2master.steampowered.com:27011")?;
+    ///     client.max_servers_on_query(256);
+    ///
+    ///     let results = client
+    ///         .query_regions(&[Region::Europe, Region::Asia], Filter::new().appid(240))?;
+    ///     for server in results.servers() {
+    ///         println!("{:?} {}", server.region, server.addr);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn query_regions(&mut self, regions: &[Region], filter: Filter) -> Result<RegionResults> {
+        let filter_str = filter.as_string();
+        let mut results = RegionResults::new();
+        for region in regions {
+            let servers = self.query_raw(region.as_u8(), &filter_str);
+            results.push(*region, servers);
+        }
+        Ok(results)
+    }
+
+    /// Query with specified Region and Filter, split into as many
+    /// sub-queries as needed to get past the master server's result cap
+    ///
+    /// Sub-queries that return [`ShardStrategy::shard_limit`] servers are
+    /// split further according to the [`ShardStrategy`]. The results are
+    /// merged and deduplicated. The limit set by
+    /// [`max_servers_on_query`](#method.max_servers_on_query) does not apply
+    /// to sharded queries.
+    ///
+    /// A sub-query that fails stops the sharded query with its error kind,
+    /// carrying a [`PartialQuery`] of the servers of the sub-queries before
+    /// it, where `pages` is the amount of sub-queries that completed.
+    ///
+    /// # Arguments
+    /// * `region` - [`Region`] enum (`Region::USEast` - `Region::Africa` / `Region::All`)
+    /// * `filter` - [`Filter`] builder (EX: `Filter::new().appid(240).map("de_dust2")`)
+    /// * `strategy` - [`ShardStrategy`] describing how to split the query
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use msq::{MSQClientBlock, Region, Filter, ShardStrategy};
+    /// use std::io::Result;
+    /// use std::time::Duration;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut client = MSQClientBlock::new()?;
+    ///     client.connect("hl2master.steampowered.com:27011")?;
+    ///
+    ///     let strategy = ShardStrategy::new()
+    ///         .regions()
+    ///         .dedicated()
+    ///         .gameaddr()
+    ///         .pacing(Duration::from_millis(500));
+    ///     let servers = client
+    ///         .query_sharded(Region::All, Filter::new().appid(440), &strategy)?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn query_sharded(&mut self, region: Region, filter: Filter, strategy: &ShardStrategy) -> Result<Vec<String>> {
+        let mut queue = ShardQueue::new(strategy, region.as_u8(), filter);
+        let limit = strategy.get_shard_limit();
+        while let Some((shard, paced)) = queue.next() {
+            if paced {
+                std::thread::sleep(strategy.get_pacing());
+            }
+            let servers = match self.query_until(shard.region_code, &shard.filter_string(), limit, Stop::default()) {
+                Ok(servers) => servers,
+                // Keep the servers of the shards queried before this one
+                Err(e) => {
+                    let pages = queue.completed();
+                    let partial = PartialQuery { servers: queue.into_servers(), pages };
+                    return Err(Error::new(e.kind(), partial));
+                }
+            };
+            queue.complete(shard, servers);
+        }
+        Ok(queue.into_servers())
     }
 
     /// Do a single query in one function
@@ -102,7 +479,7 @@ impl MSQClientBlock {
     /// * `filter` - [`Filter`] builder (EX: `Filter::new().appid(240).map("de_dust2")`)
     ///
     /// # Example
-    /// ```
+    /// ```rust,no_run
     /// use msq::{MSQClientBlock, Region, Filter};
     /// use std::io::Result;
     ///
@@ -122,55 +499,151 @@ impl MSQClientBlock {
         client.query(region, filter)
     }
 
-    fn send(&mut self, region_code: u8, filter_str: &str, address: &str) -> Result<()> {
-        let mut cursor: Cursor<Vec<u8>> = Cursor::new(vec![]);
-        cursor.write_u8(0x31)?;
-        cursor.write_u8(region_code)?;
-        cursor.write_cstring(address)?;
-        cursor.write_cstring(filter_str)?;
-        self.sock.send(cursor.get_ref())?;
+    fn query_until(&mut self, region_code: u8, filter_str: &str, max_servers: usize, stop: Stop<'_>) -> Result<Vec<String>> {
+        if self.unfinished {
+            self.reset()?;
+        }
+        self.unfinished = true;
+        self.abandoned = true;
+        let mut failovers = 0;
+        loop {
+            self.send(region_code, filter_str, "0.0.0.0:0")?; // First Packet
+            match self.recv(region_code, filter_str, max_servers, stop) {
+                // Start over on the next master server
+                Err(e) if e.kind() == ErrorKind::TimedOut
+                    && PartialQuery::from_error(&e).is_none()
+                    && failovers + 1 < self.config.masters.len() =>
+                {
+                    failovers += 1;
+                    let master = self.config.next_master();
+                    self.sock.connect(&master)?;
+                }
+                result => {
+                    self.unfinished = result.is_err();
+                    self.abandoned = matches!(&result, Err(e) if PartialQuery::from_error(e).is_some());
+                    return result;
+                }
+            }
+        }
+    }
+
+    // Leave the replies to a query that was stopped behind, on a new socket
+    // unless the port is fixed, as they would reach it all the same, or the
+    // socket was not bound by the client. Otherwise drain them.
+    fn reset(&mut self) -> Result<()> {
+        if !self.abandoned || !self.bound || self.options.local_addr().port() != 0 {
+            self.drain();
+            return Ok(());
+        }
+        let mut sock = UdpTransport::from(self.options.bind()?);
+        sock.connect(&self.sock.peer_addr()?.to_string())?;
+        self.sock = Box::new(sock);
         Ok(())
     }
 
-    fn recv(&mut self, region_code: u8, filter_str: &str) -> Result<Vec<String>> {
+    // Discard the replies left over by a query that did not finish
+    fn drain(&mut self) {
+        let mut buf: [u8; 2048] = [0x00; 2048];
+        while self.sock.recv(&mut buf, Some(Instant::now() + DRAIN_GRACE)).is_ok() {}
+    }
+
+    fn send(&mut self, region_code: u8, filter_str: &str, address: &str) -> Result<()> {
+        if let Some(wait) = self.config.pacing() {
+            std::thread::sleep(wait);
+        }
+        let packet = encode_request(region_code, address, filter_str)?;
+        self.sock.send(&packet, None)?;
+        self.config.sent(region_code, address);
+        self.capture(Direction::Send, &packet)
+    }
+
+    fn recv(&mut self, region_code: u8, filter_str: &str, max_servers: usize, stop: Stop<'_>) -> Result<Vec<String>> {
         let mut buf: [u8; 2048] = [0x00; 2048];
         let mut servers: Vec<String> = vec![];
         let mut end_of_list = false;
+        let mut attempt = 0;
+        let mut pages = 0;
+        let mut last_batch: Option<String> = None;
+        let mut reply = self.config.deadline();
         while !end_of_list {
-            let len = self.sock.recv(&mut buf)?;
+            stop.check(&servers, pages)?;
+            let wait = stop.wait(reply, true);
+            let len = match self.sock.recv(&mut buf, wait) {
+                Ok(len) => len,
+                Err(e) => {
+                    stop.check(&servers, pages)?;
+                    // Only waited long enough to check for a cancellation
+                    let polled = wait != reply && wait.is_some_and(|wait| Instant::now() >= wait);
+                    match e.kind() {
+                        ErrorKind::TimedOut if polled => continue,
+                        ErrorKind::TimedOut if attempt < self.config.retry.attempts() => (),
+                        _ => return Err(e),
+                    }
+                    attempt += 1;
+                    self.config.emit(QueryEvent::Retry { master: self.config.master(), attempt });
+                    std::thread::sleep(self.config.retry.delay(attempt));
+                    let seed = servers.last().cloned().unwrap_or_else(|| "0.0.0.0:0".to_string());
+                    self.send(region_code, filter_str, &seed)?;
+                    reply = self.config.deadline();
+                    continue;
+                }
+            };
+            attempt = 0;
+            self.capture(Direction::Recv, &buf[..len])?;
             let mut cursor = Cursor::new(buf[..len].to_vec());
 
-            if cursor.read_u8_veccheck(&vec![0xFF, 0xFF, 0xFF, 0xFF, 0x66, 0x0A])? {
+            let mut batch: Vec<String> = vec![];
+            if cursor.read_u8_veccheck(&REPLY_HEADER)? {
                 let end = cursor.get_ref().len() as u64;
                 while cursor.position() < end {
                     let mut addr: [u8; 4] = [0; 4];
-                    for i in 0..=3 {
-                        addr[i] = cursor.read_u8()?;
-                    }
+                    cursor.read_exact(&mut addr)?;
                     let port = cursor.read_u16::<BigEndian>()?;
-                    let addr_str =
-                        format!("{}.{}.{}.{}:{}", addr[0], addr[1], addr[2], addr[3], port);
+                    batch.push(format!("{}.{}.{}.{}:{}", addr[0], addr[1], addr[2], addr[3], port));
+                }
+            } else {
+                return Err(Error::other("Mismatched starting sequence"));
+            }
 
-                    // If end of IP list
-                    if servers.len() >= self.max_servers || addr_str == "0.0.0.0:0" {
-                        end_of_list = true;
-                        break;
-                    }
+            // A duplicated or late reply to an earlier request
+            if !batch.is_empty() && batch.first() == last_batch.as_ref() {
+                continue;
+            }
+            last_batch = batch.first().cloned();
+            pages += 1;
+            self.config.emit(QueryEvent::Reply { master: self.config.master(), servers: batch.len() });
 
-                    servers.push(addr_str);
+            for addr_str in batch {
+                // If end of IP list
+                if servers.len() >= max_servers || addr_str == "0.0.0.0:0" {
+                    end_of_list = true;
+                    break;
                 }
-            } else {
-                return Err(Error::new(ErrorKind::Other, "Mismatched starting sequence"));
+
+                servers.push(addr_str);
             }
 
-            if !end_of_list && servers.len() > 0 {
-                self.send(region_code, filter_str, &servers.last().unwrap())?;
+            if !end_of_list && !servers.is_empty() {
+                self.send(region_code, filter_str, servers.last().unwrap())?;
+                reply = self.config.deadline();
             }
         }
 
         Ok(servers)
     }
 
+    fn capture(&self, direction: Direction, data: &[u8]) -> Result<()> {
+        match &self.recorder {
+            Some(recorder) => {
+                let unknown = SocketAddr::from(([0, 0, 0, 0], 0));
+                let local = self.sock.local_addr().unwrap_or(unknown);
+                let peer = self.sock.peer_addr().unwrap_or(unknown);
+                recorder.record(direction, local, peer, data)
+            }
+            None => Ok(()),
+        }
+    }
+
     /// Set maximum amount of servers in a given query
     ///
     /// # Arguments
@@ -0,0 +1,346 @@
+use crate::filter::Filter;
+use crate::region::Region;
+
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+use std::str::FromStr;
+use toml::{Table, Value};
+
+#[cfg(feature = "async")]
+use crate::client_async::MSQClient;
+
+#[cfg(feature = "non-async")]
+use crate::client_blocking::MSQClientBlock;
+
+const MASTER_KEYS: [&str; 2] = ["address", "max_servers"];
+const QUERY_KEYS: [&str; 4] = ["master", "region", "filter", "max_servers"];
+const STRING_KEYS: [&str; 6] = ["gamedir", "map", "name", "version", "gameaddr", "tags"];
+const NUMBER_KEYS: [&str; 2] = ["appid", "napp"];
+const FLAG_KEYS: [&str; 9] = [
+    "dedicated", "secure", "linux", "password", "full", "empty", "proxy", "whitelisted",
+    "collapse_addr_hash",
+];
+
+/// A master server profile of a [`Queries`] file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MasterProfile {
+    /// The master server's address (EX: `hl2master.steampowered.com:27011`)
+    pub address: String,
+    /// Default maximum amount of servers of the queries using this master
+    pub max_servers: usize,
+}
+
+/// A named query of a [`Queries`] file, with its master profile resolved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamedQuery {
+    /// Name of the query (EX: `tf2_eu`)
+    pub name: String,
+    /// The master server's address
+    pub master: String,
+    /// The [`Region`] to query
+    pub region: Region,
+    /// The filter string (EX: `\\appid\\440`)
+    pub filter: String,
+    /// Maximum amount of servers in the query
+    pub max_servers: usize,
+}
+
+/// Queries - Named queries and master server profiles loaded from TOML
+///
+/// * Requires feature: `config` (Turned **off** by default)
+/// * Master server profiles are tables under `masters`, with an `address`
+///   and an optional `max_servers` (Defaults to 256).
+/// * Queries are tables under `queries`, with:
+///   * `master` - Name of the profile, optional if there is a single
+///     profile or one named `default`
+///   * `region` - Any name accepted by [`Region`]'s `FromStr`, or its
+///     code (Defaults to `all`)
+///   * `max_servers` - Overrides the profile's `max_servers`
+///   * `filter` - A raw filter string, or the [`Filter`] conditions
+///     `appid`, `napp`, `gamedir`, `map`, `name`, `version`, `gameaddr`,
+///     `tags` (comma separated), and the `true`/`false` flags `dedicated`,
+///     `secure`, `linux`, `password`, `full`, `empty`, `proxy`,
+///     `whitelisted` and `collapse_addr_hash`
+/// * Everything is validated at load time: unknown keys, wrong types,
+///   unknown profiles and invalid regions are errors.
+///
+/// # Quick Start
+/// ```toml
+/// [masters.steam]
+/// address = "hl2master.steampowered.com:27011"
+/// max_servers = 1000
+///
+/// [queries.tf2_eu]
+/// region = "eu"
+/// appid = 440
+/// empty = false
+///
+/// [queries.css_dust]
+/// region = "us-east"
+/// filter = '\appid\240\map\de_dust2'
+/// max_servers = 100
+/// ```
+/// ```rust,no_run
+/// use msq::Queries;
+/// use std::io::Result;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let servers = Queries::load("queries.toml")?.run("tf2_eu").await?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Queries {
+    masters: BTreeMap<String, MasterProfile>,
+    queries: BTreeMap<String, NamedQuery>,
+}
+
+impl Queries {
+    /// Load and validate a TOML file
+    ///
+    /// # Arguments
+    /// * `path` - Path of the file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Queries> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    /// Returns the query with the given name
+    ///
+    /// # Arguments
+    /// * `name` - Name of the query (EX: `tf2_eu`)
+    pub fn get(&self, name: &str) -> Option<&NamedQuery> {
+        self.queries.get(name)
+    }
+
+    /// Returns the master server profile with the given name
+    ///
+    /// # Arguments
+    /// * `name` - Name of the profile (EX: `steam`)
+    pub fn master(&self, name: &str) -> Option<&MasterProfile> {
+        self.masters.get(name)
+    }
+
+    /// Returns the names of the queries, in alphabetical order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.queries.keys().map(String::as_str)
+    }
+
+    /// Run the query with the given name using [`MSQClient`]
+    ///
+    /// * Requires feature: `async` (Turned **on** by default)
+    ///
+    /// # Arguments
+    /// * `name` - Name of the query (EX: `tf2_eu`)
+    #[cfg(feature = "async")]
+    pub async fn run(&self, name: &str) -> Result<Vec<String>> {
+        let query = self.query(name)?;
+        let mut client = MSQClient::new().await?;
+        client.connect(&query.master).await?;
+        client.max_servers_on_query(query.max_servers);
+        client.query_raw(query.region.as_u8(), &query.filter).await
+    }
+
+    /// Run the query with the given name using [`MSQClientBlock`]
+    ///
+    /// * Requires feature: `non-async` (Turned **on** by default)
+    ///
+    /// # Arguments
+    /// * `name` - Name of the query (EX: `tf2_eu`)
+    #[cfg(feature = "non-async")]
+    pub fn run_block(&self, name: &str) -> Result<Vec<String>> {
+        let query = self.query(name)?;
+        let mut client = MSQClientBlock::new()?;
+        client.connect(&query.master)?;
+        client.max_servers_on_query(query.max_servers);
+        client.query_raw(query.region.as_u8(), &query.filter)
+    }
+
+    #[cfg(any(feature = "async", feature = "non-async"))]
+    fn query(&self, name: &str) -> Result<&NamedQuery> {
+        self.get(name)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("Unknown query: {}", name)))
+    }
+}
+
+impl FromStr for Queries {
+    type Err = Error;
+
+    /// Parse and validate TOML
+    fn from_str(s: &str) -> Result<Self> {
+        let root: Table = s
+            .parse()
+            .map_err(|e: toml::de::Error| invalid(format!("Invalid TOML: {}", e.message())))?;
+        for key in root.keys() {
+            if key != "masters" && key != "queries" {
+                return Err(invalid(format!("Unknown key: {}", key)));
+            }
+        }
+
+        let mut masters = BTreeMap::new();
+        for (name, value) in tables(&root, "masters")? {
+            let context = format!("masters.{}", name);
+            check_keys(value, &context, &[&MASTER_KEYS])?;
+            let address = string(value, "address", &context)?
+                .ok_or_else(|| invalid(format!("{}: Missing address", context)))?;
+            if address.is_empty() {
+                return Err(invalid(format!("{}: Empty address", context)));
+            }
+            let max_servers = integer(value, "max_servers", &context)?.unwrap_or(256) as usize;
+            masters.insert(name.clone(), MasterProfile { address, max_servers });
+        }
+
+        let mut queries = BTreeMap::new();
+        for (name, value) in tables(&root, "queries")? {
+            let context = format!("queries.{}", name);
+            check_keys(value, &context, &[&QUERY_KEYS, &STRING_KEYS, &NUMBER_KEYS, &FLAG_KEYS])?;
+
+            let profile = match string(value, "master", &context)? {
+                Some(master) => masters
+                    .get(&master)
+                    .ok_or_else(|| invalid(format!("{}: Unknown master: {}", context, master)))?,
+                None => default_master(&masters)
+                    .ok_or_else(|| invalid(format!("{}: Missing master", context)))?,
+            };
+            let region = match value.get("region") {
+                None => Region::All,
+                Some(Value::String(region)) => region
+                    .parse()
+                    .map_err(|_| invalid(format!("{}: Invalid region: {}", context, region)))?,
+                Some(Value::Integer(code)) => u8::try_from(*code)
+                    .map(Region::from)
+                    .map_err(|_| invalid(format!("{}: Invalid region: {}", context, code)))?,
+                Some(_) => return Err(invalid(format!("{}: Invalid region", context))),
+            };
+            let max_servers = integer(value, "max_servers", &context)?
+                .map(|max| max as usize)
+                .unwrap_or(profile.max_servers);
+
+            queries.insert(
+                name.clone(),
+                NamedQuery {
+                    name: name.clone(),
+                    master: profile.address.clone(),
+                    region,
+                    filter: filter(value, &context)?,
+                    max_servers,
+                },
+            );
+        }
+
+        Ok(Queries { masters, queries })
+    }
+}
+
+fn invalid(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+// The `default` profile, or the only one
+fn default_master(masters: &BTreeMap<String, MasterProfile>) -> Option<&MasterProfile> {
+    masters.get("default").or(match masters.len() {
+        1 => masters.values().next(),
+        _ => None,
+    })
+}
+
+fn tables<'a>(root: &'a Table, key: &str) -> Result<Vec<(&'a String, &'a Table)>> {
+    match root.get(key) {
+        None => Ok(vec![]),
+        Some(Value::Table(entries)) => entries
+            .iter()
+            .map(|(name, value)| match value {
+                Value::Table(table) => Ok((name, table)),
+                _ => Err(invalid(format!("{}.{}: Expected a table", key, name))),
+            })
+            .collect(),
+        Some(_) => Err(invalid(format!("{}: Expected a table", key))),
+    }
+}
+
+fn check_keys(table: &Table, context: &str, allowed: &[&[&str]]) -> Result<()> {
+    for key in table.keys() {
+        if !allowed.iter().any(|keys| keys.contains(&key.as_str())) {
+            return Err(invalid(format!("{}: Unknown key: {}", context, key)));
+        }
+    }
+    Ok(())
+}
+
+fn string(table: &Table, key: &str, context: &str) -> Result<Option<String>> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value.clone())),
+        Some(_) => Err(invalid(format!("{}: Expected a string: {}", context, key))),
+    }
+}
+
+fn integer(table: &Table, key: &str, context: &str) -> Result<Option<u32>> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(Value::Integer(value)) => u32::try_from(*value)
+            .map(Some)
+            .map_err(|_| invalid(format!("{}: Out of range: {}", context, key))),
+        Some(_) => Err(invalid(format!("{}: Expected an integer: {}", context, key))),
+    }
+}
+
+fn flag(table: &Table, key: &str, context: &str) -> Result<Option<bool>> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(Value::Boolean(value)) => Ok(Some(*value)),
+        Some(_) => Err(invalid(format!("{}: Expected true or false: {}", context, key))),
+    }
+}
+
+// The raw filter string, or the filter built from the conditions
+fn filter(table: &Table, context: &str) -> Result<String> {
+    if let Some(raw) = string(table, "filter", context)? {
+        let conditions = [&STRING_KEYS[..], &NUMBER_KEYS, &FLAG_KEYS].concat();
+        if let Some(key) = conditions.iter().find(|key| table.contains_key(**key)) {
+            return Err(invalid(format!("{}: filter conflicts with {}", context, key)));
+        }
+        return Ok(raw);
+    }
+
+    let mut filter = Filter::new();
+    if let Some(appid) = integer(table, "appid", context)? {
+        filter = filter.appid(appid);
+    }
+    if let Some(napp) = integer(table, "napp", context)? {
+        filter = filter.napp(napp);
+    }
+    let strings = [
+        ("gamedir", Filter::gamedir as fn(Filter, &str) -> Filter),
+        ("map", Filter::map),
+        ("name", Filter::name_match),
+        ("version", Filter::version_match),
+        ("gameaddr", Filter::gameaddr),
+    ];
+    for (key, set) in strings {
+        if let Some(value) = string(table, key, context)? {
+            filter = set(filter, &value);
+        }
+    }
+    if let Some(tags) = string(table, "tags", context)? {
+        filter = filter.gametype(&tags.split(',').collect());
+    }
+    let flags = [
+        ("dedicated", Filter::dedicated as fn(Filter, bool) -> Filter),
+        ("secure", Filter::secure),
+        ("linux", Filter::linux),
+        ("password", Filter::password),
+        ("full", Filter::full),
+        ("empty", Filter::empty),
+        ("proxy", Filter::proxy),
+        ("whitelisted", Filter::whitelisted),
+        ("collapse_addr_hash", Filter::collapse_addr_hash),
+    ];
+    for (key, set) in flags {
+        if let Some(value) = flag(table, key, context)? {
+            filter = set(filter, value);
+        }
+    }
+    Ok(filter.as_string())
+}
@@ -0,0 +1,215 @@
+use crate::a2s_packet::ServerInfo;
+use crate::snapshot::{ServerRecord, Snapshot};
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// A change of a server between two snapshots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerChange {
+    /// The server changed maps
+    Map { old: String, new: String },
+    /// The amount of players changed
+    Players { old: u8, new: u8 },
+    /// The server was renamed
+    Name { old: String, new: String },
+    /// The server was updated to another version
+    Version { old: String, new: String },
+}
+
+/// A server that is in both snapshots, with its changes
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangedServer {
+    /// The server's address (EX: `10.0.0.1:27015`)
+    pub addr: String,
+    /// The server in the older snapshot
+    pub old: ServerRecord,
+    /// The server in the newer snapshot
+    pub new: ServerRecord,
+    /// What changed, in the order: map, players, name, version
+    pub changes: Vec<ServerChange>,
+}
+
+/// SnapshotDiff - The servers added, removed and changed between two snapshots
+///
+/// * Requires feature: `export` (Turned **off** by default)
+/// * Returned by [`Snapshot::diff`]
+/// * Servers are matched by address, the first record of an address wins
+///   if it was queried in multiple regions.
+/// * Changes are only detected for servers with A2S info in both snapshots.
+///
+/// # Example
+/// ```rust
+/// use msq::{Region, Snapshot};
+///
+/// let old = Snapshot::from_query(&vec![String::from("10.0.0.1:27015")], Region::All, "");
+/// let new = Snapshot::from_query(&vec![String::from("10.0.0.2:27015")], Region::All, "");
+/// let diff = old.diff(&new);
+/// assert_eq!(diff.added()[0].addr, "10.0.0.2:27015");
+/// assert_eq!(diff.removed()[0].addr, "10.0.0.1:27015");
+/// assert_eq!(diff.summary().to_string(), "1 added, 1 removed, 0 changed");
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SnapshotDiff {
+    added: Vec<ServerRecord>,
+    removed: Vec<ServerRecord>,
+    changed: Vec<ChangedServer>,
+}
+
+/// Counts of a [`SnapshotDiff`], its `Display` is a one line summary for alerts
+///
+/// EX: `3 added, 1 removed, 4 changed (2 map, 3 players, 0 name, 1 version)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DiffSummary {
+    /// Servers in the newer snapshot only
+    pub added: usize,
+    /// Servers in the older snapshot only
+    pub removed: usize,
+    /// Servers with at least one change
+    pub changed: usize,
+    /// Servers that changed maps
+    pub map: usize,
+    /// Servers with a different amount of players
+    pub players: usize,
+    /// Servers that were renamed
+    pub name: usize,
+    /// Servers that were updated to another version
+    pub version: usize,
+}
+
+impl Snapshot {
+    /// Compare with a newer snapshot
+    ///
+    /// # Arguments
+    /// * `newer` - The snapshot taken after this one
+    pub fn diff(&self, newer: &Snapshot) -> SnapshotDiff {
+        let old = by_addr(self.records());
+        let new = by_addr(newer.records());
+        let old_by_addr: HashMap<&str, &ServerRecord> =
+            old.iter().map(|r| (r.addr.as_str(), *r)).collect();
+        let new_addrs: HashSet<&str> = new.iter().map(|r| r.addr.as_str()).collect();
+        let mut diff = SnapshotDiff::default();
+
+        for record in &new {
+            let Some(previous) = old_by_addr.get(record.addr.as_str()) else {
+                diff.added.push((*record).clone());
+                continue;
+            };
+            let changes = match (&previous.info, &record.info) {
+                (Some(old_info), Some(new_info)) => changes(old_info, new_info),
+                _ => vec![],
+            };
+            if !changes.is_empty() {
+                diff.changed.push(ChangedServer {
+                    addr: record.addr.clone(),
+                    old: (*previous).clone(),
+                    new: (*record).clone(),
+                    changes,
+                });
+            }
+        }
+        diff.removed = old
+            .iter()
+            .filter(|r| !new_addrs.contains(r.addr.as_str()))
+            .map(|r| (*r).clone())
+            .collect();
+        diff
+    }
+}
+
+impl SnapshotDiff {
+    /// Returns the servers in the newer snapshot only
+    pub fn added(&self) -> &[ServerRecord] {
+        &self.added
+    }
+
+    /// Returns the servers in the older snapshot only
+    pub fn removed(&self) -> &[ServerRecord] {
+        &self.removed
+    }
+
+    /// Returns the servers in both snapshots that changed
+    pub fn changed(&self) -> &[ChangedServer] {
+        &self.changed
+    }
+
+    /// Returns `true` if nothing was added, removed or changed
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Returns the counts of the diff
+    pub fn summary(&self) -> DiffSummary {
+        let mut summary = DiffSummary {
+            added: self.added.len(),
+            removed: self.removed.len(),
+            changed: self.changed.len(),
+            ..Default::default()
+        };
+        for change in self.changed.iter().flat_map(|c| &c.changes) {
+            match change {
+                ServerChange::Map { .. } => summary.map += 1,
+                ServerChange::Players { .. } => summary.players += 1,
+                ServerChange::Name { .. } => summary.name += 1,
+                ServerChange::Version { .. } => summary.version += 1,
+            }
+        }
+        summary
+    }
+}
+
+impl fmt::Display for DiffSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} added, {} removed, {} changed",
+            self.added, self.removed, self.changed
+        )?;
+        if self.changed > 0 {
+            write!(
+                f,
+                " ({} map, {} players, {} name, {} version)",
+                self.map, self.players, self.name, self.version
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Records in order, without the later duplicates of an address
+fn by_addr(records: &[ServerRecord]) -> Vec<&ServerRecord> {
+    let mut seen: HashSet<&str> = HashSet::new();
+    records
+        .iter()
+        .filter(|r| seen.insert(r.addr.as_str()))
+        .collect()
+}
+
+fn changes(old: &ServerInfo, new: &ServerInfo) -> Vec<ServerChange> {
+    let mut changes = vec![];
+    if old.map != new.map {
+        changes.push(ServerChange::Map {
+            old: old.map.clone(),
+            new: new.map.clone(),
+        });
+    }
+    if old.players != new.players {
+        changes.push(ServerChange::Players {
+            old: old.players,
+            new: new.players,
+        });
+    }
+    if old.name != new.name {
+        changes.push(ServerChange::Name {
+            old: old.name.clone(),
+            new: new.name.clone(),
+        });
+    }
+    if old.version != new.version {
+        changes.push(ServerChange::Version {
+            old: old.version.clone(),
+            new: new.version.clone(),
+        });
+    }
+    changes
+}
@@ -0,0 +1,89 @@
+use crate::region::Region;
+
+use std::collections::HashSet;
+use std::io::{Error, Result};
+
+/// A server address returned by a multi-region query, tagged with the
+/// [`Region`] it was queried under
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionServer {
+    /// The region the server was returned from
+    pub region: Region,
+    /// The server's address (EX: `127.0.0.1:27015`)
+    pub addr: String,
+}
+
+/// Results of a multi-region query
+///
+/// * Returned by: [`MSQClient::query_regions`](crate::MSQClient::query_regions) and
+///   [`MSQClientBlock::query_regions`](crate::MSQClientBlock::query_regions)
+/// * Servers are deduplicated across regions. A server returned by more than
+///   one region is tagged with the first of those regions in the order the
+///   regions were given.
+/// * A region that failed to query does not fail the other regions, its
+///   error is kept in [`errors`](#method.errors) instead.
+#[derive(Debug)]
+pub struct RegionResults {
+    servers: Vec<RegionServer>,
+    counts: Vec<(Region, usize)>,
+    errors: Vec<(Region, Error)>,
+    seen: HashSet<String>,
+}
+
+impl RegionResults {
+    pub(crate) fn new() -> RegionResults {
+        RegionResults {
+            servers: vec![],
+            counts: vec![],
+            errors: vec![],
+            seen: HashSet::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, region: Region, result: Result<Vec<String>>) {
+        match result {
+            Ok(servers) => {
+                self.counts.push((region, servers.len()));
+                for addr in servers {
+                    if self.seen.insert(addr.clone()) {
+                        self.servers.push(RegionServer { region, addr });
+                    }
+                }
+            }
+            Err(e) => self.errors.push((region, e)),
+        }
+    }
+
+    /// Returns the deduplicated servers of all regions, tagged by region
+    pub fn servers(&self) -> &[RegionServer] {
+        &self.servers
+    }
+
+    /// Returns the deduplicated servers of all regions, tagged by region
+    pub fn into_servers(self) -> Vec<RegionServer> {
+        self.servers
+    }
+
+    /// Returns the amount of servers each successful region returned,
+    /// counted before deduplication
+    pub fn counts(&self) -> &[(Region, usize)] {
+        &self.counts
+    }
+
+    /// Returns the amount of servers the given region returned, or `None`
+    /// if the region was not queried or failed
+    ///
+    /// # Arguments
+    /// * `region` - The [`Region`] to get the count of
+    pub fn count(&self, region: Region) -> Option<usize> {
+        self.counts
+            .iter()
+            .find(|(r, _)| *r == region)
+            .map(|(_, count)| *count)
+    }
+
+    /// Returns the errors of the regions that failed to query
+    pub fn errors(&self) -> &[(Region, Error)] {
+        &self.errors
+    }
+}
@@ -27,8 +27,8 @@ enum FilterPropVal {
 }
 
 impl FilterPropVal {
-    fn from_special(spec: &Vec<FilterProp>) -> FilterPropVal {
-        Self::Special(spec.clone())
+    fn from_special(spec: &[FilterProp]) -> FilterPropVal {
+        Self::Special(spec.to_vec())
     }
 
     fn from_tags(tags: &Vec<&str>) -> FilterPropVal {
@@ -42,7 +42,7 @@ impl FilterPropVal {
     }
 
     fn as_str(&self) -> String {
-        match &*self {
+        match self {
             Self::Special(filterprops) => {
                 let mut sstr = String::from("");
 
@@ -62,7 +62,7 @@ impl FilterPropVal {
             Self::Tags(tags) => {
                 let mut tags_str = String::from("");
                 for tag in tags {
-                    tags_str += &tag;
+                    tags_str += tag;
                     tags_str += ",";
                 }
                 tags_str.pop();
@@ -82,7 +82,7 @@ impl FilterProp {
     fn new(name: &str, value: FilterPropVal) -> FilterProp {
         FilterProp {
             name: String::from(name),
-            value: value,
+            value,
         }
     }
 
@@ -94,11 +94,11 @@ impl FilterProp {
 /// Filter builder - Construct your filter to filter out server results
 ///
 /// * Intended to be used with: [`MSQClient`](crate::MSQClient) and
-/// [`MSQClientBlock`](crate::MSQClientBlock)
+///   [`MSQClientBlock`](crate::MSQClientBlock)
 /// * **NOTE**: Some filters may or may not work as expected depending on
-/// appid/games you try it on. The filter builder methods and string
-/// construction generally follows close to the reference listed out
-/// in the Valve developer wiki.
+///   appid/games you try it on. The filter builder methods and string
+///   construction generally follows close to the reference listed out
+///   in the Valve developer wiki.
 /// * Reference: <https://developer.valvesoftware.com/wiki/Master_Server_Query_Protocol#Filter>
 ///
 /// # Quick Start
@@ -114,6 +114,7 @@ impl FilterProp {
 ///         .gametype(&vec!["friendlyfire", "alltalk"]);
 /// ```
 ///
+#[derive(Clone)]
 pub struct Filter {
     filter_lst: Vec<FilterProp>,
     in_special: bool,
@@ -121,6 +122,12 @@ pub struct Filter {
     special_name: String,
 }
 
+impl Default for Filter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Filter {
     /// Returns a string representing the filters
     #[deprecated(since = "0.2.0", note = "Replaced with as_string (name change)")]
@@ -185,7 +192,7 @@ impl Filter {
 
     // Generic filter: Vector of strings
     fn vecstr(self, name: &str, tags: &Vec<&str>) -> Filter {
-        if tags.len() > 0 {
+        if !tags.is_empty() {
             self.push(name, FilterPropVal::from_tags(tags))
         } else {
             self
@@ -249,6 +256,132 @@ impl Filter {
         self
     }
 
+    // Closes a special filter that was left open and returns the
+    // finished list of filter properties
+    fn into_props(self) -> Vec<FilterProp> {
+        let filter = if self.in_special { self.end() } else { self };
+        filter.filter_lst
+    }
+
+    // Wraps the given properties into a special filter
+    fn special_group(name: &str, props: Vec<FilterProp>) -> Filter {
+        let filter = Filter::new();
+        if props.is_empty() {
+            filter
+        } else {
+            filter.push(name, FilterPropVal::Special(props))
+        }
+    }
+
+    /// Append all of the conditions of another filter into this one
+    ///
+    /// If this filter currently has a nor/nand special filter open, the
+    /// conditions are appended inside of that special filter instead.
+    /// An unclosed special filter in `other` is closed before appending.
+    ///
+    /// # Arguments
+    /// * `other` - The [`Filter`] to append
+    ///
+    /// # Example
+    /// ```
+    /// use msq::Filter;
+    /// let base = Filter::new().map("de_dust2").empty(true);
+    /// let filter = Filter::new()
+    ///     .appid(240)
+    ///     .nand()
+    ///         .extend(base)
+    ///     .end();
+    ///
+    /// assert_eq!(filter.as_string(), "\\appid\\240\\nand\\2\\map\\de_dust2\\noplayers\\1");
+    /// ```
+    pub fn extend(mut self, other: Filter) -> Filter {
+        let props = other.into_props();
+        if self.in_special {
+            self.spec_vec.extend(props);
+        } else {
+            self.filter_lst.extend(props);
+        }
+        self
+    }
+
+    /// Merge two filters, servers must match the conditions of both filters
+    ///
+    /// # Arguments
+    /// * `a` - The first [`Filter`]
+    /// * `b` - The second [`Filter`], appended after `a`
+    ///
+    /// # Example
+    /// ```
+    /// use msq::Filter;
+    /// let base = Filter::new().appid(240);
+    /// let filter = Filter::and(base, Filter::new().map("de_dust2"));
+    ///
+    /// assert_eq!(filter.as_string(), "\\appid\\240\\map\\de_dust2");
+    /// ```
+    pub fn and(a: Filter, b: Filter) -> Filter {
+        Filter::new().extend(a).extend(b)
+    }
+
+    /// Wrap a filter into a NAND special filter, servers matching all of the
+    /// filter's conditions will not be returned
+    ///
+    /// An empty filter produces an empty filter.
+    ///
+    /// # Arguments
+    /// * `filter` - The [`Filter`] to negate
+    ///
+    /// # Example
+    /// ```
+    /// use msq::Filter;
+    /// let filter = Filter::and(
+    ///     Filter::new().appid(240),
+    ///     Filter::not_all(Filter::new().map("de_dust2").empty(true)));
+    ///
+    /// assert_eq!(filter.as_string(), "\\appid\\240\\nand\\2\\map\\de_dust2\\noplayers\\1");
+    /// ```
+    pub fn not_all(filter: Filter) -> Filter {
+        Self::special_group("nand", filter.into_props())
+    }
+
+    /// Create a NOR special filter, servers matching any of the given
+    /// filters will not be returned
+    ///
+    /// Filters with a single condition are placed in the NOR special filter.
+    /// A filter with multiple conditions only matches when all of its
+    /// conditions match, so it gets its own NAND special filter after the
+    /// NOR special filter instead. Empty filters are skipped.
+    ///
+    /// # Arguments
+    /// * `filters` - The [`Filter`]s to exclude
+    ///
+    /// # Example
+    /// ```
+    /// use msq::Filter;
+    /// let filter = Filter::new()
+    ///     .appid(240)
+    ///     .extend(Filter::none_of([
+    ///         Filter::new().map("de_dust2"),
+    ///         Filter::new().map("cs_italy"),
+    ///     ]));
+    ///
+    /// assert_eq!(filter.as_string(), "\\appid\\240\\nor\\2\\map\\de_dust2\\map\\cs_italy");
+    /// ```
+    pub fn none_of<I: IntoIterator<Item = Filter>>(filters: I) -> Filter {
+        let mut props: Vec<FilterProp> = vec![];
+        let mut nands: Vec<Filter> = vec![];
+        for filter in filters {
+            let mut fprops = filter.into_props();
+            if fprops.len() > 1 {
+                nands.push(Self::special_group("nand", fprops));
+            } else {
+                props.append(&mut fprops);
+            }
+        }
+        nands
+            .into_iter()
+            .fold(Self::special_group("nor", props), Filter::and)
+    }
+
     /// Filters if the servers running dedicated
     ///
     /// # Arguments
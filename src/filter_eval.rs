@@ -0,0 +1,193 @@
+use crate::attrs::ServerAttrs;
+
+use std::io::{Error, Result};
+use std::net::SocketAddrV4;
+
+/// A single condition of a parsed filter string
+#[derive(Debug, Clone, PartialEq)]
+enum Cond {
+    Nand(Vec<Cond>),
+    Nor(Vec<Cond>),
+    Prop(String, String),
+}
+
+/// A parsed filter string, evaluated against the attributes of a server
+///
+/// Special filters (nand/nor) take as many following conditions as their
+/// count, where a nested special filter counts as a single condition. This
+/// matches how [`Filter`](crate::Filter) builds its filter strings.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FilterExpr {
+    conds: Vec<Cond>,
+}
+
+impl FilterExpr {
+    pub fn parse(filter_str: &str) -> Result<FilterExpr> {
+        let tokens: Vec<&str> = filter_str.split('\\').collect();
+        // A filter string starts with a backslash, so the first token is empty
+        if tokens.len() > 1 && !tokens[0].is_empty() {
+            return Err(Error::other(format!("Invalid filter: {}", filter_str)));
+        }
+
+        let mut pairs = vec![];
+        let mut rest = tokens.iter().skip(1);
+        while let Some(key) = rest.next() {
+            if key.is_empty() {
+                continue;
+            }
+            let value = rest.next().copied().unwrap_or("");
+            pairs.push((String::from(*key), String::from(value)));
+        }
+
+        let mut pos = 0;
+        let mut conds = vec![];
+        while pos < pairs.len() {
+            conds.push(Self::parse_cond(&pairs, &mut pos)?);
+        }
+        Ok(FilterExpr { conds })
+    }
+
+    fn parse_cond(pairs: &[(String, String)], pos: &mut usize) -> Result<Cond> {
+        let (key, value) = &pairs[*pos];
+        *pos += 1;
+        match key.as_str() {
+            "nand" | "nor" => {
+                let count: usize = value
+                    .parse()
+                    .map_err(|_| Error::other(format!("Invalid {} count: {}", key, value)))?;
+                let mut children = vec![];
+                for _ in 0..count {
+                    if *pos >= pairs.len() {
+                        return Err(Error::other(format!("Missing conditions of {}", key)));
+                    }
+                    children.push(Self::parse_cond(pairs, pos)?);
+                }
+                if key == "nand" {
+                    Ok(Cond::Nand(children))
+                } else {
+                    Ok(Cond::Nor(children))
+                }
+            }
+            _ => Ok(Cond::Prop(key.to_lowercase(), value.clone())),
+        }
+    }
+
+    /// Returns `true` if the filter has the `collapse_addr_hash` condition
+    pub fn collapse_addr(&self) -> bool {
+        self.conds
+            .iter()
+            .any(|c| matches!(c, Cond::Prop(k, v) if k == "collapse_addr_hash" && v == "1"))
+    }
+
+    /// Returns the filter string with its conditions in a canonical order,
+    /// so filters that only differ in the order of their conditions are equal
+    #[cfg(feature = "async")]
+    pub fn canonical(&self) -> String {
+        Self::canonical_list(&self.conds).concat()
+    }
+
+    #[cfg(feature = "async")]
+    fn canonical_list(conds: &[Cond]) -> Vec<String> {
+        let mut strs: Vec<String> = conds
+            .iter()
+            .map(|c| match c {
+                Cond::Nand(children) => {
+                    format!("\\nand\\{}{}", children.len(), Self::canonical_list(children).concat())
+                }
+                Cond::Nor(children) => {
+                    format!("\\nor\\{}{}", children.len(), Self::canonical_list(children).concat())
+                }
+                Cond::Prop(key, value) => format!("\\{}\\{}", key, value),
+            })
+            .collect();
+        strs.sort();
+        strs
+    }
+
+    /// Returns `true` if the server matches all of the conditions
+    pub fn matches(&self, addr: &SocketAddrV4, attrs: &ServerAttrs) -> bool {
+        self.conds.iter().all(|c| Self::eval(c, addr, attrs))
+    }
+
+    fn eval(cond: &Cond, addr: &SocketAddrV4, attrs: &ServerAttrs) -> bool {
+        match cond {
+            Cond::Nand(children) => {
+                children.is_empty() || !children.iter().all(|c| Self::eval(c, addr, attrs))
+            }
+            Cond::Nor(children) => !children.iter().any(|c| Self::eval(c, addr, attrs)),
+            Cond::Prop(key, value) => Self::eval_prop(key, value, addr, attrs),
+        }
+    }
+
+    fn eval_prop(key: &str, value: &str, addr: &SocketAddrV4, attrs: &ServerAttrs) -> bool {
+        let flag = value == "1";
+        match key {
+            "dedicated" => attrs.dedicated == flag,
+            "secure" => attrs.secure == flag,
+            "linux" => attrs.linux == flag,
+            "password" => attrs.password == flag,
+            "proxy" => attrs.proxy == flag,
+            "white" => attrs.whitelisted == flag,
+            // \full\1 are servers that are not full
+            "full" => (attrs.players < attrs.max_players) == flag,
+            "empty" => !flag || attrs.players > 0,
+            "noplayers" => !flag || attrs.players == 0,
+            "appid" => value.parse::<u32>() == Ok(attrs.appid),
+            "napp" => value.parse::<u32>() != Ok(attrs.appid),
+            "gamedir" => attrs.gamedir.eq_ignore_ascii_case(value),
+            "map" => attrs.map.eq_ignore_ascii_case(value),
+            "gametype" => tags(value).all(|t| has_tag(&attrs.gametype, t)),
+            "gamedata" => tags(value).all(|t| has_tag(&attrs.gamedata, t)),
+            "gamedataor" => tags(value).any(|t| has_tag(&attrs.gamedata, t)),
+            "name_match" => wildcard_match(value, &attrs.name),
+            "version_match" => wildcard_match(value, &attrs.version),
+            "gameaddr" => {
+                if value.contains(':') {
+                    wildcard_match(value, &addr.to_string())
+                } else {
+                    wildcard_match(value, &addr.ip().to_string())
+                }
+            }
+            // Handled when collecting the results
+            "collapse_addr_hash" => true,
+            // Unknown filters do not exclude any server
+            _ => true,
+        }
+    }
+}
+
+fn tags(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',').filter(|t| !t.is_empty())
+}
+
+fn has_tag(server_tags: &[String], tag: &str) -> bool {
+    server_tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+}
+
+// Case-insensitive match where `*` matches any amount of characters
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let text = text.to_lowercase();
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == text;
+    }
+
+    let mut rest = text.as_str();
+    for (i, part) in parts.iter().enumerate() {
+        if i == 0 {
+            match rest.strip_prefix(part) {
+                Some(r) => rest = r,
+                None => return false,
+            }
+        } else if i == parts.len() - 1 {
+            return rest.ends_with(part);
+        } else {
+            match rest.find(part) {
+                Some(idx) => rest = &rest[idx + part.len()..],
+                None => return false,
+            }
+        }
+    }
+    true
+}
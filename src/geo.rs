@@ -0,0 +1,285 @@
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol"))]
+use crate::fanout::RegionResults;
+use crate::region::Region;
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, Read, Result};
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+
+/// Location of a single IP address
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoInfo {
+    /// ISO 3166-1 alpha-2 country code (EX: `FR`)
+    pub country: Option<String>,
+    /// Two letter continent code (EX: `EU`)
+    pub continent: Option<String>,
+    /// The [`Region`] inferred from the location
+    pub region: Option<Region>,
+}
+
+/// A server address annotated with its location
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoServer {
+    /// The server's address (EX: `127.0.0.1:27015`)
+    pub addr: String,
+    /// The region the server was queried under
+    pub queried_region: Region,
+    /// The server's location, `None` if not found in the database
+    pub geo: Option<GeoInfo>,
+    /// `true` if the inferred region disagrees with the queried region
+    pub mismatch: bool,
+}
+
+struct GeoRange {
+    start: u128,
+    end: u128,
+    info: GeoInfo,
+}
+
+enum GeoSource {
+    MaxMind(maxminddb::Reader<Vec<u8>>),
+    Ranges(Vec<GeoRange>),
+}
+
+/// Offline GeoIP database
+///
+/// * Requires feature: `geo` (Turned **off** by default)
+/// * Region codes are what game servers claim, not where they are. This
+///   annotates query results with their location and flags servers whose
+///   location disagrees with the region they were queried under.
+/// * Loads a MaxMind-format `.mmdb` database (GeoLite2/GeoIP2 Country or
+///   City) or a CSV IP-range database.
+/// * Intended to be used with the results of
+///   [`MSQClient`](crate::MSQClient) and [`MSQClientBlock`](crate::MSQClientBlock)
+///
+/// # CSV format
+/// One IP range per line, `#` comments and blank lines are ignored.
+/// Both IPv4 and IPv6 ranges are supported, the longitude is optional
+/// and is used to tell US East and US West apart:
+/// ```text
+/// # start_ip,end_ip,country,continent,longitude
+/// 1.0.0.0,1.0.0.255,AU,OC,133.0
+/// 2.16.0.0,2.16.255.255,FR,EU
+/// ```
+///
+/// # Quick Start
+/// ```rust
+/// use msq::{GeoDatabase, Region};
+/// use std::io::Result;
+///
+/// fn main() -> Result<()> {
+///     let db = GeoDatabase::from_csv("2.16.0.0,2.16.255.255,FR,EU".as_bytes())?;
+///     let servers = vec![String::from("2.16.4.1:27015")];
+///
+///     for server in db.annotate(&servers, Region::USEast) {
+///         assert!(server.mismatch);  // Queried as US East, located in Europe
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct GeoDatabase {
+    source: GeoSource,
+}
+
+impl GeoDatabase {
+    /// Load a MaxMind-format `.mmdb` database
+    ///
+    /// # Arguments
+    /// * `path` - Path to the `.mmdb` file
+    pub fn open_mmdb<P: AsRef<Path>>(path: P) -> Result<GeoDatabase> {
+        let reader = maxminddb::Reader::open_readfile(path).map_err(Error::other)?;
+        Ok(GeoDatabase {
+            source: GeoSource::MaxMind(reader),
+        })
+    }
+
+    /// Load a CSV IP-range database from a file
+    ///
+    /// # Arguments
+    /// * `path` - Path to the CSV file
+    pub fn open_csv<P: AsRef<Path>>(path: P) -> Result<GeoDatabase> {
+        Self::from_csv(File::open(path)?)
+    }
+
+    /// Load a CSV IP-range database from a reader, see the
+    /// [CSV format](#csv-format)
+    ///
+    /// # Arguments
+    /// * `reader` - Reader of the CSV content
+    pub fn from_csv<R: Read>(reader: R) -> Result<GeoDatabase> {
+        let mut ranges = vec![];
+        for (i, line) in BufReader::new(reader).lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = || Error::other(format!("Invalid GeoIP CSV line {}: {}", i + 1, line));
+            let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+            if fields.len() < 4 {
+                return Err(invalid());
+            }
+
+            let start: IpAddr = fields[0].parse().map_err(|_| invalid())?;
+            let end: IpAddr = fields[1].parse().map_err(|_| invalid())?;
+            let longitude = match fields.get(4) {
+                Some(lon) if !lon.is_empty() => Some(lon.parse::<f64>().map_err(|_| invalid())?),
+                _ => None,
+            };
+            let country = non_empty(fields[2]);
+            let continent = non_empty(fields[3]);
+            let region = infer_region(country.as_deref(), continent.as_deref(), longitude);
+
+            ranges.push(GeoRange {
+                start: ip_key(start),
+                end: ip_key(end),
+                info: GeoInfo {
+                    country,
+                    continent,
+                    region,
+                },
+            });
+        }
+
+        ranges.sort_by_key(|r| r.start);
+        Ok(GeoDatabase {
+            source: GeoSource::Ranges(ranges),
+        })
+    }
+
+    /// Look up the location of an IP address
+    ///
+    /// # Arguments
+    /// * `ip` - The IP address to look up
+    pub fn lookup(&self, ip: IpAddr) -> Option<GeoInfo> {
+        match &self.source {
+            GeoSource::MaxMind(reader) => {
+                let city: maxminddb::geoip2::City = reader.lookup(ip).ok()?;
+                let country = city
+                    .country
+                    .and_then(|c| c.iso_code)
+                    .map(String::from);
+                let continent = city
+                    .continent
+                    .and_then(|c| c.code)
+                    .map(String::from);
+                let longitude = city.location.and_then(|l| l.longitude);
+                let region = infer_region(country.as_deref(), continent.as_deref(), longitude);
+                Some(GeoInfo {
+                    country,
+                    continent,
+                    region,
+                })
+            }
+            GeoSource::Ranges(ranges) => {
+                let key = ip_key(ip);
+                let idx = ranges.partition_point(|r| r.start <= key);
+                ranges[..idx]
+                    .iter()
+                    .rev()
+                    .find(|r| key <= r.end)
+                    .map(|r| r.info.clone())
+            }
+        }
+    }
+
+    /// Annotate the addresses returned by a query with their location
+    ///
+    /// Addresses that cannot be parsed or are not in the database are
+    /// returned without a location.
+    ///
+    /// # Arguments
+    /// * `servers` - The addresses returned by a query (EX: `127.0.0.1:27015`)
+    /// * `queried_region` - The [`Region`] the addresses were queried under
+    pub fn annotate(&self, servers: &[String], queried_region: Region) -> Vec<GeoServer> {
+        servers
+            .iter()
+            .map(|addr| self.annotate_one(addr, queried_region))
+            .collect()
+    }
+
+    /// Annotate the servers of a multi-region query with their location
+    ///
+    /// * Requires feature: `non-async` or `async` (Turned **on** by default),
+    ///   or one of `async-std` and `smol`
+    ///
+    /// # Arguments
+    /// * `results` - [`RegionResults`] returned by `query_regions`
+    #[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol"))]
+    pub fn annotate_regions(&self, results: &RegionResults) -> Vec<GeoServer> {
+        results
+            .servers()
+            .iter()
+            .map(|server| self.annotate_one(&server.addr, server.region))
+            .collect()
+    }
+
+    fn annotate_one(&self, addr: &str, queried_region: Region) -> GeoServer {
+        let geo = addr
+            .parse::<SocketAddr>()
+            .map(|a| a.ip())
+            .or_else(|_| addr.parse::<IpAddr>())
+            .ok()
+            .and_then(|ip| self.lookup(ip));
+        let mismatch = match geo.as_ref().and_then(|g| g.region) {
+            Some(region) => queried_region.is_known()
+                && queried_region != Region::All
+                && region != queried_region,
+            None => false,
+        };
+        GeoServer {
+            addr: String::from(addr),
+            queried_region,
+            geo,
+            mismatch,
+        }
+    }
+}
+
+fn non_empty(field: &str) -> Option<String> {
+    if field.is_empty() {
+        None
+    } else {
+        Some(field.to_uppercase())
+    }
+}
+
+// Maps both IPv4 and IPv6 addresses into one key space
+fn ip_key(ip: IpAddr) -> u128 {
+    match ip {
+        IpAddr::V4(ip) => u128::from(ip.to_ipv6_mapped()),
+        IpAddr::V6(ip) => u128::from(ip),
+    }
+}
+
+const MIDDLE_EAST: [&str; 14] = [
+    "AE", "BH", "IL", "IQ", "IR", "JO", "KW", "LB", "OM", "PS", "QA", "SA", "SY", "YE",
+];
+
+// North America is split into US East and US West at -100 longitude,
+// without a longitude the region is left unknown
+fn infer_region(country: Option<&str>, continent: Option<&str>, longitude: Option<f64>) -> Option<Region> {
+    if let Some(country) = country {
+        if MIDDLE_EAST.contains(&country) {
+            return Some(Region::MiddleEast);
+        }
+    }
+
+    match continent? {
+        "NA" => longitude.map(|lon| {
+            if lon < -100.0 {
+                Region::USWest
+            } else {
+                Region::USEast
+            }
+        }),
+        "SA" => Some(Region::SouthAmerica),
+        "EU" => Some(Region::Europe),
+        "AS" => Some(Region::Asia),
+        "OC" => Some(Region::Australia),
+        "AF" => Some(Region::Africa),
+        _ => None,
+    }
+}
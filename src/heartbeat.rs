@@ -0,0 +1,247 @@
+use crate::attrs::ServerAttrs;
+use crate::region::Region;
+use crate::service::{self, ServerHandle};
+
+use std::io::{Error, Result};
+use std::net::UdpSocket;
+use std::time::Duration;
+
+/// Starting sequence of the challenge reply of the master server
+pub(crate) const CHALLENGE_HEADER: [u8; 6] = [0xFF, 0xFF, 0xFF, 0xFF, 0x73, 0x0A];
+
+/// Challenge request sent by game servers
+pub(crate) const CHALLENGE_REQUEST: u8 = b'q';
+
+/// Starting sequence of the heartbeat info string
+pub(crate) const INFO_HEADER: [u8; 2] = [b'0', b'\n'];
+
+/// Sent by game servers that are shutting down
+pub(crate) const QUIT: [u8; 2] = [b'b', b'\n'];
+
+/// Engine variant of the heartbeat protocol
+///
+/// * Intended to be used with: [`Heartbeat`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeartbeatVariant {
+    /// Source engine servers, protocol `7` and the server's appid
+    Source,
+    /// GoldSrc engine servers, protocol `48` and no appid
+    GoldSrc,
+}
+
+impl HeartbeatVariant {
+    fn protocol(&self) -> u32 {
+        match self {
+            Self::Source => 7,
+            Self::GoldSrc => 48,
+        }
+    }
+}
+
+/// Heartbeat client - Registers a game server to a master server
+///
+/// * Requires feature: `server` (Turned **off** by default)
+/// * Does the heartbeat exchange: a `q` challenge request, the master
+///   server's `s` challenge reply, then the `\protocol\...\challenge\...`
+///   info string describing the game server.
+/// * The master server registers the game server by the address the
+///   heartbeat is sent from, so bind the heartbeat to the game server's
+///   port with [`bind`](#method.bind).
+///
+/// # Quick Start
+/// ```rust
+/// use msq::{Heartbeat, HeartbeatVariant, MasterServer, ServerAttrs, Region};
+/// use std::io::Result;
+/// use std::{thread, time::Duration};
+///
+/// fn main() -> Result<()> {
+///     let master = MasterServer::new();
+///     let master_handle = master.start("127.0.0.1:0")?;
+///
+///     let mut heartbeat = Heartbeat::bind("127.0.0.1:0", HeartbeatVariant::Source)?;
+///     heartbeat.connect(&master_handle.local_addr().to_string())?;
+///     heartbeat.send(&ServerAttrs::new().appid(240).map("de_dust2").players(3, 24))?;
+///
+///     // The master server does not acknowledge heartbeats
+///     thread::sleep(Duration::from_millis(100));
+///     assert_eq!(master.query(Region::All, "\\appid\\240")?.len(), 1);
+///     Ok(())
+/// }
+/// ```
+///
+pub struct Heartbeat {
+    sock: UdpSocket,
+    variant: HeartbeatVariant,
+}
+
+impl Heartbeat {
+    /// Create a new Heartbeat and binds the UDP socket to `0.0.0.0:0`
+    ///
+    /// # Arguments
+    /// * `variant` - [`HeartbeatVariant`] of the game server
+    pub fn new(variant: HeartbeatVariant) -> Result<Heartbeat> {
+        Self::bind("0.0.0.0:0", variant)
+    }
+
+    /// Create a new Heartbeat and binds the UDP socket to the given address
+    ///
+    /// # Arguments
+    /// * `addr` - The address to bind to, usually the game server's address
+    /// * `variant` - [`HeartbeatVariant`] of the game server
+    pub fn bind(addr: &str, variant: HeartbeatVariant) -> Result<Heartbeat> {
+        let sock = UdpSocket::bind(addr)?;
+        sock.set_read_timeout(Some(Duration::from_secs(5)))?;
+        Ok(Heartbeat { sock, variant })
+    }
+
+    /// Connect the heartbeat to the given master server address/hostname
+    ///
+    /// # Arguments
+    /// * `master_server_addr` - The master server's hostname/ip address
+    pub fn connect(&mut self, master_server_addr: &str) -> Result<()> {
+        self.sock.connect(master_server_addr)
+    }
+
+    /// Set how long to wait for the master server's challenge reply
+    /// (Defaults to 5 seconds)
+    ///
+    /// # Arguments
+    /// * `timeout` - Time to wait for the challenge reply
+    pub fn timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.sock.set_read_timeout(Some(timeout))
+    }
+
+    /// Send one heartbeat with the given attributes
+    ///
+    /// # Arguments
+    /// * `attrs` - The game server's [`ServerAttrs`]
+    pub fn send(&mut self, attrs: &ServerAttrs) -> Result<()> {
+        send_heartbeat(&self.sock, self.variant, attrs)
+    }
+
+    /// Tell the master server the game server is shutting down
+    pub fn quit(&mut self) -> Result<()> {
+        self.sock.send(&QUIT)?;
+        Ok(())
+    }
+
+    /// Send a heartbeat every interval on a new thread, until the returned
+    /// handle is stopped or dropped
+    ///
+    /// Failed heartbeats are retried on the next interval.
+    ///
+    /// # Arguments
+    /// * `interval` - Time between each heartbeat (EX: 5 minutes)
+    /// * `attrs` - Returns the game server's current [`ServerAttrs`]
+    pub fn start<F>(self, interval: Duration, mut attrs: F) -> Result<ServerHandle>
+    where
+        F: FnMut() -> ServerAttrs + Send + 'static,
+    {
+        let variant = self.variant;
+        service::spawn_periodic(self.sock, interval, move |sock| {
+            let _ = send_heartbeat(sock, variant, &attrs());
+        })
+    }
+}
+
+fn send_heartbeat(sock: &UdpSocket, variant: HeartbeatVariant, attrs: &ServerAttrs) -> Result<()> {
+    sock.send(&[CHALLENGE_REQUEST])?;
+
+    let mut buf: [u8; 2048] = [0x00; 2048];
+    let len = sock.recv(&mut buf)?;
+    if len < CHALLENGE_HEADER.len() + 4 || buf[..CHALLENGE_HEADER.len()] != CHALLENGE_HEADER {
+        return Err(Error::other("Mismatched starting sequence"));
+    }
+    let mut challenge: [u8; 4] = [0; 4];
+    challenge.copy_from_slice(&buf[CHALLENGE_HEADER.len()..CHALLENGE_HEADER.len() + 4]);
+    let challenge = u32::from_le_bytes(challenge);
+
+    sock.send(&encode_info(variant, challenge, attrs))?;
+    Ok(())
+}
+
+/// Encode the heartbeat info string, including its starting sequence
+pub(crate) fn encode_info(variant: HeartbeatVariant, challenge: u32, attrs: &ServerAttrs) -> Vec<u8> {
+    let server_type = if attrs.proxy {
+        "p"
+    } else if attrs.dedicated {
+        "d"
+    } else {
+        "l"
+    };
+    let mut info = format!(
+        "\\protocol\\{}\\challenge\\{}\\players\\{}\\max\\{}\\bots\\{}\\gamedir\\{}\\map\\{}\\password\\{}\\os\\{}\\lan\\0\\region\\{}\\type\\{}\\secure\\{}\\version\\{}\\product\\{}",
+        variant.protocol(),
+        challenge,
+        attrs.players,
+        attrs.max_players,
+        attrs.bots,
+        attrs.gamedir,
+        attrs.map,
+        attrs.password as i32,
+        if attrs.linux { "l" } else { "w" },
+        attrs.region.as_u8(),
+        server_type,
+        attrs.secure as i32,
+        attrs.version,
+        attrs.gamedir,
+    );
+    if variant == HeartbeatVariant::Source {
+        info += &format!("\\appid\\{}", attrs.appid);
+    }
+    if !attrs.gametype.is_empty() {
+        info += &format!("\\gametype\\{}", attrs.gametype.join(","));
+    }
+    if !attrs.gamedata.is_empty() {
+        info += &format!("\\gamedata\\{}", attrs.gamedata.join(","));
+    }
+    info += "\n";
+
+    let mut packet = INFO_HEADER.to_vec();
+    packet.extend(info.as_bytes());
+    packet
+}
+
+/// Decode a heartbeat info string (without its starting sequence) into
+/// the challenge and the game server's attributes
+pub(crate) fn decode_info(info: &[u8]) -> Result<(u32, ServerAttrs)> {
+    let info = String::from_utf8_lossy(info);
+    let info = info.trim_end_matches(['\n', '\0']);
+    let tokens: Vec<&str> = info.split('\\').skip(1).collect();
+
+    let mut challenge = None;
+    let mut attrs = ServerAttrs::new();
+    let list = |v: &str| -> Vec<String> {
+        v.split(',').filter(|t| !t.is_empty()).map(String::from).collect()
+    };
+    for pair in tokens.chunks(2) {
+        let (key, value) = (pair[0], pair.get(1).copied().unwrap_or(""));
+        let number = || value.parse::<u32>().unwrap_or(0);
+        match key {
+            "challenge" => challenge = value.parse::<u32>().ok(),
+            "players" => attrs.players = number(),
+            "max" => attrs.max_players = number(),
+            "bots" => attrs.bots = number(),
+            "gamedir" => attrs.gamedir = String::from(value),
+            "map" => attrs.map = String::from(value),
+            "password" => attrs.password = value == "1",
+            "os" => attrs.linux = value == "l",
+            "region" => attrs.region = Region::from(value.parse::<u8>().unwrap_or(0xFF)),
+            "type" => {
+                attrs.dedicated = value == "d";
+                attrs.proxy = value == "p";
+            }
+            "secure" => attrs.secure = value == "1",
+            "version" => attrs.version = String::from(value),
+            "appid" => attrs.appid = number(),
+            "gametype" => attrs.gametype = list(value),
+            "gamedata" => attrs.gamedata = list(value),
+            _ => (),
+        }
+    }
+
+    match challenge {
+        Some(challenge) => Ok((challenge, attrs)),
+        None => Err(Error::other("Missing challenge")),
+    }
+}
@@ -28,13 +28,26 @@
 //! [dependencies]
 //! msq = { version = "0.2", default-features = false, features = ["non-async"] }
 //! ```
+//!
+//! Optional features, turned **off** by default:
+//!
+//! * `async-std`: [`MSQClient`] on [async-std](https://async.rs/) with `AsyncStdUdpSocket` (`async` runs it on tokio)
+//! * `smol`: [`MSQClient`] on [smol](https://github.com/smol-rs/smol) with `SmolUdpSocket`
+//! * `serde`: [`serde`](https://serde.rs/) support for [`Region`]
+//! * `geo`: Offline GeoIP annotation of query results with `GeoDatabase`
+//! * `server`: Server side of the protocol, such as `MasterServer`, `MasterProxy`, `Heartbeat`, `A2SResponder` and `MockMasterServer`
+//! * `export`: Save query results and A2S info to JSON, NDJSON, CSV or binary files with `Snapshot`, load them back and diff them
+//! * `metrics`: `MetricsExporter`, serving server counts, players and master query stats to Prometheus
+//! * `config`: Named queries and master server profiles loaded from a TOML file with `Queries`
+//! * `cli`: The `msq` command-line tool, to query master servers and probe game servers from a shell
+//! * `tui`: `msq browse`, an interactive server browser for the terminal (includes `cli`)
 //! 
 //! # Quick Start
 //! The following example covers the primary functionalities of this library
 //! and should be quick on understanding how to use the library.
 //!
 //! ## Async version
-//! ```rust
+//! ```rust,no_run
 //! use msq::{MSQClient, Region, Filter};
 //! use std::io::Result;
 //!
@@ -73,7 +86,7 @@
 //! If you don't want to use async, then a blocking version is available.
 //! The methods functionalities and names should matches its async
 //! counterpart.
-//! ```rust
+//! ```rust,no_run
 //! use msq::{MSQClientBlock, Region, Filter};
 //! use std::io::Result;
 //!
@@ -95,22 +108,194 @@
 //! }
 //! ```
 
+mod a2s_packet;
 mod filter;
 mod region;
+
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol", feature = "server", feature = "export"))]
 mod packet_ext;
 
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol", feature = "server"))]
+mod query_packet;
+
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol", feature = "server"))]
+mod socket;
+
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol", feature = "server"))]
+mod transport;
+
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol"))]
+mod capture;
+
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol"))]
+mod fanout;
+
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol"))]
+mod shard;
+
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol"))]
+mod socks5;
+
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol"))]
+mod builder;
+
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol"))]
+mod cancel;
+
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol"))]
+mod shared;
+
+#[cfg(feature = "config")]
+mod config;
+
+#[cfg(feature = "geo")]
+mod geo;
+
+#[cfg(feature = "server")]
+mod a2s;
+
+#[cfg(feature = "server")]
+mod attrs;
+
+#[cfg(feature = "server")]
+mod filter_eval;
+
+#[cfg(feature = "server")]
+mod heartbeat;
+
+#[cfg(feature = "server")]
+mod master;
+
+#[cfg(feature = "server")]
+mod mock;
+
+#[cfg(all(feature = "server", feature = "async"))]
+mod proxy;
+
+#[cfg(feature = "server")]
+mod service;
+
+#[cfg(feature = "export")]
+mod diff;
+
+#[cfg(feature = "export")]
+mod snapshot;
+
+#[cfg(feature = "metrics")]
+mod metrics;
+
 #[cfg(feature = "async")]
+mod a2s_client_async;
+
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
 mod client_async;
 
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+mod runtime;
+
+#[cfg(feature = "async")]
+mod watcher;
+
+#[cfg(feature = "non-async")]
+mod a2s_client_blocking;
+
 #[cfg(feature = "non-async")]
 mod client_blocking;
 
+pub use crate::a2s_packet::{PlayerInfo, ServerInfo, ServerRule};
 pub use crate::filter::Filter;
 pub use crate::region::Region;
 
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol", feature = "server"))]
+pub use crate::socket::SocketOptions;
+
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol", feature = "server"))]
+pub use crate::transport::{ChannelTransport, Transport, UdpTransport};
+
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol"))]
+pub use crate::capture::{CaptureFormat, Recorder, Replay};
+
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol"))]
+pub use crate::fanout::{RegionResults, RegionServer};
+
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol"))]
+pub use crate::shard::ShardStrategy;
+
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol"))]
+pub use crate::socks5::{Socks5Proxy, Socks5Transport, Socks5UdpSocket};
+
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol"))]
+pub use crate::builder::{MSQClientBuilder, QueryEvent, RetryPolicy};
+
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol"))]
+pub use crate::cancel::{CancelToken, PartialQuery};
+
 #[cfg(feature = "async")]
+pub use crate::a2s_client_async::A2SClient;
+
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
 pub use crate::client_async::MSQClient;
 
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+pub use crate::shared::SharedMSQClient;
+
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+pub use crate::runtime::{AsyncUdpSocket, DefaultUdpSocket};
+
+#[cfg(feature = "tokio")]
+pub use crate::runtime::TokioUdpSocket;
+
+#[cfg(feature = "async-std")]
+pub use crate::runtime::AsyncStdUdpSocket;
+
+#[cfg(feature = "smol")]
+pub use crate::runtime::SmolUdpSocket;
+
+#[cfg(feature = "async")]
+pub use crate::watcher::{WatchEvent, WatchHandle, Watcher};
+
+#[cfg(feature = "non-async")]
+pub use crate::a2s_client_blocking::A2SClientBlock;
+
 #[cfg(feature = "non-async")]
 pub use crate::client_blocking::MSQClientBlock;
 
+#[cfg(feature = "non-async")]
+pub use crate::shared::SharedMSQClientBlock;
+
+#[cfg(feature = "config")]
+pub use crate::config::{MasterProfile, NamedQuery, Queries};
+
+#[cfg(feature = "export")]
+pub use crate::diff::{ChangedServer, DiffSummary, ServerChange, SnapshotDiff};
+
+#[cfg(feature = "export")]
+pub use crate::snapshot::{ServerRecord, Snapshot, SnapshotFormat};
+
+#[cfg(feature = "metrics")]
+pub use crate::metrics::{MetricsExporter, MetricsHandle};
+
+#[cfg(feature = "geo")]
+pub use crate::geo::{GeoDatabase, GeoInfo, GeoServer};
+
+#[cfg(feature = "server")]
+pub use crate::a2s::A2SResponder;
+
+#[cfg(feature = "server")]
+pub use crate::attrs::ServerAttrs;
+
+#[cfg(feature = "server")]
+pub use crate::heartbeat::{Heartbeat, HeartbeatVariant};
+
+#[cfg(feature = "server")]
+pub use crate::master::MasterServer;
+
+#[cfg(all(feature = "server", feature = "async"))]
+pub use crate::proxy::MasterProxy;
+
+#[cfg(feature = "server")]
+pub use crate::service::ServerHandle;
+
+#[cfg(feature = "server")]
+pub use crate::mock::{MockFault, MockMasterHandle, MockMasterServer, MockRequest};
+
@@ -0,0 +1,325 @@
+use crate::attrs::ServerAttrs;
+use crate::filter_eval::FilterExpr;
+use crate::heartbeat::{decode_info, CHALLENGE_HEADER, CHALLENGE_REQUEST, INFO_HEADER, QUIT};
+use crate::query_packet::{encode_reply, next_batch, QueryRequest};
+use crate::region::Region;
+use crate::service::{self, ServerHandle};
+
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::BuildHasher;
+use std::io::{Error, Result};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Challenges handed out and not used yet, beyond which the oldest is dropped
+const MAX_CHALLENGES: usize = 4096;
+
+struct Entry {
+    attrs: ServerAttrs,
+    // None for servers registered with `register`, which never expire
+    last_heartbeat: Option<Instant>,
+}
+
+/// Master server - Keeps a registry of game servers and answers queries
+///
+/// * Requires feature: `server` (Turned **off** by default)
+/// * Speaks the same protocol [`MSQClient`](crate::MSQClient) and
+///   [`MSQClientBlock`](crate::MSQClientBlock) consume: decodes 0x31
+///   requests (region byte, seed address, filter string), evaluates the
+///   filter against each registered server, and replies with paginated
+///   address batches.
+/// * Servers are only returned for queries of their region, or of
+///   [`Region::All`].
+/// * Game servers register themselves with the heartbeat exchange of
+///   [`Heartbeat`](crate::Heartbeat), and are removed when they quit or
+///   stop sending heartbeats for longer than the
+///   [`heartbeat_timeout`](#method.heartbeat_timeout). Heartbeats with a
+///   challenge the master server did not hand out, or handed out longer
+///   than the [`challenge_timeout`](#method.challenge_timeout) ago, are
+///   rejected.
+/// * Cloning a MasterServer shares its registry, so servers can be
+///   registered while it is running.
+///
+/// # Quick Start
+/// ```rust
+/// use msq::{MSQClientBlock, MasterServer, ServerAttrs, Region, Filter};
+/// use std::io::Result;
+///
+/// fn main() -> Result<()> {
+///     let master = MasterServer::new();
+///     master.register("10.0.0.1:27015", ServerAttrs::new().appid(240).map("de_dust2"))?;
+///     master.register("10.0.0.2:27015", ServerAttrs::new().appid(240).map("cs_italy"))?;
+///     let handle = master.start("127.0.0.1:0")?;
+///
+///     let mut client = MSQClientBlock::new()?;
+///     client.connect(&handle.local_addr().to_string())?;
+///     let servers = client.query(Region::All, Filter::new().appid(240).map("cs_italy"))?;
+///     assert_eq!(servers, vec!["10.0.0.2:27015"]);
+///     Ok(())
+/// }
+/// ```
+///
+#[derive(Clone)]
+pub struct MasterServer {
+    registry: Arc<Mutex<BTreeMap<SocketAddrV4, Entry>>>,
+    challenges: Arc<Mutex<HashMap<SocketAddr, (u32, Instant)>>>,
+    hasher: RandomState,
+    heartbeat_timeout: Duration,
+    challenge_timeout: Duration,
+}
+
+impl Default for MasterServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MasterServer {
+    /// Returns a new MasterServer with an empty registry
+    pub fn new() -> MasterServer {
+        MasterServer {
+            registry: Arc::new(Mutex::new(BTreeMap::new())),
+            challenges: Arc::new(Mutex::new(HashMap::new())),
+            hasher: RandomState::new(),
+            heartbeat_timeout: Duration::from_secs(15 * 60),
+            challenge_timeout: Duration::from_secs(60),
+        }
+    }
+
+    /// Set how long a game server stays registered after its last heartbeat
+    /// (Defaults to 15 minutes)
+    ///
+    /// Servers registered with [`register`](#method.register) never expire.
+    ///
+    /// # Arguments
+    /// * `timeout` - Time until a server without heartbeats is removed
+    pub fn heartbeat_timeout(mut self, timeout: Duration) -> MasterServer {
+        self.heartbeat_timeout = timeout;
+        self
+    }
+
+    /// Set how long a challenge handed out to a game server can be used for
+    /// its heartbeats (Defaults to 1 minute)
+    ///
+    /// # Arguments
+    /// * `timeout` - Time until a challenge is no longer accepted
+    pub fn challenge_timeout(mut self, timeout: Duration) -> MasterServer {
+        self.challenge_timeout = timeout;
+        self
+    }
+
+    /// Register a game server, or update the attributes of a registered one
+    ///
+    /// # Arguments
+    /// * `addr` - The server's IPv4 address (EX: `10.0.0.1:27015`)
+    /// * `attrs` - The server's [`ServerAttrs`]
+    pub fn register(&self, addr: &str, attrs: ServerAttrs) -> Result<()> {
+        let addr = parse_addr(addr)?;
+        let entry = Entry {
+            attrs,
+            last_heartbeat: None,
+        };
+        self.registry.lock().unwrap().insert(addr, entry);
+        Ok(())
+    }
+
+    /// Remove a game server from the registry
+    ///
+    /// Returns `true` if the server was registered.
+    ///
+    /// # Arguments
+    /// * `addr` - The server's IPv4 address (EX: `10.0.0.1:27015`)
+    pub fn unregister(&self, addr: &str) -> Result<bool> {
+        let addr = parse_addr(addr)?;
+        Ok(self.registry.lock().unwrap().remove(&addr).is_some())
+    }
+
+    /// Returns the attributes of a registered game server
+    ///
+    /// # Arguments
+    /// * `addr` - The server's IPv4 address (EX: `10.0.0.1:27015`)
+    pub fn get(&self, addr: &str) -> Result<Option<ServerAttrs>> {
+        let addr = parse_addr(addr)?;
+        self.expire();
+        let registry = self.registry.lock().unwrap();
+        Ok(registry.get(&addr).map(|entry| entry.attrs.clone()))
+    }
+
+    /// Returns the amount of registered game servers
+    pub fn len(&self) -> usize {
+        self.expire();
+        self.registry.lock().unwrap().len()
+    }
+
+    /// Returns `true` if no game server is registered
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the addresses of the registered servers matching a query,
+    /// in the order they are paginated in
+    ///
+    /// # Arguments
+    /// * `region` - [`Region`] enum (`Region::USEast` - `Region::Africa` / `Region::All`)
+    /// * `filter_str` - Filter in plain string (EX: `\\appid\\240\\map\\de_dust2`)
+    pub fn query(&self, region: Region, filter_str: &str) -> Result<Vec<String>> {
+        let servers = self.matching(region, filter_str)?;
+        Ok(servers.iter().map(|a| a.to_string()).collect())
+    }
+
+    /// Remove the game servers whose last heartbeat is older than the
+    /// [`heartbeat_timeout`](#method.heartbeat_timeout)
+    ///
+    /// Returns the amount of removed servers. Expired servers are also
+    /// removed before every query. Challenges older than the
+    /// [`challenge_timeout`](#method.challenge_timeout) are dropped as well.
+    pub fn expire(&self) -> usize {
+        let challenge_timeout = self.challenge_timeout;
+        self.challenges
+            .lock()
+            .unwrap()
+            .retain(|_, (_, issued)| issued.elapsed() < challenge_timeout);
+
+        let timeout = self.heartbeat_timeout;
+        let mut registry = self.registry.lock().unwrap();
+        let before = registry.len();
+        registry.retain(|_, entry| match entry.last_heartbeat {
+            Some(last) => last.elapsed() < timeout,
+            None => true,
+        });
+        before - registry.len()
+    }
+
+    /// Answer a raw packet received from the given address
+    ///
+    /// Handles 0x31 query requests, and the heartbeat exchange of game
+    /// servers: `q` challenge requests, `0\n` heartbeats and `b\n` quits.
+    /// Returns the reply to send back, if any.
+    ///
+    /// # Arguments
+    /// * `packet` - The received packet
+    /// * `from` - The address the packet was received from
+    pub fn handle_packet(&self, packet: &[u8], from: SocketAddr) -> Result<Option<Vec<u8>>> {
+        if packet.first() == Some(&CHALLENGE_REQUEST) {
+            Ok(Some(self.challenge(from)))
+        } else if packet.starts_with(&INFO_HEADER) {
+            self.heartbeat(&packet[INFO_HEADER.len()..], from)?;
+            Ok(None)
+        } else if packet.starts_with(&QUIT) {
+            self.quit(from)?;
+            Ok(None)
+        } else {
+            self.reply(packet).map(Some)
+        }
+    }
+
+    fn challenge(&self, from: SocketAddr) -> Vec<u8> {
+        let now = Instant::now();
+        let challenge = self.hasher.hash_one((from, now)) as u32;
+        let mut challenges = self.challenges.lock().unwrap();
+        if challenges.len() >= MAX_CHALLENGES && !challenges.contains_key(&from) {
+            let timeout = self.challenge_timeout;
+            challenges.retain(|_, (_, issued)| issued.elapsed() < timeout);
+            // Still full of fresh challenges, make room by dropping the oldest
+            if challenges.len() >= MAX_CHALLENGES {
+                let oldest = challenges.iter().min_by_key(|(_, (_, issued))| *issued).map(|(addr, _)| *addr);
+                if let Some(addr) = oldest {
+                    challenges.remove(&addr);
+                }
+            }
+        }
+        challenges.insert(from, (challenge, now));
+
+        let mut reply = CHALLENGE_HEADER.to_vec();
+        reply.extend(challenge.to_le_bytes());
+        reply
+    }
+
+    fn heartbeat(&self, info: &[u8], from: SocketAddr) -> Result<()> {
+        let (challenge, attrs) = decode_info(info)?;
+        let valid = match self.challenges.lock().unwrap().get(&from) {
+            Some((expected, issued)) => *expected == challenge && issued.elapsed() < self.challenge_timeout,
+            None => false,
+        };
+        if !valid {
+            return Err(Error::other(format!("Bad challenge from {}", from)));
+        }
+
+        let addr = heartbeat_addr(from)?;
+        let entry = Entry {
+            attrs,
+            last_heartbeat: Some(Instant::now()),
+        };
+        self.registry.lock().unwrap().insert(addr, entry);
+        Ok(())
+    }
+
+    fn quit(&self, from: SocketAddr) -> Result<()> {
+        let addr = heartbeat_addr(from)?;
+        self.challenges.lock().unwrap().remove(&from);
+        let mut registry = self.registry.lock().unwrap();
+        // Only servers that registered with heartbeats can quit
+        if registry.get(&addr).is_some_and(|entry| entry.last_heartbeat.is_some()) {
+            registry.remove(&addr);
+        }
+        Ok(())
+    }
+
+    fn reply(&self, packet: &[u8]) -> Result<Vec<u8>> {
+        let request = QueryRequest::decode(packet)?;
+        let mut servers = self.matching(Region::from(request.region_code), &request.filter)?;
+        // The registry is ordered by address, so pagination carries on after
+        // the seed even if the seed server is gone by now
+        if let Some(seed) = request.seed_addr()? {
+            servers.retain(|addr| *addr > seed);
+        }
+        let (batch, last) = next_batch(&servers, None);
+        encode_reply(batch, last)
+    }
+
+    /// Start answering requests on the given address on a new thread
+    ///
+    /// Invalid requests and rejected heartbeats are ignored.
+    ///
+    /// # Arguments
+    /// * `addr` - The address to bind to (EX: `0.0.0.0:27011`)
+    pub fn start(&self, addr: &str) -> Result<ServerHandle> {
+        let master = self.clone();
+        service::spawn(addr, move |sock, packet, from| {
+            if let Ok(Some(reply)) = master.handle_packet(packet, from) {
+                let _ = sock.send_to(&reply, from);
+            }
+        })
+    }
+
+    fn matching(&self, region: Region, filter_str: &str) -> Result<Vec<SocketAddrV4>> {
+        let filter = FilterExpr::parse(filter_str)?;
+        self.expire();
+        let collapse = filter.collapse_addr();
+        let mut ips: HashSet<Ipv4Addr> = HashSet::new();
+
+        let registry = self.registry.lock().unwrap();
+        let servers = registry
+            .iter()
+            .filter(|(_, entry)| region == Region::All || entry.attrs.region == region)
+            .filter(|(addr, entry)| filter.matches(addr, &entry.attrs))
+            .filter(|(addr, _)| !collapse || ips.insert(*addr.ip()))
+            .map(|(addr, _)| *addr)
+            .collect();
+        Ok(servers)
+    }
+}
+
+fn heartbeat_addr(from: SocketAddr) -> Result<SocketAddrV4> {
+    match from {
+        SocketAddr::V4(addr) => Ok(addr),
+        SocketAddr::V6(_) => Err(Error::other(format!("Unsupported heartbeat address: {}", from))),
+    }
+}
+
+fn parse_addr(addr: &str) -> Result<SocketAddrV4> {
+    addr.parse()
+        .map_err(|_| Error::other(format!("Invalid server address: {}", addr)))
+}
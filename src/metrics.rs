@@ -0,0 +1,428 @@
+use crate::a2s_client_async::A2SClient;
+use crate::a2s_packet::ServerInfo;
+use crate::client_async::MSQClient;
+use crate::filter::Filter;
+use crate::region::Region;
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{Error, ErrorKind, Result};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::{JoinHandle, JoinSet};
+
+struct ExportQuery {
+    name: String,
+    region: Region,
+    filter: String,
+}
+
+// Metrics of a named query, gauges are from the last successful poll
+#[derive(Default)]
+struct QueryMetrics {
+    region: u8,
+    servers: usize,
+    players: u64,
+    bots: u64,
+    maps: BTreeMap<String, usize>,
+    probe_ratio: Option<f64>,
+    query_seconds: f64,
+    last_poll: u64,
+    queries_ok: u64,
+    queries_failed: u64,
+    throttled: u64,
+    probes_ok: u64,
+    probes_failed: u64,
+}
+
+type SharedMetrics = Arc<Mutex<BTreeMap<String, QueryMetrics>>>;
+
+/// Prometheus exporter - Serves `/metrics` of periodic master queries and probes
+///
+/// * Requires feature: `metrics` (Turned **off** by default)
+/// * Runs every configured query on an [`interval`](#method.interval), one
+///   after another, and optionally probes the servers with A2S_INFO.
+/// * Serves the results in the Prometheus text format over HTTP at
+///   `/metrics`, labeled by query name and region:
+///   * `msq_servers`, `msq_players`, `msq_bots` and `msq_map_servers{map}`
+///     (players, bots and maps need [`probe`](#method.probe))
+///   * `msq_probes_total{result}` and `msq_probe_success_ratio`
+///   * `msq_master_queries_total{result}`, `msq_master_query_duration_seconds`
+///     and `msq_master_throttled_total`
+///   * `msq_last_poll_timestamp_seconds`
+/// * Master servers drop the requests of rate limited clients, so queries
+///   that time out are counted as throttle events.
+///
+/// # Quick Start
+/// ```rust,no_run
+/// use msq::{MetricsExporter, Region, Filter};
+/// use std::io::Result;
+/// use std::time::Duration;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let exporter = MetricsExporter::new("hl2master.steampowered.com:27011")
+///         .query("css_eu", Region::Europe, Filter::new().appid(240))
+///         .query("tf2_us", Region::USEast, Filter::new().appid(440))
+///         .interval(Duration::from_secs(300))
+///         .probe(true)
+///         .start("127.0.0.1:9640")
+///         .await?;
+///     println!("Serving http://{}/metrics", exporter.local_addr());
+///     std::future::pending::<()>().await;
+///     Ok(())
+/// }
+/// ```
+///
+pub struct MetricsExporter {
+    master: String,
+    queries: Vec<ExportQuery>,
+    interval: Duration,
+    timeout: Duration,
+    max_servers: usize,
+    probe: bool,
+    probe_timeout: Duration,
+    concurrency: usize,
+}
+
+/// Handle of a running [`MetricsExporter`]
+///
+/// The exporter stops when the handle is dropped.
+pub struct MetricsHandle {
+    local_addr: SocketAddr,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl MetricsExporter {
+    /// Returns a new MetricsExporter without queries
+    ///
+    /// # Arguments
+    /// * `master` - The master server's address (EX: `hl2master.steampowered.com:27011`)
+    pub fn new(master: &str) -> MetricsExporter {
+        MetricsExporter {
+            master: String::from(master),
+            queries: vec![],
+            interval: Duration::from_secs(60),
+            timeout: Duration::from_secs(10),
+            max_servers: 6000,
+            probe: false,
+            probe_timeout: Duration::from_secs(3),
+            concurrency: 32,
+        }
+    }
+
+    /// Add a query to export
+    ///
+    /// # Arguments
+    /// * `name` - Value of the `query` label (EX: `css_eu`)
+    /// * `region` - [`Region`] to query
+    /// * `filter` - [`Filter`] of the query
+    pub fn query(mut self, name: &str, region: Region, filter: Filter) -> MetricsExporter {
+        self.queries.push(ExportQuery {
+            name: String::from(name),
+            region,
+            filter: filter.as_string(),
+        });
+        self
+    }
+
+    /// Set the time between polls (Defaults to 60 seconds)
+    ///
+    /// # Arguments
+    /// * `interval` - Time between the start of each round of queries
+    pub fn interval(mut self, interval: Duration) -> MetricsExporter {
+        self.interval = interval;
+        self
+    }
+
+    /// Set the time until a master server query fails (Defaults to 10 seconds)
+    ///
+    /// # Arguments
+    /// * `timeout` - Time until a query fails, including all of its pages
+    pub fn timeout(mut self, timeout: Duration) -> MetricsExporter {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set maximum amount of servers in a query (Defaults to 6000)
+    ///
+    /// # Arguments
+    /// * `max_servers` - Maximum amount of servers in a query
+    pub fn max_servers(mut self, max_servers: usize) -> MetricsExporter {
+        self.max_servers = max_servers;
+        self
+    }
+
+    /// Probe every server with A2S_INFO on each poll (Defaults to `false`)
+    ///
+    /// # Arguments
+    /// * `probe` - Whether to probe the servers
+    pub fn probe(mut self, probe: bool) -> MetricsExporter {
+        self.probe = probe;
+        self
+    }
+
+    /// Set the time until a probe fails (Defaults to 3 seconds)
+    ///
+    /// # Arguments
+    /// * `timeout` - Time until a probe fails
+    pub fn probe_timeout(mut self, timeout: Duration) -> MetricsExporter {
+        self.probe_timeout = timeout;
+        self
+    }
+
+    /// Set the maximum amount of probes in flight (Defaults to 32)
+    ///
+    /// # Arguments
+    /// * `concurrency` - Maximum amount of probes in flight
+    pub fn concurrency(mut self, concurrency: usize) -> MetricsExporter {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Start polling and serving `/metrics` in tasks of the current tokio runtime
+    ///
+    /// # Arguments
+    /// * `addr` - Address of the HTTP listener (EX: `127.0.0.1:9640`)
+    pub async fn start(self, addr: &str) -> Result<MetricsHandle> {
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        let metrics: SharedMetrics = Arc::new(Mutex::new(BTreeMap::new()));
+        for query in &self.queries {
+            let mut metrics = metrics.lock().unwrap();
+            let entry = metrics.entry(query.name.clone()).or_default();
+            entry.region = query.region.as_u8();
+        }
+
+        let serve = tokio::spawn(serve(listener, metrics.clone()));
+        let poll = tokio::spawn(self.run(metrics));
+        Ok(MetricsHandle {
+            local_addr,
+            tasks: vec![serve, poll],
+        })
+    }
+
+    async fn run(self, metrics: SharedMetrics) {
+        let mut interval = tokio::time::interval(self.interval.max(Duration::from_secs(1)));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            for query in &self.queries {
+                self.poll(query, &metrics).await;
+            }
+        }
+    }
+
+    async fn poll(&self, query: &ExportQuery, metrics: &SharedMetrics) {
+        let started = Instant::now();
+        let result = tokio::time::timeout(self.timeout, async {
+            let mut client = MSQClient::new().await?;
+            client.connect(&self.master).await?;
+            client.max_servers_on_query(self.max_servers);
+            client.query_raw(query.region.as_u8(), &query.filter).await
+        })
+        .await
+        .unwrap_or_else(|_| Err(Error::new(ErrorKind::TimedOut, "Master server query timed out")));
+        let elapsed = started.elapsed();
+
+        let servers = match result {
+            Ok(servers) => servers,
+            Err(e) => {
+                let mut metrics = metrics.lock().unwrap();
+                let entry = metrics.entry(query.name.clone()).or_default();
+                entry.queries_failed += 1;
+                if e.kind() == ErrorKind::TimedOut {
+                    entry.throttled += 1;
+                }
+                return;
+            }
+        };
+        let probes = match self.probe {
+            true => Some(self.probe_all(&servers).await),
+            false => None,
+        };
+
+        let mut metrics = metrics.lock().unwrap();
+        let entry = metrics.entry(query.name.clone()).or_default();
+        entry.queries_ok += 1;
+        entry.query_seconds = elapsed.as_secs_f64();
+        entry.last_poll = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        entry.servers = servers.len();
+        if let Some(probes) = probes {
+            let ok = probes.iter().filter(|p| p.is_some()).count();
+            entry.probes_ok += ok as u64;
+            entry.probes_failed += (probes.len() - ok) as u64;
+            entry.probe_ratio = match probes.is_empty() {
+                true => None,
+                false => Some(ok as f64 / probes.len() as f64),
+            };
+            entry.players = 0;
+            entry.bots = 0;
+            entry.maps.clear();
+            for info in probes.iter().flatten() {
+                entry.players += info.players as u64;
+                entry.bots += info.bots as u64;
+                *entry.maps.entry(info.map.clone()).or_default() += 1;
+            }
+        }
+    }
+
+    async fn probe_all(&self, servers: &[String]) -> Vec<Option<ServerInfo>> {
+        let mut probes = vec![];
+        let mut tasks = JoinSet::new();
+        for addr in servers {
+            while tasks.len() >= self.concurrency.max(1) {
+                if let Some(Ok(info)) = tasks.join_next().await {
+                    probes.push(info);
+                }
+            }
+            let addr = addr.clone();
+            let timeout = self.probe_timeout;
+            tasks.spawn(async move {
+                let mut client = A2SClient::new().await.ok()?;
+                client.timeout(timeout);
+                client.info(&addr).await.ok()
+            });
+        }
+        while let Some(result) = tasks.join_next().await {
+            if let Ok(info) = result {
+                probes.push(info);
+            }
+        }
+        probes
+    }
+}
+
+impl MetricsHandle {
+    /// Returns the address of the HTTP listener
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stop polling and serving
+    pub fn stop(self) {}
+}
+
+impl Drop for MetricsHandle {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+async fn serve(listener: TcpListener, metrics: SharedMetrics) {
+    loop {
+        let Ok((stream, _)) = listener.accept().await else {
+            continue;
+        };
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            let _ = respond(stream, &metrics).await;
+        });
+    }
+}
+
+// Answers a single HTTP/1.x request, then closes the connection
+async fn respond(mut stream: TcpStream, metrics: &SharedMetrics) -> Result<()> {
+    let mut request = vec![];
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        let len = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buf))
+            .await
+            .map_err(|_| Error::new(ErrorKind::TimedOut, "HTTP request timed out"))??;
+        if len == 0 || request.len() > 8192 {
+            return Ok(());
+        }
+        request.extend_from_slice(&buf[..len]);
+    }
+
+    let line = String::from_utf8_lossy(&request);
+    let mut parts = line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", render(&metrics.lock().unwrap())),
+        (Some("GET"), _) => ("404 Not Found", String::from("Not Found\n")),
+        _ => ("405 Method Not Allowed", String::from("Method Not Allowed\n")),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+fn render(metrics: &BTreeMap<String, QueryMetrics>) -> String {
+    let mut out = String::new();
+    let labels = |name: &str, m: &QueryMetrics| {
+        format!("query=\"{}\",region=\"{}\"", escape(name), Region::from(m.region))
+    };
+    let family = |out: &mut String, metric: &str, kind: &str, help: &str, lines: Vec<String>| {
+        let _ = writeln!(out, "# HELP {} {}", metric, help);
+        let _ = writeln!(out, "# TYPE {} {}", metric, kind);
+        for line in lines {
+            let _ = writeln!(out, "{}", line);
+        }
+    };
+
+    let gauge = |metric: &str, value: &dyn Fn(&QueryMetrics) -> Option<String>| -> Vec<String> {
+        metrics
+            .iter()
+            .filter_map(|(name, m)| value(m).map(|v| format!("{}{{{}}} {}", metric, labels(name, m), v)))
+            .collect()
+    };
+    let results = |metric: &str, ok: &dyn Fn(&QueryMetrics) -> u64, failed: &dyn Fn(&QueryMetrics) -> u64| {
+        metrics
+            .iter()
+            .flat_map(|(name, m)| {
+                [("success", ok(m)), ("failure", failed(m))].map(|(result, count)| {
+                    format!("{}{{{},result=\"{}\"}} {}", metric, labels(name, m), result, count)
+                })
+            })
+            .collect::<Vec<String>>()
+    };
+
+    family(&mut out, "msq_servers", "gauge", "Servers returned by the master server query",
+        gauge("msq_servers", &|m| Some(m.servers.to_string())));
+    family(&mut out, "msq_players", "gauge", "Players on the probed servers",
+        gauge("msq_players", &|m| m.probe_ratio.map(|_| m.players.to_string())));
+    family(&mut out, "msq_bots", "gauge", "Bots on the probed servers",
+        gauge("msq_bots", &|m| m.probe_ratio.map(|_| m.bots.to_string())));
+    let maps = metrics
+        .iter()
+        .flat_map(|(name, m)| {
+            m.maps.iter().map(move |(map, count)| {
+                format!("msq_map_servers{{{},map=\"{}\"}} {}", labels(name, m), escape(map), count)
+            })
+        })
+        .collect();
+    family(&mut out, "msq_map_servers", "gauge", "Probed servers running each map", maps);
+    family(&mut out, "msq_probes_total", "counter", "A2S_INFO probes by result",
+        results("msq_probes_total", &|m| m.probes_ok, &|m| m.probes_failed));
+    family(&mut out, "msq_probe_success_ratio", "gauge", "Share of successful probes of the last poll",
+        gauge("msq_probe_success_ratio", &|m| m.probe_ratio.map(|r| r.to_string())));
+    family(&mut out, "msq_master_queries_total", "counter", "Master server queries by result",
+        results("msq_master_queries_total", &|m| m.queries_ok, &|m| m.queries_failed));
+    family(&mut out, "msq_master_query_duration_seconds", "gauge", "Duration of the last successful master server query",
+        gauge("msq_master_query_duration_seconds", &|m| (m.queries_ok > 0).then(|| m.query_seconds.to_string())));
+    family(&mut out, "msq_master_throttled_total", "counter", "Master server queries that timed out, likely rate limited",
+        gauge("msq_master_throttled_total", &|m| Some(m.throttled.to_string())));
+    family(&mut out, "msq_last_poll_timestamp_seconds", "gauge", "Unix time of the last successful poll",
+        gauge("msq_last_poll_timestamp_seconds", &|m| (m.queries_ok > 0).then(|| m.last_poll.to_string())));
+    out
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,326 @@
+use crate::attrs::ServerAttrs;
+use crate::query_packet::{encode_reply, next_batch, QueryRequest};
+use crate::region::Region;
+use crate::service::{self, ServerHandle};
+use crate::transport::{ChannelTransport, Transport};
+
+use std::io::{Error, Result};
+use std::net::{SocketAddr, SocketAddrV4};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// A fault the mock master server applies to its replies
+///
+/// * Intended to be used with: [`MockMasterServer::fault`] and
+///   [`MockMasterServer::fault_all`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockFault {
+    /// Do not send the reply
+    Drop,
+    /// Send the reply after the given delay
+    Delay(Duration),
+    /// Send the reply twice
+    Duplicate,
+    /// Send the reply with a mismatched starting sequence
+    Corrupt,
+}
+
+/// A request received by the mock master server
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockRequest {
+    /// The region of the request
+    pub region: Region,
+    /// The seed address of the request (`0.0.0.0:0` on the first request)
+    pub seed: String,
+    /// The filter string of the request
+    pub filter: String,
+}
+
+/// In-process mock master server for offline testing
+///
+/// * Requires feature: `server` (Turned **off** by default)
+/// * Binds a local UDP port and answers 0x31 requests with paginated reply
+///   batches, the same way the master server does, so
+///   [`MSQClient`](crate::MSQClient) and [`MSQClientBlock`](crate::MSQClientBlock)
+///   can be tested without network access.
+/// * Can also be served without any socket with [`channel`](#method.channel).
+/// * Servers are only returned for queries of their region, or of
+///   [`Region::All`].
+/// * Replies can be scripted to be dropped, delayed, duplicated or corrupted
+///   with [`fault`](#method.fault) and [`fault_all`](#method.fault_all).
+/// * The server runs on its own thread until the returned
+///   [`MockMasterHandle`] is dropped.
+///
+/// # Quick Start
+/// ```rust
+/// use msq::{MSQClientBlock, MockMasterServer, ServerAttrs, Region, Filter};
+/// use std::io::Result;
+///
+/// fn main() -> Result<()> {
+///     let mock = MockMasterServer::new()
+///         .servers(&["10.0.0.1:27015", "10.0.0.2:27015"])
+///         .server_with("10.0.0.3:27015", ServerAttrs::new().region(Region::Europe))
+///         .start()?;
+///
+///     let mut client = MSQClientBlock::new()?;
+///     client.connect(&mock.local_addr().to_string())?;
+///     let servers = client.query(Region::All, Filter::new())?;
+///     assert_eq!(servers.len(), 3);
+///     Ok(())
+/// }
+/// ```
+///
+pub struct MockMasterServer {
+    servers: Vec<(String, ServerAttrs)>,
+    faults: Vec<(Option<usize>, MockFault)>,
+}
+
+impl Default for MockMasterServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockMasterServer {
+    /// Returns a new MockMasterServer without any servers or faults
+    pub fn new() -> MockMasterServer {
+        MockMasterServer {
+            servers: vec![],
+            faults: vec![],
+        }
+    }
+
+    /// Add a server with default attributes (see [`ServerAttrs::new`])
+    ///
+    /// # Arguments
+    /// * `addr` - The server's IPv4 address (EX: `10.0.0.1:27015`)
+    pub fn server(self, addr: &str) -> MockMasterServer {
+        self.server_with(addr, ServerAttrs::new())
+    }
+
+    /// Add servers with default attributes (see [`ServerAttrs::new`])
+    ///
+    /// # Arguments
+    /// * `addrs` - The servers' IPv4 addresses (EX: `10.0.0.1:27015`)
+    pub fn servers(mut self, addrs: &[&str]) -> MockMasterServer {
+        for addr in addrs {
+            self = self.server(addr);
+        }
+        self
+    }
+
+    /// Add a server with the given attributes
+    ///
+    /// # Arguments
+    /// * `addr` - The server's IPv4 address (EX: `10.0.0.1:27015`)
+    /// * `attrs` - The server's [`ServerAttrs`]
+    pub fn server_with(mut self, addr: &str, attrs: ServerAttrs) -> MockMasterServer {
+        self.servers.push((String::from(addr), attrs));
+        self
+    }
+
+    /// Apply a fault to the nth reply (counted from 0, over all queries)
+    ///
+    /// # Arguments
+    /// * `reply` - Index of the reply to apply the fault to
+    /// * `fault` - The [`MockFault`] to apply
+    pub fn fault(mut self, reply: usize, fault: MockFault) -> MockMasterServer {
+        self.faults.push((Some(reply), fault));
+        self
+    }
+
+    /// Apply a fault to every reply
+    ///
+    /// # Arguments
+    /// * `fault` - The [`MockFault`] to apply
+    pub fn fault_all(mut self, fault: MockFault) -> MockMasterServer {
+        self.faults.push((None, fault));
+        self
+    }
+
+    /// Start the mock master server on a random port of `127.0.0.1`
+    pub fn start(self) -> Result<MockMasterHandle> {
+        self.bind("127.0.0.1:0")
+    }
+
+    /// Start the mock master server on the given address
+    ///
+    /// # Arguments
+    /// * `addr` - The address to bind to (EX: `127.0.0.1:27011`)
+    pub fn bind(self, addr: &str) -> Result<MockMasterHandle> {
+        let mut state = self.into_state()?;
+        let requests = state.requests.clone();
+        let handle = service::spawn(addr, move |sock, packet, from| {
+            // Ignore anything that is not a valid query request
+            if let Ok(Some(reply)) = QueryRequest::decode(packet).and_then(|request| state.reply(request)) {
+                if let Ok(sock) = sock.try_clone() {
+                    reply.send(move |packet| {
+                        let _ = sock.send_to(packet, from);
+                    });
+                }
+            }
+        })?;
+
+        Ok(MockMasterHandle { handle, requests })
+    }
+
+    /// Serve the mock master server over an in-memory [`ChannelTransport`]
+    /// instead of a UDP port
+    ///
+    /// Returns the client end of the channel, to be given to
+    /// [`MSQClient::with_transport`](crate::MSQClient::with_transport) or
+    /// [`MSQClientBlock::with_transport`](crate::MSQClientBlock::with_transport).
+    /// The server runs on its own thread until the client end is dropped.
+    ///
+    /// # Example
+    /// ```rust
+    /// use msq::{MSQClientBlock, MockMasterServer, Region, Filter};
+    /// use std::io::Result;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let transport = MockMasterServer::new()
+    ///         .servers(&["10.0.0.1:27015", "10.0.0.2:27015"])
+    ///         .channel()?;
+    ///
+    ///     let mut client = MSQClientBlock::with_transport(transport);
+    ///     let servers = client.query(Region::All, Filter::new().appid(240))?;
+    ///     assert_eq!(servers, vec!["10.0.0.1:27015", "10.0.0.2:27015"]);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn channel(self) -> Result<ChannelTransport> {
+        let mut state = self.into_state()?;
+        let (client, mut master) = ChannelTransport::pair();
+        thread::spawn(move || {
+            let mut buf = [0u8; 2048];
+            while let Ok(len) = master.recv(&mut buf, None) {
+                // Ignore anything that is not a valid query request
+                if let Ok(Some(reply)) = QueryRequest::decode(&buf[..len]).and_then(|request| state.reply(request)) {
+                    let tx = master.tx.clone();
+                    reply.send(move |packet| {
+                        let _ = tx.send(packet.to_vec());
+                    });
+                }
+            }
+        });
+        Ok(client)
+    }
+
+    fn into_state(self) -> Result<MockState> {
+        let mut servers = vec![];
+        for (addr, attrs) in self.servers {
+            let addr: SocketAddrV4 = addr
+                .parse()
+                .map_err(|_| Error::other(format!("Invalid server address: {}", addr)))?;
+            servers.push((addr, attrs));
+        }
+
+        Ok(MockState {
+            servers,
+            faults: self.faults,
+            replies: 0,
+            requests: Arc::new(Mutex::new(vec![])),
+        })
+    }
+}
+
+/// A running [`MockMasterServer`], stopped when dropped
+pub struct MockMasterHandle {
+    handle: ServerHandle,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+}
+
+impl MockMasterHandle {
+    /// Returns the address the mock master server is bound to
+    pub fn local_addr(&self) -> SocketAddr {
+        self.handle.local_addr()
+    }
+
+    /// Returns the requests received so far
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Stop the mock master server
+    pub fn stop(self) {
+        self.handle.stop();
+    }
+}
+
+struct MockState {
+    servers: Vec<(SocketAddrV4, ServerAttrs)>,
+    faults: Vec<(Option<usize>, MockFault)>,
+    replies: usize,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+}
+
+impl MockState {
+    fn reply(&mut self, request: QueryRequest) -> Result<Option<MockReply>> {
+        let region = Region::from(request.region_code);
+        self.requests.lock().unwrap().push(MockRequest {
+            region,
+            seed: request.seed.clone(),
+            filter: request.filter.clone(),
+        });
+
+        let matching: Vec<SocketAddrV4> = self
+            .servers
+            .iter()
+            .filter(|(_, attrs)| region == Region::All || attrs.region == region)
+            .map(|(addr, _)| *addr)
+            .collect();
+        let (batch, last) = next_batch(&matching, request.seed_addr()?);
+        let mut packet = encode_reply(batch, last)?;
+
+        let reply = self.replies;
+        self.replies += 1;
+        let faults: Vec<MockFault> = self
+            .faults
+            .iter()
+            .filter(|(n, _)| n.is_none() || *n == Some(reply))
+            .map(|(_, fault)| fault.clone())
+            .collect();
+
+        let mut copies = 1;
+        let mut delay = None;
+        for fault in faults {
+            match fault {
+                MockFault::Drop => return Ok(None),
+                MockFault::Delay(d) => delay = Some(d),
+                MockFault::Duplicate => copies = 2,
+                MockFault::Corrupt => packet[4] = 0x00,
+            }
+        }
+        Ok(Some(MockReply { packet, copies, delay }))
+    }
+}
+
+// A reply batch, after applying the faults
+struct MockReply {
+    packet: Vec<u8>,
+    copies: usize,
+    delay: Option<Duration>,
+}
+
+impl MockReply {
+    // Send the reply, on another thread if it is delayed
+    fn send<F: Fn(&[u8]) + Send + 'static>(self, send: F) {
+        let MockReply { packet, copies, delay } = self;
+        match delay {
+            Some(delay) => {
+                thread::spawn(move || {
+                    thread::sleep(delay);
+                    for _ in 0..copies {
+                        send(&packet);
+                    }
+                });
+            }
+            None => {
+                for _ in 0..copies {
+                    send(&packet);
+                }
+            }
+        }
+    }
+}
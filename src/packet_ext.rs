@@ -2,11 +2,14 @@ use byteorder::{ReadBytesExt, WriteBytesExt};
 use std::io::{Cursor, Result};
 
 pub trait ReadPacketExt: ReadBytesExt {
+    #[cfg(any(feature = "async", feature = "non-async", feature = "server", feature = "export"))]
     fn read_cstring(&mut self) -> Result<String>;
-    fn read_u8_veccheck(&mut self, src: &Vec<u8>) -> Result<bool>;
+    #[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol", feature = "export"))]
+    fn read_u8_veccheck(&mut self, src: &[u8]) -> Result<bool>;
 }
 
 impl ReadPacketExt for Cursor<Vec<u8>> {
+    #[cfg(any(feature = "async", feature = "non-async", feature = "server", feature = "export"))]
     fn read_cstring(&mut self) -> Result<String> {
         let end = self.get_ref().len() as u64;
         let mut svec = Vec::with_capacity(256);
@@ -22,7 +25,8 @@ impl ReadPacketExt for Cursor<Vec<u8>> {
         Ok(String::from_utf8_lossy(&svec[..]).into_owned())
     }
 
-    fn read_u8_veccheck(&mut self, cmp: &Vec<u8>) -> Result<bool> {
+    #[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol", feature = "export"))]
+    fn read_u8_veccheck(&mut self, cmp: &[u8]) -> Result<bool> {
         for cch in cmp {
             let sch = self.read_u8()?;
             if *cch != sch {
@@ -0,0 +1,259 @@
+use crate::client_async::MSQClient;
+use crate::filter_eval::FilterExpr;
+use crate::query_packet::{encode_reply, next_batch, QueryRequest};
+use crate::service::{self, ServerHandle};
+
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Result};
+use std::net::{SocketAddr, SocketAddrV4, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::runtime::{Builder, Runtime};
+
+// Region code and canonical filter string of a query
+type CacheKey = (u8, String);
+
+// Address of a client and seed address of its request
+type Waiter = (SocketAddr, Option<SocketAddrV4>);
+
+struct CacheEntry {
+    // In address order, so that pages continue after their seed address
+    servers: Vec<SocketAddrV4>,
+    fetched: Instant,
+}
+
+/// Caching master server proxy
+///
+/// * Requires features: `server` (Turned **off** by default) and `async`
+///   (Turned **on** by default)
+/// * A local UDP service that accepts the same 0x31 requests as the master
+///   server, so any MSQ client can query it instead.
+/// * Answers from a cache keyed by region and canonical filter string, so
+///   filters that only differ in the order of their conditions share a
+///   cache entry. Entries are refreshed from the upstream master server
+///   with [`MSQClient`] once they are older than the [`ttl`](#method.ttl).
+/// * Concurrent requests for the same query are coalesced into a single
+///   upstream query.
+/// * Cached lists are kept in address order, and follow-up requests of a
+///   paginated query continue after their seed address, even from a list
+///   refreshed since the first reply. If the upstream master server fails,
+///   an expired entry is served until the next refresh succeeds.
+/// * Up to [`cache_size`](#method.cache_size) queries are cached, the least
+///   recently refreshed one makes room for a new one.
+///
+/// # Quick Start
+/// ```rust,no_run
+/// use msq::{MSQClient, MasterProxy, Region, Filter};
+/// use std::io::Result;
+/// use std::time::Duration;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let proxy = MasterProxy::new("hl2master.steampowered.com:27011")
+///         .ttl(Duration::from_secs(300))
+///         .start("127.0.0.1:27011")?;
+///
+///     let mut client = MSQClient::new().await?;
+///     client.connect(&proxy.local_addr().to_string()).await?;
+///     client.max_servers_on_query(256);
+///     let servers = client.query(Region::Europe, Filter::new().appid(240)).await?;
+///     Ok(())
+/// }
+/// ```
+///
+pub struct MasterProxy {
+    upstream: String,
+    ttl: Duration,
+    max_servers: usize,
+    upstream_timeout: Duration,
+    cache_size: usize,
+}
+
+impl MasterProxy {
+    /// Returns a new MasterProxy for the given upstream master server
+    ///
+    /// # Arguments
+    /// * `upstream` - The upstream master server's hostname/ip address
+    pub fn new(upstream: &str) -> MasterProxy {
+        MasterProxy {
+            upstream: String::from(upstream),
+            ttl: Duration::from_secs(60),
+            max_servers: 6000,
+            upstream_timeout: Duration::from_secs(10),
+            cache_size: 1024,
+        }
+    }
+
+    /// Set how long a cached query is answered without refreshing it
+    /// (Defaults to 60 seconds)
+    ///
+    /// # Arguments
+    /// * `ttl` - Time until a cached query is refreshed
+    pub fn ttl(mut self, ttl: Duration) -> MasterProxy {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Set the maximum amount of servers of an upstream query (Defaults to 6000)
+    ///
+    /// # Arguments
+    /// * `max_servers` - Maximum amount of servers in a query
+    pub fn max_servers(mut self, max_servers: usize) -> MasterProxy {
+        self.max_servers = max_servers;
+        self
+    }
+
+    /// Set how long to wait for an upstream query (Defaults to 10 seconds)
+    ///
+    /// # Arguments
+    /// * `timeout` - Time until an upstream query fails
+    pub fn upstream_timeout(mut self, timeout: Duration) -> MasterProxy {
+        self.upstream_timeout = timeout;
+        self
+    }
+
+    /// Set the maximum amount of cached queries (Defaults to 1024)
+    ///
+    /// # Arguments
+    /// * `cache_size` - Maximum amount of queries in the cache
+    pub fn cache_size(mut self, cache_size: usize) -> MasterProxy {
+        self.cache_size = cache_size;
+        self
+    }
+
+    /// Start answering requests on the given address on a new thread
+    ///
+    /// Upstream queries run on their own runtime, started by the thread on
+    /// the first request, so this does not need to be called from within
+    /// one. Invalid requests are ignored.
+    ///
+    /// # Arguments
+    /// * `addr` - The address to bind to (EX: `127.0.0.1:27011`)
+    pub fn start(self, addr: &str) -> Result<ServerHandle> {
+        let mut state = ProxyState {
+            proxy: Arc::new(self),
+            runtime: None,
+            sock: None,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+            inflight: Arc::new(Mutex::new(HashMap::new())),
+        };
+        service::spawn(addr, move |sock, packet, from| {
+            let _ = state.handle(sock, packet, from);
+        })
+    }
+}
+
+struct ProxyState {
+    proxy: Arc<MasterProxy>,
+    // Built on the service thread, which also drops it
+    runtime: Option<Runtime>,
+    sock: Option<Arc<UdpSocket>>,
+    cache: Arc<Mutex<HashMap<CacheKey, CacheEntry>>>,
+    // Clients waiting for a query being refreshed
+    inflight: Arc<Mutex<HashMap<CacheKey, Vec<Waiter>>>>,
+}
+
+impl ProxyState {
+    fn handle(&mut self, sock: &UdpSocket, packet: &[u8], from: SocketAddr) -> Result<()> {
+        let request = QueryRequest::decode(packet)?;
+        let canonical = match FilterExpr::parse(&request.filter) {
+            Ok(filter) => filter.canonical(),
+            Err(_) => request.filter.clone(),
+        };
+        let key = (request.region_code, canonical);
+
+        let seed = request.seed_addr()?;
+
+        {
+            let cache = self.cache.lock().unwrap();
+            if let Some(entry) = cache.get(&key) {
+                // Keep paginating an expired list rather than refreshing it
+                // between two pages
+                if seed.is_some() || entry.fetched.elapsed() < self.proxy.ttl {
+                    sock.send_to(&page(&entry.servers, seed)?, from)?;
+                    return Ok(());
+                }
+            }
+        }
+
+        if self.runtime.is_none() {
+            let runtime = Builder::new_multi_thread()
+                .worker_threads(2)
+                .enable_all()
+                .build()?;
+            self.runtime = Some(runtime);
+        }
+        if self.sock.is_none() {
+            self.sock = Some(Arc::new(sock.try_clone()?));
+        }
+
+        let mut inflight = self.inflight.lock().unwrap();
+        let waiters = inflight.entry(key.clone()).or_default();
+        waiters.push((from, seed));
+        if waiters.len() > 1 {
+            // Coalesced into the upstream query already running
+            return Ok(());
+        }
+        drop(inflight);
+
+        let sock = self.sock.clone().unwrap();
+        let proxy = self.proxy.clone();
+        let cache = self.cache.clone();
+        let inflight = self.inflight.clone();
+        let runtime = self.runtime.as_ref().unwrap();
+        runtime.spawn(async move {
+            let result = refresh(&proxy, key.0, &request.filter).await;
+
+            let mut cache = cache.lock().unwrap();
+            if let Ok(mut servers) = result {
+                servers.sort();
+                servers.dedup();
+                if !cache.contains_key(&key) && cache.len() >= proxy.cache_size {
+                    let oldest = cache.iter().min_by_key(|(_, entry)| entry.fetched).map(|(key, _)| key.clone());
+                    if let Some(oldest) = oldest {
+                        cache.remove(&oldest);
+                    }
+                }
+                let fetched = Instant::now();
+                cache.insert(key.clone(), CacheEntry { servers, fetched });
+            }
+            let waiters = inflight.lock().unwrap().remove(&key).unwrap_or_default();
+            // Without any cached list, the clients time out on their own
+            if let Some(entry) = cache.get(&key) {
+                for (waiter, seed) in waiters {
+                    if let Ok(reply) = page(&entry.servers, seed) {
+                        let _ = sock.send_to(&reply, waiter);
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+// The reply batch after the seed address of a request
+fn page(servers: &[SocketAddrV4], seed: Option<SocketAddrV4>) -> Result<Vec<u8>> {
+    let start = seed.map_or(0, |seed| servers.partition_point(|addr| *addr <= seed));
+    let (batch, last) = next_batch(&servers[start..], None);
+    encode_reply(batch, last)
+}
+
+async fn refresh(proxy: &MasterProxy, region_code: u8, filter: &str) -> Result<Vec<SocketAddrV4>> {
+    let query = async {
+        let mut client = MSQClient::new().await?;
+        client.connect(&proxy.upstream).await?;
+        client.max_servers_on_query(proxy.max_servers);
+        client.query_raw(region_code, filter).await
+    };
+    let servers = tokio::time::timeout(proxy.upstream_timeout, query)
+        .await
+        .map_err(|_| Error::new(ErrorKind::TimedOut, "Upstream query timed out"))??;
+
+    servers
+        .iter()
+        .map(|s| {
+            s.parse()
+                .map_err(|_| Error::other(format!("Invalid server address: {}", s)))
+        })
+        .collect()
+}
@@ -0,0 +1,111 @@
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol"))]
+use crate::packet_ext::WritePacketExt;
+#[cfg(feature = "server")]
+use crate::packet_ext::ReadPacketExt;
+
+use byteorder::WriteBytesExt;
+use std::io::{Cursor, Result};
+#[cfg(feature = "server")]
+use byteorder::{BigEndian, ReadBytesExt};
+#[cfg(feature = "server")]
+use std::io::Error;
+#[cfg(feature = "server")]
+use std::net::{Ipv4Addr, SocketAddrV4};
+
+/// Starting sequence of every reply batch of the master server
+pub(crate) const REPLY_HEADER: [u8; 6] = [0xFF, 0xFF, 0xFF, 0xFF, 0x66, 0x0A];
+
+/// Maximum amount of addresses in a single reply batch
+#[cfg(feature = "server")]
+pub(crate) const REPLY_BATCH_SIZE: usize = 231;
+
+/// Encode a 0x31 query request
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol"))]
+pub(crate) fn encode_request(region_code: u8, seed: &str, filter: &str) -> Result<Vec<u8>> {
+    let mut cursor: Cursor<Vec<u8>> = Cursor::new(vec![]);
+    cursor.write_u8(0x31)?;
+    cursor.write_u8(region_code)?;
+    cursor.write_cstring(seed)?;
+    cursor.write_cstring(filter)?;
+    Ok(cursor.into_inner())
+}
+
+/// A decoded 0x31 query request
+#[cfg(feature = "server")]
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct QueryRequest {
+    pub region_code: u8,
+    pub seed: String,
+    pub filter: String,
+}
+
+#[cfg(feature = "server")]
+impl QueryRequest {
+    pub fn decode(buf: &[u8]) -> Result<QueryRequest> {
+        let mut cursor = Cursor::new(buf.to_vec());
+        if cursor.read_u8()? != 0x31 {
+            return Err(Error::other("Not a query request"));
+        }
+        let region_code = cursor.read_u8()?;
+        let seed = cursor.read_cstring()?;
+        let filter = cursor.read_cstring()?;
+        Ok(QueryRequest {
+            region_code,
+            seed,
+            filter,
+        })
+    }
+
+    /// Returns the seed address, `None` on the first request of a query
+    pub fn seed_addr(&self) -> Result<Option<SocketAddrV4>> {
+        let seed: SocketAddrV4 = self
+            .seed
+            .parse()
+            .map_err(|_| Error::other(format!("Invalid seed address: {}", self.seed)))?;
+        if seed == SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0) {
+            Ok(None)
+        } else {
+            Ok(Some(seed))
+        }
+    }
+}
+
+/// Encode a reply batch, terminated with `0.0.0.0:0` if `last` is set
+#[cfg(feature = "server")]
+pub(crate) fn encode_reply(addrs: &[SocketAddrV4], last: bool) -> Result<Vec<u8>> {
+    let mut cursor: Cursor<Vec<u8>> = Cursor::new(vec![]);
+    for byte in REPLY_HEADER {
+        cursor.write_u8(byte)?;
+    }
+    let end = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
+    let terminator = if last { Some(&end) } else { None };
+    for addr in addrs.iter().chain(terminator) {
+        for octet in addr.ip().octets() {
+            cursor.write_u8(octet)?;
+        }
+        cursor.write_u16::<BigEndian>(addr.port())?;
+    }
+    Ok(cursor.into_inner())
+}
+
+/// Split the addresses after the seed into the next reply batch
+///
+/// Returns the batch and whether it is the last one.
+#[cfg(feature = "server")]
+pub(crate) fn next_batch(addrs: &[SocketAddrV4], seed: Option<SocketAddrV4>) -> (&[SocketAddrV4], bool) {
+    let start = match seed {
+        Some(seed) => addrs
+            .iter()
+            .position(|a| *a == seed)
+            .map(|i| i + 1)
+            .unwrap_or(addrs.len()),
+        None => 0,
+    };
+    let rest = &addrs[start..];
+    // The terminator takes up a slot in the batch
+    if rest.len() < REPLY_BATCH_SIZE {
+        (rest, true)
+    } else {
+        (&rest[..REPLY_BATCH_SIZE], false)
+    }
+}
@@ -1,9 +1,17 @@
-use std::io::{Result, Error, ErrorKind};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io::{Result, Error};
+use std::str::FromStr;
 
 /// Region enum to restrict the servers region the query searches for
 ///
 /// * Intended to be used with: [`MSQClient`](crate::MSQClient) and
-/// [`MSQClientBlock`](crate::MSQClientBlock)
+///   [`MSQClientBlock`](crate::MSQClientBlock)
+/// * Region codes outside of the reference below are kept as
+///   `Region::Other`, so region bytes seen in captured traffic convert
+///   back to the same byte.
+/// * Regions compare by their byte code, so `Region::Other(0x03)` is equal
+///   to `Region::Europe`.
 ///
 /// # Reference
 /// | `Region` Enum          | Region            | Byte |
@@ -17,8 +25,24 @@ use std::io::{Result, Error, ErrorKind};
 /// | `Region::MiddleEast`   | Middle East       | 0x06 |
 /// | `Region::Africa`       | Africa            | 0x07 |
 /// | `Region::All`          | Rest of the world | 0xFF |
+/// | `Region::Other(code)`  | Unknown           | code |
 ///
-#[derive(Debug, PartialEq)]
+/// # Parsing
+/// Regions can be parsed from their names case-insensitively, ignoring
+/// spaces, `-` and `_` (EX: `"Europe"`, `"us-east"`, `"South America"`),
+/// from common aliases (EX: `"eu"`, `"sa"`, `"world"`) or from their byte
+/// code (EX: `"3"`, `"0xff"`).
+///
+/// ```rust
+/// use msq::Region;
+///
+/// assert_eq!("eu".parse::<Region>().unwrap(), Region::Europe);
+/// assert_eq!("US-East".parse::<Region>().unwrap(), Region::USEast);
+/// assert_eq!("0x2a".parse::<Region>().unwrap(), Region::Other(0x2a));
+/// assert_eq!(Region::SouthAmerica.to_string(), "South America");
+/// ```
+///
+#[derive(Debug, Clone, Copy)]
 pub enum Region {
     USEast,
     USWest,
@@ -29,9 +53,35 @@ pub enum Region {
     MiddleEast,
     Africa,
     All, // Rest of the world
+    Other(u8),
 }
 
 impl Region {
+    /// All of the known regions, including [`Region::All`]
+    ///
+    /// # Example
+    /// ```rust
+    /// use msq::Region;
+    ///
+    /// let regional: Vec<Region> = Region::ALL_REGIONS
+    ///     .iter()
+    ///     .copied()
+    ///     .filter(|r| *r != Region::All)
+    ///     .collect();
+    /// assert_eq!(regional.len(), 8);
+    /// ```
+    pub const ALL_REGIONS: [Region; 9] = [
+        Region::USEast,
+        Region::USWest,
+        Region::SouthAmerica,
+        Region::Europe,
+        Region::Asia,
+        Region::Australia,
+        Region::MiddleEast,
+        Region::Africa,
+        Region::All,
+    ];
+
     /// Return raw u8 byte code of its specified region
     ///
     /// # Example
@@ -55,9 +105,21 @@ impl Region {
             Self::MiddleEast => 0x06,
             Self::Africa => 0x07,
             Self::All => 0xFF,
+            Self::Other(code) => *code,
         }
     }
 
+    /// Return the region of the given u8 byte code
+    ///
+    /// Unknown codes return `Region::Other`, so this never fails.
+    ///
+    /// # Example
+    /// ```rust
+    /// use msq::Region;
+    ///
+    /// assert_eq!(Region::from_u8(0x03).unwrap(), Region::Europe);
+    /// assert_eq!(Region::from_u8(0x2a).unwrap(), Region::Other(0x2a));
+    /// ```
     pub fn from_u8(code: u8) -> Result<Self> {
         match code {
             0x00 => Ok(Self::USEast),
@@ -69,7 +131,142 @@ impl Region {
             0x06 => Ok(Self::MiddleEast),
             0x07 => Ok(Self::Africa),
             0xFF => Ok(Self::All),
-            _ => Err(Error::new(ErrorKind::Other, "Invalid code")),
+            _ => Ok(Self::Other(code)),
+        }
+    }
+
+    /// Returns an iterator over all of the known regions, see [`Region::ALL_REGIONS`]
+    pub fn iter() -> impl Iterator<Item = Region> {
+        Self::ALL_REGIONS.iter().copied()
+    }
+
+    /// Returns `true` if the region is one of the known regions
+    pub fn is_known(&self) -> bool {
+        !matches!(Region::from(self.as_u8()), Self::Other(_))
+    }
+}
+
+impl From<u8> for Region {
+    fn from(code: u8) -> Self {
+        // from_u8 never fails
+        Self::from_u8(code).unwrap_or(Self::Other(code))
+    }
+}
+
+impl From<Region> for u8 {
+    fn from(region: Region) -> Self {
+        region.as_u8()
+    }
+}
+
+impl PartialEq for Region {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_u8() == other.as_u8()
+    }
+}
+
+impl Eq for Region {}
+
+impl Hash for Region {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_u8().hash(state);
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match Region::from(self.as_u8()) {
+            Self::USEast => write!(f, "US East coast"),
+            Self::USWest => write!(f, "US West coast"),
+            Self::SouthAmerica => write!(f, "South America"),
+            Self::Europe => write!(f, "Europe"),
+            Self::Asia => write!(f, "Asia"),
+            Self::Australia => write!(f, "Australia"),
+            Self::MiddleEast => write!(f, "Middle East"),
+            Self::Africa => write!(f, "Africa"),
+            Self::All => write!(f, "Rest of the world"),
+            Self::Other(code) => write!(f, "Unknown ({:#04x})", code),
+        }
+    }
+}
+
+impl FromStr for Region {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let name: String = s
+            .chars()
+            .filter(|c| !matches!(c, ' ' | '-' | '_'))
+            .flat_map(|c| c.to_lowercase())
+            .collect();
+
+        match name.as_str() {
+            "useast" | "useastcoast" | "use" | "east" => Ok(Self::USEast),
+            "uswest" | "uswestcoast" | "usw" | "west" => Ok(Self::USWest),
+            "southamerica" | "sa" | "samerica" => Ok(Self::SouthAmerica),
+            "europe" | "eu" => Ok(Self::Europe),
+            "asia" | "as" => Ok(Self::Asia),
+            "australia" | "au" | "oceania" => Ok(Self::Australia),
+            "middleeast" | "me" => Ok(Self::MiddleEast),
+            "africa" | "af" => Ok(Self::Africa),
+            "all" | "world" | "restoftheworld" | "row" => Ok(Self::All),
+            _ => {
+                let code = match name.strip_prefix("0x") {
+                    Some(hex) => u8::from_str_radix(hex, 16),
+                    None => name.parse::<u8>(),
+                };
+                match code {
+                    Ok(code) => Ok(Region::from(code)),
+                    Err(_) => Err(Error::other(format!("Invalid region: {}", s))),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Region {
+    /// Serializes as the region's byte code
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_u8(self.as_u8())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Region {
+    /// Deserializes from the region's byte code, or any string accepted by
+    /// its [`FromStr`] implementation
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct RegionVisitor;
+
+        impl serde::de::Visitor<'_> for RegionVisitor {
+            type Value = Region;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a region byte code or name")
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, v: u64) -> std::result::Result<Region, E> {
+                u8::try_from(v)
+                    .map(Region::from)
+                    .map_err(|_| E::custom(format!("Invalid region code: {}", v)))
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, v: i64) -> std::result::Result<Region, E> {
+                u8::try_from(v)
+                    .map(Region::from)
+                    .map_err(|_| E::custom(format!("Invalid region code: {}", v)))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> std::result::Result<Region, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(RegionVisitor)
+        } else {
+            deserializer.deserialize_u8(RegionVisitor)
         }
     }
 }
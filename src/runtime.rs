@@ -0,0 +1,257 @@
+use crate::cancel::CancelToken;
+use crate::socket::peer_addr;
+
+use std::future::Future;
+use std::io::{Error, ErrorKind, Result};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::Poll;
+use std::time::{Duration, Instant};
+
+/// An async UDP socket of an async runtime, used by [`MSQClient`](crate::MSQClient)
+///
+/// * Provided implementations, each behind the feature of its runtime:
+///   [`TokioUdpSocket`] (`tokio`, included in `async`), [`AsyncStdUdpSocket`]
+///   (`async-std`) and [`SmolUdpSocket`] (`smol`)
+/// * Implement it to run the client on any other executor.
+/// * The socket is connected to the master server, so it only sends to and
+///   receives from a single peer.
+pub trait AsyncUdpSocket: Sized + Send + Sync + 'static {
+    /// Bind a new socket to the given address
+    fn bind(addr: &str) -> impl Future<Output = Result<Self>> + Send;
+
+    /// Wrap a bound [`std`] UDP socket. Called from within the runtime.
+    fn from_std(sock: std::net::UdpSocket) -> Result<Self>;
+
+    /// Connect the socket to the given address/hostname, mapping IPv4
+    /// addresses to IPv6 on IPv6 sockets
+    fn connect(&self, addr: &str) -> impl Future<Output = Result<()>> + Send;
+
+    /// Send a datagram to the connected address
+    fn send(&self, buf: &[u8]) -> impl Future<Output = Result<usize>> + Send;
+
+    /// Receive a datagram from the connected address
+    fn recv(&self, buf: &mut [u8]) -> impl Future<Output = Result<usize>> + Send;
+
+    /// Returns the address the socket is bound to
+    fn local_addr(&self) -> Result<SocketAddr>;
+
+    /// Returns the address the socket is connected to
+    fn peer_addr(&self) -> Result<SocketAddr>;
+
+    /// Wait for the given duration with the runtime's timer
+    fn sleep(duration: Duration) -> impl Future<Output = ()> + Send;
+}
+
+/// [`AsyncUdpSocket`] of [`tokio`]
+///
+/// * Requires feature: `tokio` (Turned **on** by default, as part of `async`)
+#[cfg(feature = "tokio")]
+pub struct TokioUdpSocket(tokio::net::UdpSocket);
+
+#[cfg(feature = "tokio")]
+impl AsyncUdpSocket for TokioUdpSocket {
+    async fn bind(addr: &str) -> Result<Self> {
+        Ok(TokioUdpSocket(tokio::net::UdpSocket::bind(addr).await?))
+    }
+
+    fn from_std(sock: std::net::UdpSocket) -> Result<Self> {
+        sock.set_nonblocking(true)?;
+        Ok(TokioUdpSocket(tokio::net::UdpSocket::from_std(sock)?))
+    }
+
+    async fn connect(&self, addr: &str) -> Result<()> {
+        let peer = peer_addr(self.0.local_addr()?, tokio::net::lookup_host(addr).await?)?;
+        self.0.connect(peer).await
+    }
+
+    async fn send(&self, buf: &[u8]) -> Result<usize> {
+        self.0.send(buf).await
+    }
+
+    async fn recv(&self, buf: &mut [u8]) -> Result<usize> {
+        self.0.recv(buf).await
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        self.0.local_addr()
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        self.0.peer_addr()
+    }
+
+    async fn sleep(duration: Duration) {
+        tokio::time::sleep(duration).await
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl From<tokio::net::UdpSocket> for TokioUdpSocket {
+    fn from(sock: tokio::net::UdpSocket) -> Self {
+        TokioUdpSocket(sock)
+    }
+}
+
+/// [`AsyncUdpSocket`] of [`async-std`](async_std)
+///
+/// * Requires feature: `async-std` (Turned **off** by default)
+#[cfg(feature = "async-std")]
+pub struct AsyncStdUdpSocket(async_std::net::UdpSocket);
+
+#[cfg(feature = "async-std")]
+impl AsyncUdpSocket for AsyncStdUdpSocket {
+    async fn bind(addr: &str) -> Result<Self> {
+        Ok(AsyncStdUdpSocket(async_std::net::UdpSocket::bind(addr).await?))
+    }
+
+    fn from_std(sock: std::net::UdpSocket) -> Result<Self> {
+        Ok(AsyncStdUdpSocket(async_std::net::UdpSocket::from(sock)))
+    }
+
+    async fn connect(&self, addr: &str) -> Result<()> {
+        let addrs = async_std::net::ToSocketAddrs::to_socket_addrs(addr).await?;
+        self.0.connect(peer_addr(self.0.local_addr()?, addrs)?).await
+    }
+
+    async fn send(&self, buf: &[u8]) -> Result<usize> {
+        self.0.send(buf).await
+    }
+
+    async fn recv(&self, buf: &mut [u8]) -> Result<usize> {
+        self.0.recv(buf).await
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        self.0.local_addr()
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        self.0.peer_addr()
+    }
+
+    async fn sleep(duration: Duration) {
+        async_std::task::sleep(duration).await
+    }
+}
+
+#[cfg(feature = "async-std")]
+impl From<async_std::net::UdpSocket> for AsyncStdUdpSocket {
+    fn from(sock: async_std::net::UdpSocket) -> Self {
+        AsyncStdUdpSocket(sock)
+    }
+}
+
+/// [`AsyncUdpSocket`] of [`smol`]
+///
+/// * Requires feature: `smol` (Turned **off** by default)
+#[cfg(feature = "smol")]
+pub struct SmolUdpSocket(smol::net::UdpSocket);
+
+#[cfg(feature = "smol")]
+impl AsyncUdpSocket for SmolUdpSocket {
+    async fn bind(addr: &str) -> Result<Self> {
+        Ok(SmolUdpSocket(smol::net::UdpSocket::bind(addr).await?))
+    }
+
+    fn from_std(sock: std::net::UdpSocket) -> Result<Self> {
+        Ok(SmolUdpSocket(smol::net::UdpSocket::try_from(sock)?))
+    }
+
+    async fn connect(&self, addr: &str) -> Result<()> {
+        let peer = peer_addr(self.0.local_addr()?, smol::net::resolve(addr).await?)?;
+        self.0.connect(peer).await
+    }
+
+    async fn send(&self, buf: &[u8]) -> Result<usize> {
+        self.0.send(buf).await
+    }
+
+    async fn recv(&self, buf: &mut [u8]) -> Result<usize> {
+        self.0.recv(buf).await
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        self.0.local_addr()
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        self.0.peer_addr()
+    }
+
+    async fn sleep(duration: Duration) {
+        smol::Timer::after(duration).await;
+    }
+}
+
+#[cfg(feature = "smol")]
+impl From<smol::net::UdpSocket> for SmolUdpSocket {
+    fn from(sock: smol::net::UdpSocket) -> Self {
+        SmolUdpSocket(sock)
+    }
+}
+
+/// The socket of [`MSQClient::new`](crate::MSQClient::new): the first
+/// enabled of `tokio`, `async-std` and `smol`
+#[cfg(feature = "tokio")]
+pub type DefaultUdpSocket = TokioUdpSocket;
+
+/// The socket of [`MSQClient::new`](crate::MSQClient::new): the first
+/// enabled of `tokio`, `async-std` and `smol`
+#[cfg(all(not(feature = "tokio"), feature = "async-std"))]
+pub type DefaultUdpSocket = AsyncStdUdpSocket;
+
+/// The socket of [`MSQClient::new`](crate::MSQClient::new): the first
+/// enabled of `tokio`, `async-std` and `smol`
+#[cfg(all(not(feature = "tokio"), not(feature = "async-std"), feature = "smol"))]
+pub type DefaultUdpSocket = SmolUdpSocket;
+
+// Run the futures concurrently on the current task, without spawning
+pub(crate) async fn join_all<F: Future>(futures: Vec<F>) -> Vec<F::Output> {
+    let mut futures: Vec<Pin<Box<F>>> = futures.into_iter().map(Box::pin).collect();
+    let mut outputs: Vec<Option<F::Output>> = futures.iter().map(|_| None).collect();
+    std::future::poll_fn(|cx| {
+        let mut pending = false;
+        for (future, output) in futures.iter_mut().zip(outputs.iter_mut()) {
+            if output.is_none() {
+                match future.as_mut().poll(cx) {
+                    Poll::Ready(value) => *output = Some(value),
+                    Poll::Pending => pending = true,
+                }
+            }
+        }
+        match pending {
+            true => Poll::Pending,
+            false => Poll::Ready(()),
+        }
+    })
+    .await;
+    outputs.into_iter().flatten().collect()
+}
+
+// Wait for the future until the deadline or a cancellation, sleeping on the
+// socket's runtime
+pub(crate) async fn until<S: AsyncUdpSocket, F: Future<Output = Result<T>>, T>(
+    deadline: Option<Instant>,
+    cancel: Option<&CancelToken>,
+    future: F,
+) -> Result<T> {
+    if deadline.is_none() && cancel.is_none() {
+        return future.await;
+    }
+    let mut future = Box::pin(future);
+    let mut sleep = deadline.map(|deadline| Box::pin(S::sleep(deadline.saturating_duration_since(Instant::now()))));
+    std::future::poll_fn(|cx| {
+        if let Poll::Ready(output) = future.as_mut().poll(cx) {
+            return Poll::Ready(output);
+        }
+        if let Some(Poll::Ready(())) = cancel.map(|cancel| cancel.poll_cancelled(cx)) {
+            return Poll::Ready(Err(Error::new(ErrorKind::Interrupted, "Query was cancelled")));
+        }
+        match sleep.as_mut().map(|sleep| sleep.as_mut().poll(cx)) {
+            Some(Poll::Ready(())) => Poll::Ready(Err(Error::new(ErrorKind::TimedOut, "Deadline has passed"))),
+            _ => Poll::Pending,
+        }
+    })
+    .await
+}
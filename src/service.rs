@@ -0,0 +1,103 @@
+use std::io::{ErrorKind, Result};
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// A UDP service running on its own thread, stopped when dropped
+///
+/// * Returned by the servers of the `server` feature, such as
+///   [`MasterServer::start`](crate::MasterServer::start)
+pub struct ServerHandle {
+    local_addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ServerHandle {
+    /// Returns the address the service is bound to
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stop the service and wait for its thread to finish
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for ServerHandle {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Bind a UDP socket and call the handler with every received datagram
+/// on a new thread, until the returned handle is stopped or dropped
+pub(crate) fn spawn<F>(addr: &str, mut handler: F) -> Result<ServerHandle>
+where
+    F: FnMut(&UdpSocket, &[u8], SocketAddr) + Send + 'static,
+{
+    let sock = UdpSocket::bind(addr)?;
+    sock.set_read_timeout(Some(Duration::from_millis(20)))?;
+    let local_addr = sock.local_addr()?;
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread_stop = stop.clone();
+
+    let thread = thread::spawn(move || {
+        let mut buf: [u8; 2048] = [0x00; 2048];
+        while !thread_stop.load(Ordering::SeqCst) {
+            match sock.recv_from(&mut buf) {
+                Ok((len, from)) => handler(&sock, &buf[..len], from),
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => continue,
+                // Replies to closed ports may show up as errors on some platforms
+                Err(e) if e.kind() == ErrorKind::ConnectionReset => continue,
+                Err(_) => break,
+            }
+        }
+    });
+
+    Ok(ServerHandle {
+        local_addr,
+        stop,
+        thread: Some(thread),
+    })
+}
+
+/// Call the task with the socket every interval on a new thread, until the
+/// returned handle is stopped or dropped
+pub(crate) fn spawn_periodic<F>(sock: UdpSocket, interval: Duration, mut task: F) -> Result<ServerHandle>
+where
+    F: FnMut(&UdpSocket) + Send + 'static,
+{
+    let local_addr = sock.local_addr()?;
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread_stop = stop.clone();
+
+    let thread = thread::spawn(move || {
+        let tick = Duration::from_millis(20);
+        while !thread_stop.load(Ordering::SeqCst) {
+            task(&sock);
+            // Sleep in small steps so stopping does not wait for the interval
+            let mut slept = Duration::from_millis(0);
+            while slept < interval && !thread_stop.load(Ordering::SeqCst) {
+                thread::sleep(tick.min(interval - slept));
+                slept += tick;
+            }
+        }
+    });
+
+    Ok(ServerHandle {
+        local_addr,
+        stop,
+        thread: Some(thread),
+    })
+}
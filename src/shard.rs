@@ -0,0 +1,369 @@
+//! Query sharding - Split one logical query into many smaller queries
+//!
+//! The master server truncates very large result sets, so a broad query
+//! such as every server of a popular game never returns everything. A
+//! [`ShardStrategy`] describes how a query gets split into sub-queries
+//! whenever a sub-query returns as many servers as the shard limit.
+//!
+//! # Quick Start
+//!
+//! ```
+//! use msq::ShardStrategy;
+//! let strategy = ShardStrategy::new()
+//!     .regions()
+//!     .dedicated()
+//!     .secure()
+//!     .gameaddr();
+//! ```
+//!
+use crate::filter::Filter;
+
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+
+#[derive(Clone)]
+enum ShardKey {
+    Regions,
+    Maps(Vec<String>),
+    Dedicated,
+    Secure,
+    Linux,
+    Password,
+    Empty,
+    GameAddr,
+}
+
+/// Sharding strategy - Describes how a query gets split when truncated
+///
+/// * Intended to be used with: [`MSQClient::query_sharded`](crate::MSQClient::query_sharded)
+///   and [`MSQClientBlock::query_sharded`](crate::MSQClientBlock::query_sharded)
+/// * Each sub-query may return up to [`shard_limit`](#method.shard_limit)
+///   servers. A sub-query that reaches the limit is considered truncated and
+///   gets split by the next partition in the order they were added.
+/// * Every partition splits a sub-query into sub-queries that together cover
+///   the same servers, except for [`regions`](#method.regions) (see its notes).
+/// * Results of all sub-queries are merged and deduplicated.
+///
+/// # Quick Start
+/// ```rust
+/// use msq::ShardStrategy;
+/// use std::time::Duration;
+///
+/// let strategy = ShardStrategy::new()     // Create a ShardStrategy builder
+///         .regions()                      // First split per region
+///         .maps(&["cp_badlands", "pl_upward"])  // Then per map
+///         .dedicated()                    // Then dedicated/non-dedicated
+///         .gameaddr()                     // Then per IP address prefix
+///         .shard_limit(6000)              // Servers before a sub-query counts as truncated
+///         .pacing(Duration::from_millis(500));  // Wait between each sub-query
+/// ```
+///
+#[derive(Clone)]
+pub struct ShardStrategy {
+    keys: Vec<ShardKey>,
+    shard_limit: usize,
+    pacing: Duration,
+}
+
+impl Default for ShardStrategy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShardStrategy {
+    /// Returns a new ShardStrategy struct without any partitions, a shard
+    /// limit of `6000` servers and no pacing
+    pub fn new() -> ShardStrategy {
+        ShardStrategy {
+            keys: vec![],
+            shard_limit: 6000,
+            pacing: Duration::from_millis(0),
+        }
+    }
+
+    fn key(mut self, key: ShardKey) -> ShardStrategy {
+        self.keys.push(key);
+        self
+    }
+
+    /// Split a [`Region::All`](crate::Region::All) query into one query per region
+    ///
+    /// **NOTE**: Servers that report the "rest of the world" region are only
+    /// returned by `Region::All`, and will not be found by the regional
+    /// sub-queries. Queries that are already restricted to a region are not
+    /// split by this partition.
+    pub fn regions(self) -> ShardStrategy {
+        self.key(ShardKey::Regions)
+    }
+
+    /// Split into one query per given map, plus one query for servers
+    /// running none of the given maps
+    ///
+    /// # Arguments
+    /// * `maps` - The map names (ex: `cp_badlands`)
+    pub fn maps(self, maps: &[&str]) -> ShardStrategy {
+        self.key(ShardKey::Maps(maps.iter().map(|m| String::from(*m)).collect()))
+    }
+
+    /// Split into dedicated and non-dedicated servers
+    pub fn dedicated(self) -> ShardStrategy {
+        self.key(ShardKey::Dedicated)
+    }
+
+    /// Split into secure and non-secure servers
+    pub fn secure(self) -> ShardStrategy {
+        self.key(ShardKey::Secure)
+    }
+
+    /// Split into servers running and not running on Linux
+    pub fn linux(self) -> ShardStrategy {
+        self.key(ShardKey::Linux)
+    }
+
+    /// Split into password protected and non password protected servers
+    pub fn password(self) -> ShardStrategy {
+        self.key(ShardKey::Password)
+    }
+
+    /// Split into empty and non-empty servers
+    pub fn empty(self) -> ShardStrategy {
+        self.key(ShardKey::Empty)
+    }
+
+    /// Split into one query per IP address prefix (ex: `gameaddr` of `81.*`)
+    ///
+    /// A truncated sub-query gets split again by the next octet, up to
+    /// 3 octets (ex: `81.4.19.*`). Each split runs 256 sub-queries, so this
+    /// is best added as the last partition.
+    pub fn gameaddr(self) -> ShardStrategy {
+        self.key(ShardKey::GameAddr)
+    }
+
+    /// Set the amount of servers a sub-query may return before it counts as
+    /// truncated and gets split further
+    ///
+    /// Set it at or below the amount of servers the master server returns
+    /// before it truncates a query.
+    ///
+    /// # Arguments
+    /// * `shard_limit` - Maximum amount of servers in a sub-query
+    pub fn shard_limit(mut self, shard_limit: usize) -> ShardStrategy {
+        self.shard_limit = shard_limit;
+        self
+    }
+
+    /// Set the time to wait between each sub-query, to avoid getting
+    /// throttled by the master server
+    ///
+    /// # Arguments
+    /// * `pacing` - The time to wait between each sub-query
+    pub fn pacing(mut self, pacing: Duration) -> ShardStrategy {
+        self.pacing = pacing;
+        self
+    }
+
+    pub(crate) fn get_shard_limit(&self) -> usize {
+        self.shard_limit
+    }
+
+    pub(crate) fn get_pacing(&self) -> Duration {
+        self.pacing
+    }
+}
+
+/// A single sub-query of a sharded query
+pub(crate) struct Shard {
+    pub region_code: u8,
+    filter: Filter,
+    prefix: Vec<u8>,
+    depth: usize,
+}
+
+impl Shard {
+    /// Returns the filter string of this sub-query
+    pub fn filter_string(&self) -> String {
+        self.full_filter().as_string()
+    }
+
+    fn full_filter(&self) -> Filter {
+        if self.prefix.is_empty() {
+            self.filter.clone()
+        } else {
+            let octets: Vec<String> = self.prefix.iter().map(|o| o.to_string()).collect();
+            self.filter
+                .clone()
+                .gameaddr(&format!("{}.*", octets.join(".")))
+        }
+    }
+
+    fn child(&self, region_code: u8, filter: Filter, prefix: Vec<u8>, depth: usize) -> Shard {
+        Shard {
+            region_code,
+            filter,
+            prefix,
+            depth,
+        }
+    }
+}
+
+/// Work queue of a sharded query, shared by both clients
+///
+/// The clients pop shards with [`next`](#method.next), run them and hand the
+/// results back with [`complete`](#method.complete), which splits truncated
+/// shards and merges the servers.
+pub(crate) struct ShardQueue<'a> {
+    strategy: &'a ShardStrategy,
+    pending: VecDeque<Shard>,
+    seen: HashSet<String>,
+    servers: Vec<String>,
+    completed: usize,
+    started: bool,
+}
+
+impl<'a> ShardQueue<'a> {
+    pub fn new(strategy: &'a ShardStrategy, region_code: u8, filter: Filter) -> ShardQueue<'a> {
+        ShardQueue {
+            strategy,
+            pending: VecDeque::from([Shard {
+                region_code,
+                filter,
+                prefix: vec![],
+                depth: 0,
+            }]),
+            seen: HashSet::new(),
+            servers: vec![],
+            completed: 0,
+            started: false,
+        }
+    }
+
+    /// Returns the next shard to query and whether a previous shard has
+    /// already been queried (for pacing)
+    pub fn next(&mut self) -> Option<(Shard, bool)> {
+        // Pop from the front to query shards in the order they were split
+        let shard = self.pending.pop_front()?;
+        let started = self.started;
+        self.started = true;
+        Some((shard, started))
+    }
+
+    /// Merge the servers of a queried shard, splitting it when truncated
+    pub fn complete(&mut self, shard: Shard, servers: Vec<String>) {
+        let truncated = servers.len() >= self.strategy.shard_limit;
+        for server in servers {
+            if self.seen.insert(server.clone()) {
+                self.servers.push(server);
+            }
+        }
+        self.completed += 1;
+
+        if truncated {
+            let children = self.split(&shard, shard.depth);
+            self.pending.extend(children);
+        }
+    }
+
+    /// Returns the amount of shards queried so far
+    pub fn completed(&self) -> usize {
+        self.completed
+    }
+
+    /// Returns the merged and deduplicated servers
+    pub fn into_servers(self) -> Vec<String> {
+        self.servers
+    }
+
+    // Split a shard with the first applicable partition starting from depth
+    fn split(&self, shard: &Shard, depth: usize) -> Vec<Shard> {
+        let key = match self.strategy.keys.get(depth) {
+            Some(key) => key,
+            None => return vec![],
+        };
+
+        let next = depth + 1;
+        let boolean_split = |with: Filter| {
+            vec![
+                shard.child(
+                    shard.region_code,
+                    Filter::and(shard.filter.clone(), with.clone()),
+                    shard.prefix.clone(),
+                    next,
+                ),
+                shard.child(
+                    shard.region_code,
+                    Filter::and(shard.filter.clone(), Filter::not_all(with)),
+                    shard.prefix.clone(),
+                    next,
+                ),
+            ]
+        };
+
+        match key {
+            ShardKey::Regions => {
+                if shard.region_code != 0xFF {
+                    return self.split(shard, next);
+                }
+                (0x00..=0x07)
+                    .map(|code| shard.child(code, shard.filter.clone(), shard.prefix.clone(), next))
+                    .collect()
+            }
+            ShardKey::Maps(maps) => {
+                if maps.is_empty() {
+                    return self.split(shard, next);
+                }
+                let mut children: Vec<Shard> = maps
+                    .iter()
+                    .map(|map| {
+                        shard.child(
+                            shard.region_code,
+                            shard.filter.clone().map(map),
+                            shard.prefix.clone(),
+                            next,
+                        )
+                    })
+                    .collect();
+                let rest = Filter::none_of(maps.iter().map(|map| Filter::new().map(map)));
+                children.push(shard.child(
+                    shard.region_code,
+                    Filter::and(shard.filter.clone(), rest),
+                    shard.prefix.clone(),
+                    next,
+                ));
+                children
+            }
+            ShardKey::Dedicated => boolean_split(Filter::new().dedicated(true)),
+            ShardKey::Secure => boolean_split(Filter::new().secure(true)),
+            ShardKey::Linux => boolean_split(Filter::new().linux(true)),
+            ShardKey::Password => boolean_split(Filter::new().password(true)),
+            ShardKey::Empty => vec![
+                shard.child(
+                    shard.region_code,
+                    shard.filter.clone().empty(true),
+                    shard.prefix.clone(),
+                    next,
+                ),
+                shard.child(
+                    shard.region_code,
+                    shard.filter.clone().empty(false),
+                    shard.prefix.clone(),
+                    next,
+                ),
+            ],
+            ShardKey::GameAddr => {
+                if shard.prefix.len() >= 3 {
+                    return self.split(shard, next);
+                }
+                // Stay on this partition until the prefix has 3 octets
+                let child_depth = if shard.prefix.len() + 1 < 3 { depth } else { next };
+                (0..=255)
+                    .map(|octet| {
+                        let mut prefix = shard.prefix.clone();
+                        prefix.push(octet);
+                        shard.child(shard.region_code, shard.filter.clone(), prefix, child_depth)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use crate::builder::ClientConfig;
+use crate::filter::Filter;
+use crate::region::Region;
+use crate::socket::SocketOptions;
+
+use std::io::Result;
+use std::sync::{Arc, Mutex};
+
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+use crate::client_async::MSQClient;
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+use crate::runtime::{AsyncUdpSocket, DefaultUdpSocket};
+#[cfg(feature = "non-async")]
+use crate::client_blocking::MSQClientBlock;
+
+// Idle clients a shared client keeps, unless set with MSQClientBuilder::pool_size
+const POOL_SIZE: usize = 8;
+
+/// How to open another client like the one a shared client was made from
+#[derive(Clone)]
+pub(crate) struct Template {
+    pub(crate) options: SocketOptions,
+    pub(crate) master: String,
+    pub(crate) config: ClientConfig,
+    pub(crate) max_servers: usize,
+}
+
+// Clients waiting for their next query
+struct Pool<C> {
+    template: Template,
+    idle: Mutex<Vec<C>>,
+    size: usize,
+}
+
+impl<C> Pool<C> {
+    fn new(client: C, template: Template) -> Pool<C> {
+        let size = template.config.pool_size.unwrap_or(POOL_SIZE);
+        Pool {
+            template,
+            idle: Mutex::new(vec![client]),
+            size,
+        }
+    }
+
+    fn take(&self) -> Option<C> {
+        self.idle.lock().unwrap().pop()
+    }
+
+    // Keep the client for a next query, unless its query failed and may have
+    // left replies behind, or enough clients are idle already
+    fn give<T>(&self, client: C, result: &Result<T>) {
+        let mut idle = self.idle.lock().unwrap();
+        if result.is_ok() && idle.len() < self.size {
+            idle.push(client);
+        }
+    }
+
+    fn idle(&self) -> usize {
+        self.idle.lock().unwrap().len()
+    }
+}
+
+/// A cloneable [`MSQClient`] running any amount of queries at once (async)
+///
+/// * Requires feature: `async` (Turned **on** by default), or one of
+///   `async-std` and `smol`
+/// * Made with [`MSQClient::into_shared`]. Clones share the same sockets.
+/// * Each query runs on a socket of its own, so replies never mix. Sockets
+///   are kept for the next queries, up to the
+///   [`pool_size`](crate::MSQClientBuilder::pool_size). More are bound with
+///   the client's [`SocketOptions`] on a random port and connected to the
+///   same master server.
+/// * The rate limit of the client applies to all queries together.
+/// * The non-async/blocking version of this: [`SharedMSQClientBlock`]
+///
+/// # Quick Start
+/// ```rust,no_run
+/// use msq::{MSQClient, Region, Filter};
+/// use std::io::Result;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let mut client = MSQClient::new().await?;
+///     client.connect("hl2master.steampowered.com:27011").await?;
+///     let client = client.into_shared()?;
+///
+///     let mut tasks = vec![];
+///     for appid in [240, 440, 730] {
+///         let client = client.clone();
+///         tasks.push(tokio::spawn(async move {
+///             client.query(Region::Europe, Filter::new().appid(appid)).await
+///         }));
+///     }
+///     for task in tasks {
+///         let servers = task.await.unwrap()?;
+///     }
+///     Ok(())
+/// }
+/// ```
+///
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+pub struct SharedMSQClient<S: AsyncUdpSocket = DefaultUdpSocket> {
+    pool: Arc<Pool<MSQClient<S>>>,
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+impl<S: AsyncUdpSocket> Clone for SharedMSQClient<S> {
+    fn clone(&self) -> Self {
+        SharedMSQClient {
+            pool: self.pool.clone(),
+        }
+    }
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+impl<S: AsyncUdpSocket> SharedMSQClient<S> {
+    pub(crate) fn new(client: MSQClient<S>, template: Template) -> SharedMSQClient<S> {
+        SharedMSQClient {
+            pool: Arc::new(Pool::new(client, template)),
+        }
+    }
+
+    /// Query with raw bytes
+    ///
+    /// # Arguments
+    /// * `region_code` - Region code in u8 (`0x00 - 0x07 / 0xFF`)
+    /// * `filter_str` - Filter in plain string (EX: `\\appid\\240\\map\\de_dust2`)
+    pub async fn query_raw(&self, region_code: u8, filter_str: &str) -> Result<Vec<String>> {
+        let mut client = match self.pool.take() {
+            Some(client) => client,
+            None => MSQClient::from_template(&self.pool.template).await?,
+        };
+        let servers = client.query_raw(region_code, filter_str).await;
+        self.pool.give(client, &servers);
+        servers
+    }
+
+    /// Query with specified Region and Filter
+    ///
+    /// Returns a Vec list of IP addresses in strings
+    ///
+    /// # Arguments
+    /// * `region` - [`Region`] enum (`Region::USEast` - `Region::Africa` / `Region::All`)
+    /// * `filter` - [`Filter`] builder (EX: `Filter::new().appid(240).map("de_dust2")`)
+    pub async fn query(&self, region: Region, filter: Filter) -> Result<Vec<String>> {
+        self.query_raw(region.as_u8(), &filter.as_string()).await
+    }
+
+    /// Returns the amount of sockets waiting for a query
+    pub fn idle(&self) -> usize {
+        self.pool.idle()
+    }
+}
+
+/// A cloneable [`MSQClientBlock`] running any amount of queries at once (non-async)
+///
+/// * Requires feature: `non-async` (Turned **on** by default)
+/// * Made with [`MSQClientBlock::into_shared`]. Clones share the same
+///   sockets and can be sent to other threads.
+/// * Each query runs on a socket of its own, so replies never mix. Sockets
+///   are kept for the next queries, up to the
+///   [`pool_size`](crate::MSQClientBuilder::pool_size). More are bound with
+///   the client's [`SocketOptions`] on a random port and connected to the
+///   same master server.
+/// * The rate limit of the client applies to all queries together.
+/// * The async version of this: [`SharedMSQClient`]
+///
+/// # Quick Start
+/// ```rust,no_run
+/// use msq::{MSQClientBlock, Region, Filter};
+/// use std::io::Result;
+/// use std::thread;
+///
+/// fn main() -> Result<()> {
+///     let mut client = MSQClientBlock::new()?;
+///     client.connect("hl2master.steampowered.com:27011")?;
+///     let client = client.into_shared()?;
+///
+///     let threads: Vec<_> = [240, 440, 730]
+///         .into_iter()
+///         .map(|appid| {
+///             let client = client.clone();
+///             thread::spawn(move || client.query(Region::Europe, Filter::new().appid(appid)))
+///         })
+///         .collect();
+///     for thread in threads {
+///         let servers = thread.join().unwrap()?;
+///     }
+///     Ok(())
+/// }
+/// ```
+///
+#[cfg(feature = "non-async")]
+#[derive(Clone)]
+pub struct SharedMSQClientBlock {
+    pool: Arc<Pool<MSQClientBlock>>,
+}
+
+#[cfg(feature = "non-async")]
+impl SharedMSQClientBlock {
+    pub(crate) fn new(client: MSQClientBlock, template: Template) -> SharedMSQClientBlock {
+        SharedMSQClientBlock {
+            pool: Arc::new(Pool::new(client, template)),
+        }
+    }
+
+    /// Query with raw bytes
+    ///
+    /// # Arguments
+    /// * `region_code` - Region code in u8 (`0x00 - 0x07 / 0xFF`)
+    /// * `filter_str` - Filter in plain string (EX: `\\appid\\240\\map\\de_dust2`)
+    pub fn query_raw(&self, region_code: u8, filter_str: &str) -> Result<Vec<String>> {
+        let mut client = match self.pool.take() {
+            Some(client) => client,
+            None => MSQClientBlock::from_template(&self.pool.template)?,
+        };
+        let servers = client.query_raw(region_code, filter_str);
+        self.pool.give(client, &servers);
+        servers
+    }
+
+    /// Query with specified Region and Filter
+    ///
+    /// Returns a Vec list of IP addresses in strings
+    ///
+    /// # Arguments
+    /// * `region` - [`Region`] enum (`Region::USEast` - `Region::Africa` / `Region::All`)
+    /// * `filter` - [`Filter`] builder (EX: `Filter::new().appid(240).map("de_dust2")`)
+    pub fn query(&self, region: Region, filter: Filter) -> Result<Vec<String>> {
+        self.query_raw(region.as_u8(), &filter.as_string())
+    }
+
+    /// Returns the amount of sockets waiting for a query
+    pub fn idle(&self) -> usize {
+        self.pool.idle()
+    }
+}
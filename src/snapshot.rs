@@ -0,0 +1,682 @@
+use crate::a2s_packet::ServerInfo;
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol"))]
+use crate::fanout::RegionResults;
+use crate::packet_ext::{ReadPacketExt, WritePacketExt};
+use crate::region::Region;
+
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
+use serde_json::{json, Map, Value};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Cursor, Error, ErrorKind, Read, Result, Write};
+use std::net::SocketAddrV4;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const BINARY_MAGIC: [u8; 4] = *b"MSQS";
+const BINARY_VERSION: u8 = 1;
+
+const CSV_HEADER: [&str; 21] = [
+    "address", "region", "filter", "timestamp", "protocol", "name", "map", "folder", "game",
+    "appid", "players", "max_players", "bots", "dedicated", "proxy", "linux", "password",
+    "secure", "version", "port", "keywords",
+];
+// The ping column comes last, it is empty for servers without A2S info
+const CSV_PING: &str = "ping_ms";
+
+/// File format of a [`Snapshot`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotFormat {
+    /// A JSON array of records
+    Json,
+    /// One JSON record per line
+    Ndjson,
+    /// CSV with a header, A2S columns are empty for servers without A2S info
+    Csv,
+    /// Compact binary format, filters are only stored once
+    Binary,
+}
+
+/// A game server in a [`Snapshot`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerRecord {
+    /// The server's address (EX: `10.0.0.1:27015`)
+    pub addr: String,
+    /// The region the server was queried in
+    pub region: Region,
+    /// The filter string of the query that returned the server
+    pub filter: String,
+    /// Unix time in milliseconds the server was queried at
+    pub timestamp: u64,
+    /// A2S_INFO of the server, if it was probed
+    pub info: Option<ServerInfo>,
+}
+
+/// Snapshot - Query results that can be saved to and loaded from files
+///
+/// * Requires feature: `export` (Turned **off** by default)
+/// * Each [`ServerRecord`] keeps the region and filter it was queried with,
+///   its timestamp, and its A2S info if it was probed.
+/// * Written and read in every [`SnapshotFormat`], so saved snapshots
+///   can be reloaded as typed results.
+///
+/// # Quick Start
+/// ```rust,no_run
+/// use msq::{MSQClientBlock, A2SClientBlock, Snapshot, SnapshotFormat, Region, Filter};
+/// use std::io::Result;
+///
+/// fn main() -> Result<()> {
+///     let filter = Filter::new().appid(240);
+///     let filter_str = filter.as_string();
+///     let mut client = MSQClientBlock::new()?;
+///     client.connect("hl2master.steampowered.com:27011")?;
+///     let servers = client.query(Region::Europe, filter)?;
+///
+///     let mut snapshot = Snapshot::from_query(&servers, Region::Europe, &filter_str);
+///     let mut a2s = A2SClientBlock::new()?;
+///     for server in &servers {
+///         if let Ok(info) = a2s.info(server) {
+///             snapshot.set_info(server, info);
+///         }
+///     }
+///     snapshot.save("css_eu.ndjson", SnapshotFormat::Ndjson)?;
+///
+///     let reloaded = Snapshot::load("css_eu.ndjson", SnapshotFormat::Ndjson)?;
+///     assert_eq!(reloaded, snapshot);
+///     Ok(())
+/// }
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Snapshot {
+    records: Vec<ServerRecord>,
+}
+
+impl Snapshot {
+    /// Returns a new empty Snapshot
+    pub fn new() -> Snapshot {
+        Snapshot { records: vec![] }
+    }
+
+    /// Returns a Snapshot of the results of a query, timestamped now
+    ///
+    /// # Arguments
+    /// * `servers` - The addresses returned by the query
+    /// * `region` - The [`Region`] of the query
+    /// * `filter_str` - The filter string of the query (EX: `\\appid\\240`)
+    pub fn from_query(servers: &[String], region: Region, filter_str: &str) -> Snapshot {
+        let timestamp = now();
+        let records = servers
+            .iter()
+            .map(|addr| ServerRecord {
+                addr: addr.clone(),
+                region,
+                filter: String::from(filter_str),
+                timestamp,
+                info: None,
+            })
+            .collect();
+        Snapshot { records }
+    }
+
+    /// Returns a Snapshot of the results of a multi-region query, timestamped now
+    ///
+    /// * Requires feature: `non-async` or `async` (Turned **on** by default),
+    ///   or one of `async-std` and `smol`
+    ///
+    /// # Arguments
+    /// * `results` - The [`RegionResults`] of the query
+    /// * `filter_str` - The filter string of the query (EX: `\\appid\\240`)
+    #[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol"))]
+    pub fn from_regions(results: &RegionResults, filter_str: &str) -> Snapshot {
+        let timestamp = now();
+        let records = results
+            .servers()
+            .iter()
+            .map(|server| ServerRecord {
+                addr: server.addr.clone(),
+                region: server.region,
+                filter: String::from(filter_str),
+                timestamp,
+                info: None,
+            })
+            .collect();
+        Snapshot { records }
+    }
+
+    /// Add a record
+    ///
+    /// # Arguments
+    /// * `record` - The [`ServerRecord`] to add
+    pub fn push(&mut self, record: ServerRecord) {
+        self.records.push(record);
+    }
+
+    /// Set the A2S info of every record of a server
+    ///
+    /// Returns `true` if the server is in the snapshot.
+    ///
+    /// # Arguments
+    /// * `addr` - The server's address (EX: `10.0.0.1:27015`)
+    /// * `info` - The server's [`ServerInfo`]
+    pub fn set_info(&mut self, addr: &str, info: ServerInfo) -> bool {
+        let mut found = false;
+        for record in self.records.iter_mut().filter(|r| r.addr == addr) {
+            record.info = Some(info.clone());
+            found = true;
+        }
+        found
+    }
+
+    /// Returns the records
+    pub fn records(&self) -> &[ServerRecord] {
+        &self.records
+    }
+
+    /// Returns the records, consuming the snapshot
+    pub fn into_records(self) -> Vec<ServerRecord> {
+        self.records
+    }
+
+    /// Returns the amount of records
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Returns `true` if there are no records
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Write the snapshot to a file, replacing it if it exists
+    ///
+    /// # Arguments
+    /// * `path` - Path of the file
+    /// * `format` - [`SnapshotFormat`] of the file
+    pub fn save<P: AsRef<Path>>(&self, path: P, format: SnapshotFormat) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer, format)?;
+        writer.flush()
+    }
+
+    /// Read a snapshot from a file
+    ///
+    /// # Arguments
+    /// * `path` - Path of the file
+    /// * `format` - [`SnapshotFormat`] of the file
+    pub fn load<P: AsRef<Path>>(path: P, format: SnapshotFormat) -> Result<Snapshot> {
+        Self::read(File::open(path)?, format)
+    }
+
+    /// Write the snapshot to the given writer
+    ///
+    /// # Arguments
+    /// * `writer` - Where to write the snapshot to
+    /// * `format` - [`SnapshotFormat`] to write
+    pub fn write<W: Write>(&self, mut writer: W, format: SnapshotFormat) -> Result<()> {
+        match format {
+            SnapshotFormat::Json => {
+                let records: Vec<Value> = self.records.iter().map(record_to_json).collect();
+                serde_json::to_writer_pretty(&mut writer, &records)?;
+                writeln!(writer)
+            }
+            SnapshotFormat::Ndjson => {
+                for record in &self.records {
+                    serde_json::to_writer(&mut writer, &record_to_json(record))?;
+                    writeln!(writer)?;
+                }
+                Ok(())
+            }
+            SnapshotFormat::Csv => write_csv(&self.records, writer),
+            SnapshotFormat::Binary => writer.write_all(&encode_binary(&self.records)?),
+        }
+    }
+
+    /// Read a snapshot from the given reader
+    ///
+    /// # Arguments
+    /// * `reader` - Where to read the snapshot from
+    /// * `format` - [`SnapshotFormat`] to read
+    pub fn read<R: Read>(reader: R, format: SnapshotFormat) -> Result<Snapshot> {
+        let records = match format {
+            SnapshotFormat::Json => {
+                let values: Vec<Value> = serde_json::from_reader(reader)?;
+                values.iter().map(record_from_json).collect::<Result<_>>()?
+            }
+            SnapshotFormat::Ndjson => {
+                let mut records = vec![];
+                for line in BufReader::new(reader).lines() {
+                    let line = line?;
+                    if !line.trim().is_empty() {
+                        records.push(record_from_json(&serde_json::from_str(&line)?)?);
+                    }
+                }
+                records
+            }
+            SnapshotFormat::Csv => read_csv(reader)?,
+            SnapshotFormat::Binary => {
+                let mut buf = vec![];
+                BufReader::new(reader).read_to_end(&mut buf)?;
+                decode_binary(buf)?
+            }
+        };
+        Ok(Snapshot { records })
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn invalid(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn record_to_json(record: &ServerRecord) -> Value {
+    let info = record.info.as_ref().map(|info| {
+        json!({
+            "protocol": info.protocol,
+            "name": info.name,
+            "map": info.map,
+            "folder": info.folder,
+            "game": info.game,
+            "appid": info.appid,
+            "players": info.players,
+            "max_players": info.max_players,
+            "bots": info.bots,
+            "dedicated": info.dedicated,
+            "proxy": info.proxy,
+            "linux": info.linux,
+            "password": info.password,
+            "secure": info.secure,
+            "version": info.version,
+            "port": info.port,
+            "keywords": info.keywords,
+            "ping_ms": info.ping.as_secs_f64() * 1000.0,
+        })
+    });
+    json!({
+        "address": record.addr,
+        "region": record.region,
+        "filter": record.filter,
+        "timestamp": record.timestamp,
+        "info": info,
+    })
+}
+
+fn record_from_json(value: &Value) -> Result<ServerRecord> {
+    let object = value
+        .as_object()
+        .ok_or_else(|| invalid(format!("Invalid record: {}", value)))?;
+    let info = match object.get("info") {
+        None | Some(Value::Null) => None,
+        Some(info) => Some(info_from_json(info)?),
+    };
+    Ok(ServerRecord {
+        addr: json_string(object, "address")?,
+        region: serde_json::from_value(object.get("region").cloned().unwrap_or(Value::Null))?,
+        filter: json_string(object, "filter")?,
+        timestamp: json_u64(object, "timestamp")?,
+        info,
+    })
+}
+
+fn info_from_json(value: &Value) -> Result<ServerInfo> {
+    let object = value
+        .as_object()
+        .ok_or_else(|| invalid(format!("Invalid info: {}", value)))?;
+    let small = |key: &str| -> Result<u8> {
+        json_u64(object, key)?
+            .try_into()
+            .map_err(|_| invalid(format!("Invalid {}", key)))
+    };
+    let port = match object.get("port") {
+        None | Some(Value::Null) => None,
+        Some(_) => Some(
+            json_u64(object, "port")?
+                .try_into()
+                .map_err(|_| invalid(String::from("Invalid port")))?,
+        ),
+    };
+    let keywords = match object.get("keywords") {
+        Some(Value::Array(keywords)) => keywords
+            .iter()
+            .filter_map(|k| k.as_str().map(String::from))
+            .collect(),
+        _ => vec![],
+    };
+    let ping_ms = object.get("ping_ms").and_then(Value::as_f64).unwrap_or(0.0);
+    Ok(ServerInfo {
+        protocol: small("protocol")?,
+        name: json_string(object, "name")?,
+        map: json_string(object, "map")?,
+        folder: json_string(object, "folder")?,
+        game: json_string(object, "game")?,
+        appid: json_u64(object, "appid")? as u32,
+        players: small("players")?,
+        max_players: small("max_players")?,
+        bots: small("bots")?,
+        dedicated: json_bool(object, "dedicated"),
+        proxy: json_bool(object, "proxy"),
+        linux: json_bool(object, "linux"),
+        password: json_bool(object, "password"),
+        secure: json_bool(object, "secure"),
+        version: json_string(object, "version")?,
+        port,
+        keywords,
+        ping: Duration::try_from_secs_f64(ping_ms / 1000.0).unwrap_or_default(),
+    })
+}
+
+fn json_string(object: &Map<String, Value>, key: &str) -> Result<String> {
+    object
+        .get(key)
+        .and_then(Value::as_str)
+        .map(String::from)
+        .ok_or_else(|| invalid(format!("Missing {}", key)))
+}
+
+fn json_u64(object: &Map<String, Value>, key: &str) -> Result<u64> {
+    object
+        .get(key)
+        .and_then(Value::as_u64)
+        .ok_or_else(|| invalid(format!("Missing {}", key)))
+}
+
+fn json_bool(object: &Map<String, Value>, key: &str) -> bool {
+    object.get(key).and_then(Value::as_bool).unwrap_or(false)
+}
+
+fn write_csv<W: Write>(records: &[ServerRecord], mut writer: W) -> Result<()> {
+    writeln!(writer, "{},{}", CSV_HEADER.join(","), CSV_PING)?;
+    for record in records {
+        let mut cells = vec![
+            record.addr.clone(),
+            record.region.as_u8().to_string(),
+            record.filter.clone(),
+            record.timestamp.to_string(),
+        ];
+        match &record.info {
+            Some(info) => cells.extend([
+                info.protocol.to_string(),
+                info.name.clone(),
+                info.map.clone(),
+                info.folder.clone(),
+                info.game.clone(),
+                info.appid.to_string(),
+                info.players.to_string(),
+                info.max_players.to_string(),
+                info.bots.to_string(),
+                info.dedicated.to_string(),
+                info.proxy.to_string(),
+                info.linux.to_string(),
+                info.password.to_string(),
+                info.secure.to_string(),
+                info.version.clone(),
+                info.port.map(|p| p.to_string()).unwrap_or_default(),
+                info.keywords.join(","),
+                (info.ping.as_secs_f64() * 1000.0).to_string(),
+            ]),
+            None => cells.extend(vec![String::new(); CSV_HEADER.len() - 3]),
+        }
+        let cells: Vec<String> = cells.iter().map(|c| csv_field(c)).collect();
+        writeln!(writer, "{}", cells.join(","))?;
+    }
+    Ok(())
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        String::from(s)
+    }
+}
+
+// Split CSV into rows of fields, quoted fields may contain line breaks
+fn csv_rows(text: &str) -> Vec<Vec<String>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted => (),
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+fn read_csv<R: Read>(mut reader: R) -> Result<Vec<ServerRecord>> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    let mut rows = csv_rows(&text).into_iter();
+    match rows.next() {
+        Some(header) if header.len() == CSV_HEADER.len() + 1 => (),
+        _ => return Err(invalid(String::from("Invalid CSV header"))),
+    }
+
+    let mut records = vec![];
+    for row in rows {
+        if row.len() != CSV_HEADER.len() + 1 {
+            return Err(invalid(format!("Invalid CSV row: {}", row.join(","))));
+        }
+        let number = |i: usize| -> Result<u64> {
+            row[i]
+                .parse()
+                .map_err(|_| invalid(format!("Invalid {}: {}", CSV_HEADER[i], row[i])))
+        };
+        let small = |i: usize| -> Result<u8> {
+            row[i]
+                .parse()
+                .map_err(|_| invalid(format!("Invalid {}: {}", CSV_HEADER[i], row[i])))
+        };
+        let flag = |i: usize| row[i] == "true";
+
+        let ping = &row[CSV_HEADER.len()];
+        let info = if ping.is_empty() {
+            None
+        } else {
+            let ping_ms: f64 = ping
+                .parse()
+                .map_err(|_| invalid(format!("Invalid ping_ms: {}", ping)))?;
+            Some(ServerInfo {
+                protocol: small(4)?,
+                name: row[5].clone(),
+                map: row[6].clone(),
+                folder: row[7].clone(),
+                game: row[8].clone(),
+                appid: number(9)? as u32,
+                players: small(10)?,
+                max_players: small(11)?,
+                bots: small(12)?,
+                dedicated: flag(13),
+                proxy: flag(14),
+                linux: flag(15),
+                password: flag(16),
+                secure: flag(17),
+                version: row[18].clone(),
+                port: if row[19].is_empty() { None } else { Some(number(19)? as u16) },
+                keywords: row[20]
+                    .split(',')
+                    .filter(|k| !k.is_empty())
+                    .map(String::from)
+                    .collect(),
+                ping: Duration::try_from_secs_f64(ping_ms / 1000.0).unwrap_or_default(),
+            })
+        };
+        records.push(ServerRecord {
+            addr: row[0].clone(),
+            region: Region::from(small(1)?),
+            filter: row[2].clone(),
+            timestamp: number(3)?,
+            info,
+        });
+    }
+    Ok(records)
+}
+
+// Layout: magic, version, filter table (count, cstrings), record count, then
+// each record: IPv4 address and port, region, filter index, timestamp and
+// optionally its A2S info
+fn encode_binary(records: &[ServerRecord]) -> Result<Vec<u8>> {
+    let mut filters: Vec<&str> = vec![];
+    for record in records {
+        if !filters.contains(&record.filter.as_str()) {
+            filters.push(&record.filter);
+        }
+    }
+
+    let mut cursor: Cursor<Vec<u8>> = Cursor::new(vec![]);
+    for byte in BINARY_MAGIC {
+        cursor.write_u8(byte)?;
+    }
+    cursor.write_u8(BINARY_VERSION)?;
+    cursor.write_u32::<LittleEndian>(filters.len() as u32)?;
+    for filter in &filters {
+        cursor.write_cstring(filter)?;
+    }
+
+    cursor.write_u32::<LittleEndian>(records.len() as u32)?;
+    for record in records {
+        let addr: SocketAddrV4 = record
+            .addr
+            .parse()
+            .map_err(|_| Error::other(format!("Invalid server address: {}", record.addr)))?;
+        for octet in addr.ip().octets() {
+            cursor.write_u8(octet)?;
+        }
+        cursor.write_u16::<BigEndian>(addr.port())?;
+        cursor.write_u8(record.region.as_u8())?;
+        let filter = filters.iter().position(|f| *f == record.filter).unwrap_or(0);
+        cursor.write_u32::<LittleEndian>(filter as u32)?;
+        cursor.write_u64::<LittleEndian>(record.timestamp)?;
+
+        match &record.info {
+            Some(info) => {
+                cursor.write_u8(1)?;
+                cursor.write_u8(info.protocol)?;
+                cursor.write_cstring(&info.name)?;
+                cursor.write_cstring(&info.map)?;
+                cursor.write_cstring(&info.folder)?;
+                cursor.write_cstring(&info.game)?;
+                cursor.write_u32::<LittleEndian>(info.appid)?;
+                cursor.write_u8(info.players)?;
+                cursor.write_u8(info.max_players)?;
+                cursor.write_u8(info.bots)?;
+                let flags = [info.dedicated, info.proxy, info.linux, info.password, info.secure]
+                    .iter()
+                    .enumerate()
+                    .fold(0u8, |flags, (i, set)| flags | ((*set as u8) << i));
+                cursor.write_u8(flags)?;
+                cursor.write_cstring(&info.version)?;
+                // 0 is not a valid game port
+                cursor.write_u16::<LittleEndian>(info.port.unwrap_or(0))?;
+                cursor.write_cstring(&info.keywords.join(","))?;
+                cursor.write_u64::<LittleEndian>(info.ping.as_nanos().min(u64::MAX as u128) as u64)?;
+            }
+            None => cursor.write_u8(0)?,
+        }
+    }
+    Ok(cursor.into_inner())
+}
+
+fn decode_binary(buf: Vec<u8>) -> Result<Vec<ServerRecord>> {
+    let mut cursor = Cursor::new(buf);
+    if !cursor.read_u8_veccheck(&BINARY_MAGIC)? {
+        return Err(invalid(String::from("Not a binary snapshot")));
+    }
+    let version = cursor.read_u8()?;
+    if version != BINARY_VERSION {
+        return Err(invalid(format!("Unsupported binary snapshot version: {}", version)));
+    }
+
+    let mut filters = vec![];
+    for _ in 0..cursor.read_u32::<LittleEndian>()? {
+        filters.push(cursor.read_cstring()?);
+    }
+
+    let count = cursor.read_u32::<LittleEndian>()?;
+    let mut records = vec![];
+    for _ in 0..count {
+        let mut ip: [u8; 4] = [0; 4];
+        cursor.read_exact(&mut ip)?;
+        let port = cursor.read_u16::<BigEndian>()?;
+        let region = Region::from(cursor.read_u8()?);
+        let filter = filters
+            .get(cursor.read_u32::<LittleEndian>()? as usize)
+            .cloned()
+            .ok_or_else(|| invalid(String::from("Invalid filter index")))?;
+        let timestamp = cursor.read_u64::<LittleEndian>()?;
+
+        let info = if cursor.read_u8()? == 1 {
+            let protocol = cursor.read_u8()?;
+            let name = cursor.read_cstring()?;
+            let map = cursor.read_cstring()?;
+            let folder = cursor.read_cstring()?;
+            let game = cursor.read_cstring()?;
+            let appid = cursor.read_u32::<LittleEndian>()?;
+            let players = cursor.read_u8()?;
+            let max_players = cursor.read_u8()?;
+            let bots = cursor.read_u8()?;
+            let flags = cursor.read_u8()?;
+            let version = cursor.read_cstring()?;
+            let port = cursor.read_u16::<LittleEndian>()?;
+            let keywords = cursor.read_cstring()?;
+            let ping = cursor.read_u64::<LittleEndian>()?;
+            Some(ServerInfo {
+                protocol,
+                name,
+                map,
+                folder,
+                game,
+                appid,
+                players,
+                max_players,
+                bots,
+                dedicated: flags & 0x01 != 0,
+                proxy: flags & 0x02 != 0,
+                linux: flags & 0x04 != 0,
+                password: flags & 0x08 != 0,
+                secure: flags & 0x10 != 0,
+                version,
+                port: if port == 0 { None } else { Some(port) },
+                keywords: keywords
+                    .split(',')
+                    .filter(|k| !k.is_empty())
+                    .map(String::from)
+                    .collect(),
+                ping: Duration::from_nanos(ping),
+            })
+        } else {
+            None
+        };
+
+        records.push(ServerRecord {
+            addr: SocketAddrV4::new(ip.into(), port).to_string(),
+            region,
+            filter,
+            timestamp,
+            info,
+        });
+    }
+    Ok(records)
+}
@@ -0,0 +1,198 @@
+use socket2::{Domain, Protocol, Socket, Type};
+use std::io::{Error, ErrorKind, Result};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+
+/// Options of the UDP socket of a client
+///
+/// * Intended to be used with: [`MSQClient::with_options`](crate::MSQClient::with_options)
+///   and [`MSQClientBlock::with_options`](crate::MSQClientBlock::with_options)
+/// * Binds to `0.0.0.0` on a random port unless told otherwise.
+/// * An IPv6 socket is dual-stack by default, so it also reaches IPv4 master
+///   servers through IPv4-mapped addresses.
+/// * Existing sockets can be used instead with `from_socket`.
+///
+/// # Quick Start
+/// ```rust,no_run
+/// use msq::{MSQClientBlock, SocketOptions, Region, Filter};
+/// use std::io::Result;
+/// use std::net::Ipv6Addr;
+///
+/// fn main() -> Result<()> {
+///     let options = SocketOptions::new()
+///         .ip(Ipv6Addr::UNSPECIFIED.into())   // Bind to [::]
+///         .port(27005)                        // Fixed source port
+///         .recv_buffer_size(1 << 20)          // 1 MiB receive buffer
+///         .ttl(32)
+///         .dscp(46);                          // Expedited Forwarding
+///     let mut client = MSQClientBlock::with_options(&options)?;
+///     client.connect("hl2master.steampowered.com:27011")?;
+///     let servers = client.query(Region::Europe, Filter::new().appid(240))?;
+///     Ok(())
+/// }
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SocketOptions {
+    ip: IpAddr,
+    port: u16,
+    dual_stack: bool,
+    recv_buffer_size: Option<usize>,
+    ttl: Option<u32>,
+    dscp: Option<u8>,
+}
+
+impl Default for SocketOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SocketOptions {
+    /// Create a new SocketOptions variable binding to `0.0.0.0:0`
+    pub fn new() -> SocketOptions {
+        SocketOptions {
+            ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: 0,
+            dual_stack: true,
+            recv_buffer_size: None,
+            ttl: None,
+            dscp: None,
+        }
+    }
+
+    /// Bind to the given local IPv4 or IPv6 address
+    ///
+    /// # Arguments
+    /// * `ip` - The local address (EX: `192.168.1.10`, `::`)
+    pub fn ip(mut self, ip: IpAddr) -> Self {
+        self.ip = ip;
+        self
+    }
+
+    /// Bind to a fixed local port (Defaults to `0`, a random port)
+    ///
+    /// # Arguments
+    /// * `port` - The local port
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Whether an IPv6 socket also handles IPv4 (Defaults to `true`)
+    ///
+    /// # Arguments
+    /// * `dual_stack` - `false` to only use IPv6
+    pub fn dual_stack(mut self, dual_stack: bool) -> Self {
+        self.dual_stack = dual_stack;
+        self
+    }
+
+    /// Set the size of the socket's receive buffer in bytes
+    ///
+    /// # Arguments
+    /// * `size` - Size in bytes, the system may round or cap it
+    pub fn recv_buffer_size(mut self, size: usize) -> Self {
+        self.recv_buffer_size = Some(size);
+        self
+    }
+
+    /// Set the TTL (IPv4) or hop limit (IPv6) of sent datagrams
+    ///
+    /// # Arguments
+    /// * `ttl` - Time to live, `1 - 255`
+    pub fn ttl(mut self, ttl: u32) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Set the DSCP of sent datagrams
+    ///
+    /// # Arguments
+    /// * `dscp` - Differentiated services code point, `0 - 63`
+    pub fn dscp(mut self, dscp: u8) -> Self {
+        self.dscp = Some(dscp);
+        self
+    }
+
+    /// Returns the local address to bind to
+    pub fn local_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.ip, self.port)
+    }
+
+    /// Create and bind a UDP socket with these options
+    pub fn bind(&self) -> Result<UdpSocket> {
+        let addr = self.local_addr();
+        let sock = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+        if addr.is_ipv6() {
+            sock.set_only_v6(!self.dual_stack)?;
+        }
+        if let Some(size) = self.recv_buffer_size {
+            sock.set_recv_buffer_size(size)?;
+        }
+        if let Some(ttl) = self.ttl {
+            match addr {
+                SocketAddr::V4(_) => sock.set_ttl_v4(ttl)?,
+                SocketAddr::V6(_) => sock.set_unicast_hops_v6(ttl)?,
+            }
+        }
+        if let Some(dscp) = self.dscp {
+            if dscp > 63 {
+                return Err(Error::new(ErrorKind::InvalidInput, format!("Invalid DSCP: {}", dscp)));
+            }
+            set_dscp(&sock, addr, dscp)?;
+        }
+        sock.bind(&addr.into())?;
+        Ok(sock.into())
+    }
+}
+
+// DSCP is the upper 6 bits of the IPv4 TOS and IPv6 traffic class
+#[cfg(any(
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "netbsd",
+    target_os = "openbsd",
+))]
+fn set_dscp(sock: &Socket, addr: SocketAddr, dscp: u8) -> Result<()> {
+    match addr {
+        SocketAddr::V4(_) => sock.set_tos_v4((dscp as u32) << 2),
+        SocketAddr::V6(_) => sock.set_tclass_v6((dscp as u32) << 2),
+    }
+}
+
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "netbsd",
+    target_os = "openbsd",
+)))]
+fn set_dscp(_sock: &Socket, _addr: SocketAddr, _dscp: u8) -> Result<()> {
+    Err(Error::new(ErrorKind::Unsupported, "DSCP is not supported on this platform"))
+}
+
+/// Pick the address to connect a socket bound to `local` to, out of the
+/// resolved addresses of a host. IPv4 addresses are mapped to IPv6 for
+/// IPv6 sockets.
+pub(crate) fn peer_addr<I: IntoIterator<Item = SocketAddr>>(local: SocketAddr, addrs: I) -> Result<SocketAddr> {
+    let addrs: Vec<SocketAddr> = addrs.into_iter().collect();
+    let same_family = addrs.iter().find(|addr| addr.is_ipv4() == local.is_ipv4());
+    match (same_family, local) {
+        (Some(addr), _) => Ok(*addr),
+        (None, SocketAddr::V6(_)) => addrs
+            .iter()
+            .find_map(|addr| match addr.ip() {
+                IpAddr::V4(ip) => Some(SocketAddr::new(IpAddr::V6(ip.to_ipv6_mapped()), addr.port())),
+                IpAddr::V6(_) => None,
+            })
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Could not resolve the address")),
+        (None, SocketAddr::V4(_)) if !addrs.is_empty() => Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Only IPv6 addresses resolved for an IPv4 socket",
+        )),
+        (None, SocketAddr::V4(_)) => Err(Error::new(ErrorKind::InvalidInput, "Could not resolve the address")),
+    }
+}
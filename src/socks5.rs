@@ -0,0 +1,391 @@
+use crate::transport::{time_left, timed_out, Transport};
+
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+const VERSION: u8 = 0x05;
+const AUTH_VERSION: u8 = 0x01;
+const METHOD_NONE: u8 = 0x00;
+const METHOD_PASSWORD: u8 = 0x02;
+const METHOD_UNACCEPTABLE: u8 = 0xFF;
+const CMD_UDP_ASSOCIATE: u8 = 0x03;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+/// A SOCKS5 proxy to tunnel UDP traffic through with UDP ASSOCIATE
+///
+/// * Intended to be used with: [`Socks5Transport`] for
+///   [`MSQClient`](crate::MSQClient) and [`MSQClientBlock`](crate::MSQClientBlock),
+///   and the `with_proxy` constructors of the A2S clients
+/// * Every association keeps a TCP connection to the proxy open for as long
+///   as it is used, as the proxy ends the association when it closes.
+/// * Supports no authentication and username/password authentication
+///   ([RFC 1929](https://www.rfc-editor.org/rfc/rfc1929)).
+///
+/// # Quick Start
+/// ```rust,no_run
+/// use msq::{MSQClientBlock, Socks5Proxy, Region, Filter};
+/// use std::io::Result;
+///
+/// fn main() -> Result<()> {
+///     let proxy = Socks5Proxy::new("10.0.0.1:1080").auth("user", "password");
+///     let mut client = MSQClientBlock::with_transport(proxy.transport()?);
+///     client.connect("hl2master.steampowered.com:27011")?;
+///     let servers = client.query(Region::Europe, Filter::new().appid(240))?;
+///     Ok(())
+/// }
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct Socks5Proxy {
+    addr: String,
+    auth: Option<(String, String)>,
+    timeout: Duration,
+}
+
+impl Socks5Proxy {
+    /// Create a new Socks5Proxy of the proxy at the given address
+    ///
+    /// # Arguments
+    /// * `addr` - The proxy's hostname/ip address (EX: `10.0.0.1:1080`)
+    pub fn new(addr: &str) -> Socks5Proxy {
+        Socks5Proxy {
+            addr: addr.to_string(),
+            auth: None,
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Authenticate with a username and password
+    ///
+    /// # Arguments
+    /// * `username` - Username, up to 255 bytes
+    /// * `password` - Password, up to 255 bytes
+    pub fn auth(mut self, username: &str, password: &str) -> Self {
+        self.auth = Some((username.to_string(), password.to_string()));
+        self
+    }
+
+    /// Set how long to wait for the proxy while associating (Defaults to 10 seconds)
+    ///
+    /// # Arguments
+    /// * `timeout` - Time until connecting, authenticating or associating fails
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Open a new UDP association through the proxy
+    pub fn associate(&self) -> Result<Socks5UdpSocket> {
+        let proxy = self
+            .addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| Error::other(format!("Invalid proxy address: {}", self.addr)))?;
+        let mut control = TcpStream::connect_timeout(&proxy, self.timeout)?;
+        control.set_read_timeout(Some(self.timeout))?;
+        control.set_write_timeout(Some(self.timeout))?;
+        control.set_nodelay(true)?;
+        self.authenticate(&mut control)?;
+
+        // Tell the proxy where the datagrams will come from
+        let sock = UdpSocket::bind(SocketAddr::new(control.local_addr()?.ip(), 0))?;
+        let mut request = vec![VERSION, CMD_UDP_ASSOCIATE, 0x00];
+        write_addr(&mut request, sock.local_addr()?);
+        control.write_all(&request)?;
+
+        let mut reply = [0u8; 3];
+        control.read_exact(&mut reply)?;
+        if reply[0] != VERSION {
+            return Err(Error::new(ErrorKind::InvalidData, "Not a SOCKS5 proxy"));
+        }
+        if reply[1] != 0x00 {
+            return Err(Error::other(format!("SOCKS5 UDP ASSOCIATE failed: {}", reply_error(reply[1]))));
+        }
+        let mut relay = read_addr(&mut control)?;
+        // An unspecified relay address means the proxy's own address
+        if relay.ip().is_unspecified() {
+            relay.set_ip(proxy.ip());
+        }
+
+        Ok(Socks5UdpSocket { _control: control, sock, relay })
+    }
+
+    /// Open a new UDP association through the proxy, as a [`Transport`]
+    pub fn transport(&self) -> Result<Socks5Transport> {
+        Ok(Socks5Transport {
+            sock: self.associate()?,
+            peer: None,
+        })
+    }
+
+    fn authenticate(&self, control: &mut TcpStream) -> Result<()> {
+        match &self.auth {
+            Some(_) => control.write_all(&[VERSION, 2, METHOD_NONE, METHOD_PASSWORD])?,
+            None => control.write_all(&[VERSION, 1, METHOD_NONE])?,
+        }
+        let mut reply = [0u8; 2];
+        control.read_exact(&mut reply)?;
+        if reply[0] != VERSION {
+            return Err(Error::new(ErrorKind::InvalidData, "Not a SOCKS5 proxy"));
+        }
+
+        match (reply[1], &self.auth) {
+            (METHOD_NONE, _) => Ok(()),
+            (METHOD_PASSWORD, Some((username, password))) => {
+                if username.len() > 255 || password.len() > 255 {
+                    return Err(Error::new(ErrorKind::InvalidInput, "SOCKS5 username or password is too long"));
+                }
+                let mut request = vec![AUTH_VERSION, username.len() as u8];
+                request.extend(username.as_bytes());
+                request.push(password.len() as u8);
+                request.extend(password.as_bytes());
+                control.write_all(&request)?;
+
+                let mut status = [0u8; 2];
+                control.read_exact(&mut status)?;
+                match status[1] {
+                    0x00 => Ok(()),
+                    _ => Err(Error::new(ErrorKind::PermissionDenied, "SOCKS5 authentication failed")),
+                }
+            }
+            (METHOD_UNACCEPTABLE, _) => Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "SOCKS5 proxy accepts none of the authentication methods",
+            )),
+            (method, _) => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("SOCKS5 proxy chose an unknown authentication method: {}", method),
+            )),
+        }
+    }
+}
+
+/// A UDP socket tunneled through a [`Socks5Proxy`]
+///
+/// * Created with [`Socks5Proxy::associate`]
+/// * Adds and strips the SOCKS5 UDP header of every datagram, so it is used
+///   like a plain unconnected UDP socket. Fragmented datagrams are dropped.
+pub struct Socks5UdpSocket {
+    _control: TcpStream,
+    sock: UdpSocket,
+    relay: SocketAddr,
+}
+
+impl Socks5UdpSocket {
+    /// Send a datagram to the given address through the proxy
+    ///
+    /// # Arguments
+    /// * `buf` - The datagram
+    /// * `target` - The address to send it to
+    pub fn send_to(&self, buf: &[u8], target: SocketAddr) -> Result<usize> {
+        self.sock.send_to(&encode_datagram(target, buf), self.relay)?;
+        Ok(buf.len())
+    }
+
+    /// Receive a datagram through the proxy
+    ///
+    /// Returns the length of the datagram and the address it came from
+    ///
+    /// # Arguments
+    /// * `buf` - Where to write the datagram to
+    pub fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr)> {
+        let mut packet = [0u8; 2048];
+        loop {
+            let (len, from) = self.sock.recv_from(&mut packet)?;
+            if let Some(received) = unwrap_relayed(&packet[..len], from, self.relay, buf) {
+                return Ok(received);
+            }
+        }
+    }
+
+    /// Set how long [`recv_from`](#method.recv_from) waits for a datagram,
+    /// `None` to wait for as long as it takes
+    ///
+    /// # Arguments
+    /// * `timeout` - Time until receiving fails
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        self.sock.set_read_timeout(timeout)
+    }
+
+    /// Returns the local address of the UDP socket
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.sock.local_addr()
+    }
+
+    /// Returns the address of the proxy's UDP relay
+    pub fn relay_addr(&self) -> SocketAddr {
+        self.relay
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn into_parts(self) -> (TcpStream, UdpSocket, SocketAddr) {
+        (self._control, self.sock, self.relay)
+    }
+}
+
+/// [`Transport`] tunneled through a [`Socks5Proxy`]
+///
+/// * Created with [`Socks5Proxy::transport`]
+/// * Datagrams from any other address than the connected one are dropped.
+pub struct Socks5Transport {
+    sock: Socks5UdpSocket,
+    peer: Option<SocketAddr>,
+}
+
+impl Socks5Transport {
+    fn peer(&self) -> Result<SocketAddr> {
+        self.peer
+            .ok_or_else(|| Error::new(ErrorKind::NotConnected, "Transport is not connected"))
+    }
+}
+
+impl Transport for Socks5Transport {
+    fn connect(&mut self, addr: &str) -> Result<()> {
+        let peer = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| Error::other(format!("Invalid address: {}", addr)))?;
+        self.peer = Some(peer);
+        Ok(())
+    }
+
+    fn send(&mut self, buf: &[u8], deadline: Option<Instant>) -> Result<usize> {
+        if let Some(deadline) = deadline {
+            time_left(deadline)?;
+        }
+        self.sock.send_to(buf, self.peer()?)
+    }
+
+    fn recv(&mut self, buf: &mut [u8], deadline: Option<Instant>) -> Result<usize> {
+        let peer = self.peer()?;
+        loop {
+            self.sock.set_read_timeout(deadline.map(time_left).transpose()?)?;
+            let (len, from) = self.sock.recv_from(buf).map_err(timed_out)?;
+            if from == peer {
+                return Ok(len);
+            }
+        }
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        self.sock.local_addr()
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        self.peer()
+    }
+}
+
+/// Add the SOCKS5 UDP header to a datagram sent to the given address
+pub(crate) fn encode_datagram(target: SocketAddr, data: &[u8]) -> Vec<u8> {
+    // Reserved and fragment number
+    let mut packet = vec![0x00, 0x00, 0x00];
+    write_addr(&mut packet, target);
+    packet.extend(data);
+    packet
+}
+
+/// Strip the SOCKS5 UDP header of a datagram, returning where it came from.
+/// `None` for fragments, domain name sources and malformed datagrams.
+pub(crate) fn decode_datagram(packet: &[u8]) -> Option<(SocketAddr, &[u8])> {
+    if packet.len() < 4 || packet[2] != 0x00 {
+        return None;
+    }
+    let (ip, rest): (IpAddr, &[u8]) = match packet[3] {
+        ATYP_IPV4 if packet.len() >= 10 => {
+            let octets: [u8; 4] = packet[4..8].try_into().ok()?;
+            (octets.into(), &packet[8..])
+        }
+        ATYP_IPV6 if packet.len() >= 22 => {
+            let octets: [u8; 16] = packet[4..20].try_into().ok()?;
+            (octets.into(), &packet[20..])
+        }
+        _ => return None,
+    };
+    let port = u16::from_be_bytes([rest[0], rest[1]]);
+    Some((SocketAddr::new(ip, port), &rest[2..]))
+}
+
+/// Copy the data of a datagram received from the relay into `buf`,
+/// returning its length and where it came from. `None` for datagrams to
+/// drop: from any other address than the relay, or that do not decode.
+pub(crate) fn unwrap_relayed(packet: &[u8], from: SocketAddr, relay: SocketAddr, buf: &mut [u8]) -> Option<(usize, SocketAddr)> {
+    // Only the relay may send datagrams to the association
+    if from != relay {
+        return None;
+    }
+    let (source, data) = decode_datagram(packet)?;
+    let len = data.len().min(buf.len());
+    buf[..len].copy_from_slice(&data[..len]);
+    Some((len, source))
+}
+
+fn write_addr(buf: &mut Vec<u8>, addr: SocketAddr) {
+    match addr.ip() {
+        IpAddr::V4(ip) => {
+            buf.push(ATYP_IPV4);
+            buf.extend(ip.octets());
+        }
+        IpAddr::V6(ip) => {
+            buf.push(ATYP_IPV6);
+            buf.extend(ip.octets());
+        }
+    }
+    buf.extend(addr.port().to_be_bytes());
+}
+
+fn read_addr<R: Read>(reader: &mut R) -> Result<SocketAddr> {
+    let mut atyp = [0u8; 1];
+    reader.read_exact(&mut atyp)?;
+    let ip: IpAddr = match atyp[0] {
+        ATYP_IPV4 => {
+            let mut octets = [0u8; 4];
+            reader.read_exact(&mut octets)?;
+            octets.into()
+        }
+        ATYP_IPV6 => {
+            let mut octets = [0u8; 16];
+            reader.read_exact(&mut octets)?;
+            octets.into()
+        }
+        ATYP_DOMAIN => {
+            let mut len = [0u8; 1];
+            reader.read_exact(&mut len)?;
+            let mut domain = vec![0u8; len[0] as usize];
+            reader.read_exact(&mut domain)?;
+            let mut port = [0u8; 2];
+            reader.read_exact(&mut port)?;
+            let domain = String::from_utf8_lossy(&domain).to_string();
+            return (domain.as_str(), u16::from_be_bytes(port))
+                .to_socket_addrs()?
+                .next()
+                .ok_or_else(|| Error::other(format!("Invalid relay address: {}", domain)));
+        }
+        atyp => {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("SOCKS5 proxy sent an unknown address type: {}", atyp),
+            ))
+        }
+    };
+    let mut port = [0u8; 2];
+    reader.read_exact(&mut port)?;
+    Ok(SocketAddr::new(ip, u16::from_be_bytes(port)))
+}
+
+fn reply_error(code: u8) -> &'static str {
+    match code {
+        0x01 => "general SOCKS server failure",
+        0x02 => "connection not allowed by ruleset",
+        0x03 => "network unreachable",
+        0x04 => "host unreachable",
+        0x05 => "connection refused",
+        0x06 => "TTL expired",
+        0x07 => "command not supported",
+        0x08 => "address type not supported",
+        _ => "unknown error",
+    }
+}
@@ -0,0 +1,453 @@
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+use crate::cancel::CANCEL_POLL;
+use crate::socket::peer_addr;
+
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+use std::collections::VecDeque;
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+use std::future::Future;
+use std::io::{Error, ErrorKind, Result};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+use std::pin::Pin;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+use std::sync::{Arc, Mutex};
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+use std::task::{Context, Poll, Waker};
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+use std::thread;
+use std::time::Instant;
+
+/// Transport - Sends and receives the datagrams of a client
+///
+/// * Intended to be used with: [`MSQClient::with_transport`](crate::MSQClient::with_transport)
+///   and [`MSQClientBlock::with_transport`](crate::MSQClientBlock::with_transport)
+/// * Provided implementations: [`UdpTransport`] (the default of
+///   [`MSQClientBlock`](crate::MSQClientBlock)), [`ChannelTransport`]
+///   (in-memory, no sockets), [`Socks5Transport`](crate::Socks5Transport)
+///   (through a SOCKS5 proxy) and [`Replay`](crate::Replay) (a recorded session)
+/// * Implement it to route the traffic of a client through any other path.
+/// * A transport talks to a single peer, the master server it is connected to.
+/// * Every call may be given a deadline: a call that could not complete
+///   before it fails with [`ErrorKind::TimedOut`]. `None` waits for as long
+///   as it takes.
+///
+/// # Quick Start
+/// ```rust,no_run
+/// use msq::{MSQClientBlock, UdpTransport, Region, Filter};
+/// use std::io::Result;
+///
+/// fn main() -> Result<()> {
+///     let mut client = MSQClientBlock::with_transport(UdpTransport::bind("0.0.0.0:0")?);
+///     client.connect("hl2master.steampowered.com:27011")?;
+///     let servers = client.query(Region::Europe, Filter::new().appid(240))?;
+///     Ok(())
+/// }
+/// ```
+///
+pub trait Transport: Send {
+    /// Connect the transport to the given address/hostname
+    fn connect(&mut self, addr: &str) -> Result<()>;
+
+    /// Send a datagram to the connected peer
+    fn send(&mut self, buf: &[u8], deadline: Option<Instant>) -> Result<usize>;
+
+    /// Receive a datagram from the connected peer
+    fn recv(&mut self, buf: &mut [u8], deadline: Option<Instant>) -> Result<usize>;
+
+    /// Returns the local address of the transport
+    fn local_addr(&self) -> Result<SocketAddr>;
+
+    /// Returns the address of the connected peer
+    fn peer_addr(&self) -> Result<SocketAddr>;
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn connect(&mut self, addr: &str) -> Result<()> {
+        (**self).connect(addr)
+    }
+
+    fn send(&mut self, buf: &[u8], deadline: Option<Instant>) -> Result<usize> {
+        (**self).send(buf, deadline)
+    }
+
+    fn recv(&mut self, buf: &mut [u8], deadline: Option<Instant>) -> Result<usize> {
+        (**self).recv(buf, deadline)
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        (**self).local_addr()
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        (**self).peer_addr()
+    }
+}
+
+/// The time left until the deadline, or an error once it has passed
+pub(crate) fn time_left(deadline: Instant) -> Result<std::time::Duration> {
+    match deadline.checked_duration_since(Instant::now()) {
+        Some(left) if !left.is_zero() => Ok(left),
+        _ => Err(Error::new(ErrorKind::TimedOut, "Deadline has passed")),
+    }
+}
+
+/// [`Transport`] over a [`std`] UDP socket
+pub struct UdpTransport {
+    sock: UdpSocket,
+}
+
+impl UdpTransport {
+    /// Bind a new UDP socket to the given address
+    ///
+    /// # Arguments
+    /// * `addr` - The address to bind to (EX: `0.0.0.0:0`)
+    pub fn bind(addr: &str) -> Result<UdpTransport> {
+        Ok(UdpTransport {
+            sock: UdpSocket::bind(addr)?,
+        })
+    }
+
+    /// Returns the underlying UDP socket
+    pub fn socket(&self) -> &UdpSocket {
+        &self.sock
+    }
+}
+
+impl From<UdpSocket> for UdpTransport {
+    fn from(sock: UdpSocket) -> Self {
+        UdpTransport { sock }
+    }
+}
+
+impl Transport for UdpTransport {
+    fn connect(&mut self, addr: &str) -> Result<()> {
+        let peer = peer_addr(self.sock.local_addr()?, addr.to_socket_addrs()?)?;
+        self.sock.connect(peer)
+    }
+
+    fn send(&mut self, buf: &[u8], deadline: Option<Instant>) -> Result<usize> {
+        self.sock.set_write_timeout(deadline.map(time_left).transpose()?)?;
+        self.sock.send(buf).map_err(timed_out)
+    }
+
+    fn recv(&mut self, buf: &mut [u8], deadline: Option<Instant>) -> Result<usize> {
+        self.sock.set_read_timeout(deadline.map(time_left).transpose()?)?;
+        self.sock.recv(buf).map_err(timed_out)
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        self.sock.local_addr()
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        self.sock.peer_addr()
+    }
+}
+
+// Socket timeouts surface as WouldBlock on unix and TimedOut on windows
+pub(crate) fn timed_out(err: Error) -> Error {
+    match err.kind() {
+        ErrorKind::WouldBlock => Error::new(ErrorKind::TimedOut, err),
+        _ => err,
+    }
+}
+
+/// In-memory [`Transport`], one end of a pair of channels
+///
+/// * Created in pairs with [`pair`](#method.pair): every datagram sent on one
+///   end is received on the other, without any socket.
+/// * [`MockMasterServer::channel`](crate::MockMasterServer::channel) serves a
+///   mock master server on the other end.
+/// * Both ends report the same local and peer address, `0.0.0.0:0` unless
+///   given with [`addrs`](#method.addrs). Connecting is a no-op.
+/// * Once one end is dropped, the other fails with
+///   [`ErrorKind::BrokenPipe`] after receiving what was already sent.
+///
+/// # Quick Start
+/// ```rust
+/// use msq::{ChannelTransport, Transport};
+/// use std::io::Result;
+///
+/// fn main() -> Result<()> {
+///     let (mut client, mut master) = ChannelTransport::pair();
+///     client.send(b"ping", None)?;
+///
+///     let mut buf = [0u8; 16];
+///     let len = master.recv(&mut buf, None)?;
+///     assert_eq!(&buf[..len], b"ping");
+///     Ok(())
+/// }
+/// ```
+///
+pub struct ChannelTransport {
+    pub(crate) tx: Sender<Vec<u8>>,
+    rx: Receiver<Vec<u8>>,
+    local: SocketAddr,
+    peer: SocketAddr,
+}
+
+impl ChannelTransport {
+    /// Create two connected ends
+    pub fn pair() -> (ChannelTransport, ChannelTransport) {
+        let unknown = SocketAddr::from(([0, 0, 0, 0], 0));
+        let (a_tx, b_rx) = mpsc::channel();
+        let (b_tx, a_rx) = mpsc::channel();
+        (
+            ChannelTransport { tx: a_tx, rx: a_rx, local: unknown, peer: unknown },
+            ChannelTransport { tx: b_tx, rx: b_rx, local: unknown, peer: unknown },
+        )
+    }
+
+    /// Set the local and peer address this end reports, such as in
+    /// [`Recorder`](crate::Recorder) captures
+    ///
+    /// # Arguments
+    /// * `local` - The local address of this end
+    /// * `peer` - The address of the other end
+    pub fn addrs(mut self, local: SocketAddr, peer: SocketAddr) -> Self {
+        self.local = local;
+        self.peer = peer;
+        self
+    }
+}
+
+impl Transport for ChannelTransport {
+    fn connect(&mut self, _addr: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn send(&mut self, buf: &[u8], _deadline: Option<Instant>) -> Result<usize> {
+        self.tx
+            .send(buf.to_vec())
+            .map_err(|_| Error::new(ErrorKind::BrokenPipe, "Channel closed"))?;
+        Ok(buf.len())
+    }
+
+    fn recv(&mut self, buf: &mut [u8], deadline: Option<Instant>) -> Result<usize> {
+        let data = match deadline {
+            Some(deadline) => self.rx.recv_timeout(time_left(deadline)?).map_err(|err| match err {
+                RecvTimeoutError::Timeout => Error::new(ErrorKind::TimedOut, "Deadline has passed"),
+                RecvTimeoutError::Disconnected => Error::new(ErrorKind::BrokenPipe, "Channel closed"),
+            })?,
+            None => self
+                .rx
+                .recv()
+                .map_err(|_| Error::new(ErrorKind::BrokenPipe, "Channel closed"))?,
+        };
+        let len = data.len().min(buf.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        Ok(len)
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.local)
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        Ok(self.peer)
+    }
+}
+
+/// A [`Transport`] driven on a thread of its own, so the async client can
+/// wait for it without blocking its runtime
+///
+/// Every call is handed to the thread and completes a future that does not
+/// depend on any runtime. Receiving waits in steps of [`CANCEL_POLL`], so a
+/// call whose future was dropped stops waiting soon after. A datagram that
+/// arrives for such a call is kept for the next one.
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+pub(crate) struct TransportThread {
+    calls: Sender<Call>,
+    local: Result<SocketAddr>,
+    peer: Result<SocketAddr>,
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+enum Call {
+    Connect(String, Reply<(SocketAddr, SocketAddr)>),
+    Send(Vec<u8>, Option<Instant>, Reply<usize>),
+    Recv(Option<Instant>, Reply<Vec<u8>>),
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+impl TransportThread {
+    pub fn spawn(mut transport: Box<dyn Transport>) -> TransportThread {
+        let local = transport.local_addr();
+        let peer = transport.peer_addr();
+        let (calls, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut kept: VecDeque<Vec<u8>> = VecDeque::new();
+            while let Ok(call) = rx.recv() {
+                match call {
+                    Call::Connect(addr, reply) => {
+                        let connected = transport.connect(&addr).and_then(|_| {
+                            Ok((transport.local_addr()?, transport.peer_addr()?))
+                        });
+                        reply.complete(connected);
+                    }
+                    Call::Send(buf, deadline, reply) => reply.complete(transport.send(&buf, deadline)),
+                    Call::Recv(deadline, reply) => match kept.pop_front() {
+                        Some(datagram) => reply.complete(Ok(datagram)),
+                        None => recv_until(&mut *transport, deadline, reply, &mut kept),
+                    },
+                }
+            }
+        });
+        TransportThread { calls, local, peer }
+    }
+
+    pub async fn connect(&mut self, addr: &str) -> Result<()> {
+        let (local, peer) = self.call(|reply| Call::Connect(String::from(addr), reply)).await?;
+        self.local = Ok(local);
+        self.peer = Ok(peer);
+        Ok(())
+    }
+
+    pub async fn send(&self, buf: &[u8], deadline: Option<Instant>) -> Result<usize> {
+        self.call(|reply| Call::Send(buf.to_vec(), deadline, reply)).await
+    }
+
+    pub async fn recv(&self, buf: &mut [u8], deadline: Option<Instant>) -> Result<usize> {
+        let datagram = self.call(|reply| Call::Recv(deadline, reply)).await?;
+        let len = datagram.len().min(buf.len());
+        buf[..len].copy_from_slice(&datagram[..len]);
+        Ok(len)
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        copy_addr(&self.local)
+    }
+
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        copy_addr(&self.peer)
+    }
+
+    fn call<T>(&self, call: impl FnOnce(Reply<T>) -> Call) -> Pending<T> {
+        let slot = Arc::new(Slot::default());
+        // A call the thread is gone for is dropped, completing it
+        let _ = self.calls.send(call(Reply(slot.clone(), false)));
+        Pending { slot }
+    }
+}
+
+// Receive on the transport thread until the deadline, or until the caller
+// is gone
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+fn recv_until(transport: &mut dyn Transport, deadline: Option<Instant>, reply: Reply<Vec<u8>>, kept: &mut VecDeque<Vec<u8>>) {
+    let mut buf: [u8; 2048] = [0x00; 2048];
+    while !reply.abandoned() {
+        let step = Instant::now() + CANCEL_POLL;
+        let wait = deadline.map_or(step, |deadline| deadline.min(step));
+        match transport.recv(&mut buf, Some(wait)) {
+            Ok(len) if reply.abandoned() => kept.push_back(buf[..len].to_vec()),
+            Ok(len) => reply.complete(Ok(buf[..len].to_vec())),
+            // Only the step is over, not a transport that gave up right away
+            Err(e) if e.kind() == ErrorKind::TimedOut
+                && Instant::now() >= wait
+                && deadline.is_none_or(|deadline| Instant::now() < deadline) =>
+            {
+                continue
+            }
+            Err(e) => reply.complete(Err(e)),
+        }
+        return;
+    }
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+fn copy_addr(addr: &Result<SocketAddr>) -> Result<SocketAddr> {
+    match addr {
+        Ok(addr) => Ok(*addr),
+        Err(e) => Err(Error::new(e.kind(), e.to_string())),
+    }
+}
+
+// The result of a call to the transport thread, and the task waiting for it
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+struct Slot<T> {
+    state: Mutex<SlotState<T>>,
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+struct SlotState<T> {
+    result: Option<Result<T>>,
+    waker: Option<Waker>,
+    abandoned: bool,
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+impl<T> Default for Slot<T> {
+    fn default() -> Self {
+        Slot {
+            state: Mutex::new(SlotState { result: None, waker: None, abandoned: false }),
+        }
+    }
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+impl<T> Slot<T> {
+    fn complete(&self, result: Result<T>) {
+        let mut state = self.state.lock().unwrap();
+        state.result = Some(result);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+// The transport thread's end of a call, failing the call if dropped without
+// a result
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+struct Reply<T>(Arc<Slot<T>>, bool);
+
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+impl<T> Reply<T> {
+    fn complete(mut self, result: Result<T>) {
+        self.0.complete(result);
+        self.1 = true;
+    }
+
+    fn abandoned(&self) -> bool {
+        self.0.state.lock().unwrap().abandoned
+    }
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+impl<T> Drop for Reply<T> {
+    fn drop(&mut self) {
+        if !self.1 {
+            self.0.complete(Err(Error::new(ErrorKind::BrokenPipe, "Transport thread stopped")));
+        }
+    }
+}
+
+// Future of a call to the transport thread, abandoning the call when dropped
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+struct Pending<T> {
+    slot: Arc<Slot<T>>,
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+impl<T> Future for Pending<T> {
+    type Output = Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<T>> {
+        let mut state = self.slot.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+impl<T> Drop for Pending<T> {
+    fn drop(&mut self) {
+        self.slot.state.lock().unwrap().abandoned = true;
+    }
+}
@@ -0,0 +1,345 @@
+use crate::a2s_client_async::A2SClient;
+use crate::a2s_packet::ServerInfo;
+use crate::client_async::MSQClient;
+use crate::filter::Filter;
+use crate::region::Region;
+
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
+use std::hash::BuildHasher;
+use std::io::{Error, ErrorKind, Result};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::{JoinHandle, JoinSet};
+
+/// An event emitted by a [`Watcher`]
+#[derive(Debug)]
+pub enum WatchEvent {
+    /// A server appeared in the query results, with its A2S info if probed
+    ServerAdded {
+        addr: String,
+        info: Option<ServerInfo>,
+    },
+    /// A server is no longer in the query results
+    ServerRemoved { addr: String },
+    /// A probed server changed maps
+    MapChanged {
+        addr: String,
+        old: String,
+        new: String,
+    },
+    /// A probed server has a different amount of players
+    PlayersChanged { addr: String, old: u8, new: u8 },
+    /// The master server query failed, the next poll is in `retry_in`
+    QueryFailed { error: Error, retry_in: Duration },
+}
+
+/// Watcher - Polls a master server query and emits change events
+///
+/// * Requires feature: `async` (Turned **on** by default)
+/// * Runs the query on an [`interval`](#method.interval) with a random
+///   [`jitter`](#method.jitter) added, and optionally probes every server
+///   with A2S_INFO to detect map and player changes.
+/// * Every server of the first poll is emitted as a
+///   [`WatchEvent::ServerAdded`].
+/// * Failed queries back off exponentially, doubling the wait up to the
+///   [`max_backoff`](#method.max_backoff), so a rate limiting master server
+///   is not hammered. The interval can't go below 1 second.
+///
+/// # Quick Start
+/// ```rust,no_run
+/// use msq::{Watcher, WatchEvent, Region, Filter};
+/// use std::time::Duration;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut watch = Watcher::new("hl2master.steampowered.com:27011", Region::Europe, Filter::new().appid(240))
+///         .interval(Duration::from_secs(120))
+///         .probe(true)
+///         .start();
+///
+///     while let Some(event) = watch.recv().await {
+///         match event {
+///             WatchEvent::ServerAdded { addr, .. } => println!("+ {}", addr),
+///             WatchEvent::ServerRemoved { addr } => println!("- {}", addr),
+///             WatchEvent::MapChanged { addr, new, .. } => println!("{} is now on {}", addr, new),
+///             _ => (),
+///         }
+///     }
+/// }
+/// ```
+///
+pub struct Watcher {
+    master: String,
+    region: Region,
+    filter: String,
+    interval: Duration,
+    jitter: Duration,
+    max_backoff: Duration,
+    timeout: Duration,
+    max_servers: usize,
+    probe: bool,
+    probe_timeout: Duration,
+    concurrency: usize,
+}
+
+/// Handle of a running [`Watcher`], receives its events
+///
+/// The watcher stops when the handle is dropped.
+pub struct WatchHandle {
+    events: mpsc::Receiver<WatchEvent>,
+    task: JoinHandle<()>,
+}
+
+impl Watcher {
+    /// Returns a new Watcher
+    ///
+    /// # Arguments
+    /// * `master` - The master server's address (EX: `hl2master.steampowered.com:27011`)
+    /// * `region` - [`Region`] to query
+    /// * `filter` - [`Filter`] of the query
+    pub fn new(master: &str, region: Region, filter: Filter) -> Watcher {
+        Watcher {
+            master: String::from(master),
+            region,
+            filter: filter.as_string(),
+            interval: Duration::from_secs(60),
+            jitter: Duration::from_secs(5),
+            max_backoff: Duration::from_secs(600),
+            timeout: Duration::from_secs(10),
+            max_servers: 6000,
+            probe: false,
+            probe_timeout: Duration::from_secs(3),
+            concurrency: 32,
+        }
+    }
+
+    /// Set the time between polls (Defaults to 60 seconds, at least 1 second)
+    ///
+    /// # Arguments
+    /// * `interval` - Time between the end of a poll and the next one
+    pub fn interval(mut self, interval: Duration) -> Watcher {
+        self.interval = interval;
+        self
+    }
+
+    /// Set the maximum random delay added to each interval (Defaults to 5 seconds)
+    ///
+    /// # Arguments
+    /// * `jitter` - Upper bound of the random delay
+    pub fn jitter(mut self, jitter: Duration) -> Watcher {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Set the longest wait after failed queries (Defaults to 10 minutes)
+    ///
+    /// # Arguments
+    /// * `max_backoff` - Upper bound of the exponential backoff
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Watcher {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Set the time until a master server query fails (Defaults to 10 seconds)
+    ///
+    /// # Arguments
+    /// * `timeout` - Time until a query fails, including all of its pages
+    pub fn timeout(mut self, timeout: Duration) -> Watcher {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set maximum amount of servers in a query (Defaults to 6000)
+    ///
+    /// # Arguments
+    /// * `max_servers` - Maximum amount of servers in a query
+    pub fn max_servers(mut self, max_servers: usize) -> Watcher {
+        self.max_servers = max_servers;
+        self
+    }
+
+    /// Probe every server with A2S_INFO on each poll (Defaults to `false`)
+    ///
+    /// Servers that fail a probe keep the info of their last successful probe.
+    ///
+    /// # Arguments
+    /// * `probe` - Whether to probe the servers
+    pub fn probe(mut self, probe: bool) -> Watcher {
+        self.probe = probe;
+        self
+    }
+
+    /// Set the time until a probe fails (Defaults to 3 seconds)
+    ///
+    /// # Arguments
+    /// * `timeout` - Time until a probe fails
+    pub fn probe_timeout(mut self, timeout: Duration) -> Watcher {
+        self.probe_timeout = timeout;
+        self
+    }
+
+    /// Set the maximum amount of probes in flight (Defaults to 32)
+    ///
+    /// # Arguments
+    /// * `concurrency` - Maximum amount of probes in flight
+    pub fn concurrency(mut self, concurrency: usize) -> Watcher {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Start polling in a task of the current tokio runtime
+    pub fn start(self) -> WatchHandle {
+        let (sender, events) = mpsc::channel(1024);
+        let task = tokio::spawn(self.run(sender));
+        WatchHandle { events, task }
+    }
+
+    async fn run(self, sender: mpsc::Sender<WatchEvent>) {
+        let hasher = RandomState::new();
+        let interval = self.interval.max(Duration::from_secs(1));
+        let mut known: Option<BTreeMap<String, Option<ServerInfo>>> = None;
+        let mut failures: u32 = 0;
+        loop {
+            let wait = match self.poll().await {
+                Ok(servers) => {
+                    failures = 0;
+                    let servers = match self.probe {
+                        true => self.probe_all(servers, known.as_ref()).await,
+                        false => servers.into_iter().map(|addr| (addr, None)).collect(),
+                    };
+                    for event in events(known.as_ref(), &servers) {
+                        if sender.send(event).await.is_err() {
+                            return;
+                        }
+                    }
+                    known = Some(servers);
+                    interval
+                }
+                Err(error) => {
+                    failures = failures.saturating_add(1);
+                    let retry_in = interval
+                        .saturating_mul(2u32.saturating_pow(failures.min(16)))
+                        .min(self.max_backoff.max(interval));
+                    if sender.send(WatchEvent::QueryFailed { error, retry_in }).await.is_err() {
+                        return;
+                    }
+                    retry_in
+                }
+            };
+
+            let roll = hasher.hash_one(Instant::now()) as f64 / u64::MAX as f64;
+            tokio::select! {
+                _ = tokio::time::sleep(wait + self.jitter.mul_f64(roll)) => (),
+                _ = sender.closed() => return,
+            }
+        }
+    }
+
+    async fn poll(&self) -> Result<Vec<String>> {
+        tokio::time::timeout(self.timeout, async {
+            let mut client = MSQClient::new().await?;
+            client.connect(&self.master).await?;
+            client.max_servers_on_query(self.max_servers);
+            client.query_raw(self.region.as_u8(), &self.filter).await
+        })
+        .await
+        .map_err(|_| Error::new(ErrorKind::TimedOut, "Master server query timed out"))?
+    }
+
+    async fn probe_all(
+        &self,
+        servers: Vec<String>,
+        known: Option<&BTreeMap<String, Option<ServerInfo>>>,
+    ) -> BTreeMap<String, Option<ServerInfo>> {
+        let mut results = BTreeMap::new();
+        let mut tasks = JoinSet::new();
+        for addr in servers {
+            while tasks.len() >= self.concurrency.max(1) {
+                collect(&mut tasks, &mut results, known).await;
+            }
+            let timeout = self.probe_timeout;
+            tasks.spawn(async move {
+                let info = async {
+                    let mut client = A2SClient::new().await?;
+                    client.timeout(timeout);
+                    client.info(&addr).await
+                };
+                (addr.clone(), info.await)
+            });
+        }
+        while !tasks.is_empty() {
+            collect(&mut tasks, &mut results, known).await;
+        }
+        results
+    }
+}
+
+impl WatchHandle {
+    /// Receive the next event, returns `None` once the watcher stopped
+    pub async fn recv(&mut self) -> Option<WatchEvent> {
+        self.events.recv().await
+    }
+
+    /// Stop the watcher
+    pub fn stop(self) {}
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn collect(
+    tasks: &mut JoinSet<(String, Result<ServerInfo>)>,
+    results: &mut BTreeMap<String, Option<ServerInfo>>,
+    known: Option<&BTreeMap<String, Option<ServerInfo>>>,
+) {
+    if let Some(Ok((addr, info))) = tasks.join_next().await {
+        // Keep the last known info of servers that failed the probe
+        let info = info
+            .ok()
+            .or_else(|| known.and_then(|k| k.get(&addr).cloned().flatten()));
+        results.insert(addr, info);
+    }
+}
+
+// Events between the previous poll and the current one
+fn events(
+    known: Option<&BTreeMap<String, Option<ServerInfo>>>,
+    servers: &BTreeMap<String, Option<ServerInfo>>,
+) -> Vec<WatchEvent> {
+    let mut events = vec![];
+    let empty = BTreeMap::new();
+    let known = known.unwrap_or(&empty);
+    for (addr, info) in servers {
+        let Some(old) = known.get(addr) else {
+            events.push(WatchEvent::ServerAdded {
+                addr: addr.clone(),
+                info: info.clone(),
+            });
+            continue;
+        };
+        if let (Some(old), Some(new)) = (old, info) {
+            if old.map != new.map {
+                events.push(WatchEvent::MapChanged {
+                    addr: addr.clone(),
+                    old: old.map.clone(),
+                    new: new.map.clone(),
+                });
+            }
+            if old.players != new.players {
+                events.push(WatchEvent::PlayersChanged {
+                    addr: addr.clone(),
+                    old: old.players,
+                    new: new.players,
+                });
+            }
+        }
+    }
+    for addr in known.keys().filter(|addr| !servers.contains_key(*addr)) {
+        events.push(WatchEvent::ServerRemoved { addr: addr.clone() });
+    }
+    events
+}
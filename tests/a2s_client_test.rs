@@ -0,0 +1,120 @@
+#[cfg(any(feature = "async", feature = "non-async"))]
+use msq::{A2SResponder, ServerAttrs};
+#[cfg(feature = "async")]
+use msq::A2SClient;
+#[cfg(feature = "non-async")]
+use msq::A2SClientBlock;
+#[cfg(any(feature = "async", feature = "non-async"))]
+use std::io::{ErrorKind, Result};
+#[cfg(any(feature = "async", feature = "non-async"))]
+use std::time::Duration;
+
+#[cfg(any(feature = "async", feature = "non-async"))]
+fn responder() -> A2SResponder {
+    let attrs = ServerAttrs::new()
+        .appid(240)
+        .name("Test Server")
+        .map("de_dust2")
+        .gamedir("cstrike")
+        .version("1.0.0.1")
+        .players(2, 24)
+        .dedicated(true)
+        .secure(true)
+        .gametype(&["alltalk", "friendlyfire"]);
+    let mut responder = A2SResponder::new(attrs)
+        .game("Counter-Strike: Source")
+        .player("alice", 12, Duration::from_secs(600))
+        .player("bob", 3, Duration::from_secs(60));
+    // Enough rules for a split reply
+    for i in 0..200 {
+        responder = responder.rule(&format!("sv_rule_{}", i), &i.to_string());
+    }
+    responder
+}
+
+#[test]
+#[cfg(feature = "non-async")]
+fn test_a2s_client_block() -> Result<()> {
+    let handle = responder().start()?;
+    let addr = handle.local_addr().to_string();
+    let mut client = A2SClientBlock::new()?;
+
+    let info = client.info(&addr)?;
+    assert_eq!(info.name, "Test Server");
+    assert_eq!(info.map, "de_dust2");
+    assert_eq!(info.folder, "cstrike");
+    assert_eq!(info.game, "Counter-Strike: Source");
+    assert_eq!(info.version, "1.0.0.1");
+    assert_eq!(info.appid, 240);
+    assert_eq!((info.players, info.max_players, info.bots), (2, 24, 0));
+    assert!(info.dedicated && info.secure && !info.linux && !info.password);
+    assert_eq!(info.port, Some(handle.local_addr().port()));
+    assert_eq!(info.keywords, vec!["alltalk", "friendlyfire"]);
+
+    let players = client.players(&addr)?;
+    assert_eq!(players.len(), 2);
+    assert_eq!(players[0].name, "alice");
+    assert_eq!(players[0].score, 12);
+    assert_eq!(players[0].duration, Duration::from_secs(600));
+
+    let rules = client.rules(&addr)?;
+    assert_eq!(rules.len(), 200);
+    assert_eq!(rules[199].name, "sv_rule_199");
+    assert_eq!(rules[199].value, "199");
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "non-async")]
+fn test_a2s_client_block_timeout() -> Result<()> {
+    let handle = responder().loss(1.0).start()?;
+    let mut client = A2SClientBlock::new()?;
+    client.timeout(Duration::from_millis(200));
+    let err = client.info(&handle.local_addr().to_string()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TimedOut);
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "non-async")]
+fn test_a2s_client_block_split_range() -> Result<()> {
+    // Server answering with a part numbered past the total of its split reply
+    let server = std::net::UdpSocket::bind("127.0.0.1:0")?;
+    let addr = server.local_addr()?.to_string();
+    std::thread::spawn(move || -> Result<()> {
+        let mut buf = [0u8; 1400];
+        let (_, client) = server.recv_from(&mut buf)?;
+        for number in [0u8, 5] {
+            let mut part = vec![0xFE, 0xFF, 0xFF, 0xFF, 0x01, 0x00, 0x00, 0x00, 2, number, 0xE0, 0x04];
+            part.extend([0xFF, 0xFF, 0xFF, 0xFF, 0x49]);
+            server.send_to(&part, client)?;
+        }
+        Ok(())
+    });
+    let mut client = A2SClientBlock::new()?;
+    client.timeout(Duration::from_millis(200));
+    let err = client.info(&addr).unwrap_err();
+    assert!(err.to_string().contains("out of range"));
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+#[tokio::main]
+#[test]
+async fn test_a2s_client_async() -> Result<()> {
+    let handle = responder().challenge(false).latency(Duration::from_millis(50)).start()?;
+    let addr = handle.local_addr().to_string();
+    let mut client = A2SClient::new().await?;
+
+    let info = client.info(&addr).await?;
+    assert_eq!(info.name, "Test Server");
+    assert!(info.ping >= Duration::from_millis(50));
+    assert_eq!(client.players(&addr).await?.len(), 2);
+    assert_eq!(client.rules(&addr).await?.len(), 200);
+
+    let lossy = responder().loss(1.0).start()?;
+    client.timeout(Duration::from_millis(200));
+    let err = client.info(&lossy.local_addr().to_string()).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TimedOut);
+    Ok(())
+}
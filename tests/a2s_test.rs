@@ -0,0 +1,150 @@
+#[cfg(feature = "server")]
+use msq::{A2SResponder, ServerAttrs};
+#[cfg(feature = "server")]
+use std::io::Result;
+#[cfg(feature = "server")]
+use std::net::UdpSocket;
+#[cfg(feature = "server")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "server")]
+fn socket(addr: &str) -> Result<UdpSocket> {
+    let sock = UdpSocket::bind("127.0.0.1:0")?;
+    sock.set_read_timeout(Some(Duration::from_millis(300)))?;
+    sock.connect(addr)?;
+    Ok(sock)
+}
+
+// Send a request and return the reply, reassembling split packets
+#[cfg(feature = "server")]
+fn request(sock: &UdpSocket, packet: &[u8]) -> Result<Vec<u8>> {
+    sock.send(packet)?;
+    let mut buf = [0u8; 2048];
+    let len = sock.recv(&mut buf)?;
+    if buf[..4] != [0xFE, 0xFF, 0xFF, 0xFF] {
+        return Ok(buf[..len].to_vec());
+    }
+
+    let total = buf[8] as usize;
+    let mut parts = vec![vec![]; total];
+    parts[buf[9] as usize] = buf[12..len].to_vec();
+    for _ in 1..total {
+        let len = sock.recv(&mut buf)?;
+        parts[buf[9] as usize] = buf[12..len].to_vec();
+    }
+    Ok(parts.concat())
+}
+
+#[cfg(feature = "server")]
+fn with_challenge(sock: &UdpSocket, packet: &[u8]) -> Result<Vec<u8>> {
+    let mut first = packet.to_vec();
+    first.extend([0xFF, 0xFF, 0xFF, 0xFF]);
+    let challenge = request(sock, &first)?;
+    assert_eq!(&challenge[..5], &[0xFF, 0xFF, 0xFF, 0xFF, 0x41]);
+
+    let mut second = packet.to_vec();
+    second.extend(&challenge[5..9]);
+    request(sock, &second)
+}
+
+#[cfg(feature = "server")]
+const INFO: &[u8] = b"\xFF\xFF\xFF\xFFTSource Engine Query\0";
+
+#[test]
+#[cfg(feature = "server")]
+fn test_a2s_info() -> Result<()> {
+    let attrs = ServerAttrs::new()
+        .appid(240)
+        .name("Test Server")
+        .map("de_dust2")
+        .gamedir("cstrike")
+        .players(3, 24)
+        .dedicated(true)
+        .linux(true)
+        .gametype(&["alltalk"]);
+    let handle = A2SResponder::new(attrs).game("Counter-Strike: Source").start()?;
+    let sock = socket(&handle.local_addr().to_string())?;
+
+    // A challenge is required
+    let reply = request(&sock, INFO)?;
+    assert_eq!(reply[4], 0x41);
+
+    let reply = with_challenge(&sock, INFO)?;
+    let mut expected = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x49, 17];
+    expected.extend(b"Test Server\0de_dust2\0cstrike\0Counter-Strike: Source\0");
+    expected.extend([240, 0, 3, 24, 0, b'd', b'l', 0, 0]);
+    expected.extend(b"\0");
+    expected.extend([0xA1]);
+    expected.extend(handle.local_addr().port().to_le_bytes());
+    expected.extend(b"alltalk\0");
+    expected.extend(240u64.to_le_bytes());
+    assert_eq!(reply, expected);
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "server")]
+fn test_a2s_player() -> Result<()> {
+    let handle = A2SResponder::new(ServerAttrs::new())
+        .player("alice", 12, Duration::from_secs(600))
+        .player("bob", -1, Duration::from_secs(30))
+        .start()?;
+    let sock = socket(&handle.local_addr().to_string())?;
+
+    let reply = with_challenge(&sock, &[0xFF, 0xFF, 0xFF, 0xFF, 0x55])?;
+    let mut expected = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x44, 2, 0];
+    expected.extend(b"alice\0");
+    expected.extend(12i32.to_le_bytes());
+    expected.extend(600f32.to_le_bytes());
+    expected.push(1);
+    expected.extend(b"bob\0");
+    expected.extend((-1i32).to_le_bytes());
+    expected.extend(30f32.to_le_bytes());
+    assert_eq!(reply, expected);
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "server")]
+fn test_a2s_rules_split() -> Result<()> {
+    let mut responder = A2SResponder::new(ServerAttrs::new()).challenge(false);
+    for i in 0..200 {
+        responder = responder.rule(&format!("sv_rule_{}", i), &i.to_string());
+    }
+    let handle = responder.start()?;
+    let sock = socket(&handle.local_addr().to_string())?;
+
+    // Without challenges, any challenge is accepted
+    let reply = request(&sock, &[0xFF, 0xFF, 0xFF, 0xFF, 0x56, 0xFF, 0xFF, 0xFF, 0xFF])?;
+    assert!(reply.len() > 1400);
+    assert_eq!(&reply[..7], &[0xFF, 0xFF, 0xFF, 0xFF, 0x45, 200, 0]);
+    assert!(reply.ends_with(b"sv_rule_199\x00199\x00"));
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "server")]
+fn test_a2s_faults() -> Result<()> {
+    let lossy = A2SResponder::new(ServerAttrs::new()).challenge(false).loss(1.0).start()?;
+    let sock = socket(&lossy.local_addr().to_string())?;
+    assert!(request(&sock, INFO).is_err());
+
+    let malformed = A2SResponder::new(ServerAttrs::new().name("Test Server"))
+        .challenge(false)
+        .malformed(1.0)
+        .start()?;
+    let sock = socket(&malformed.local_addr().to_string())?;
+    let reply = request(&sock, INFO)?;
+    assert_eq!(reply[4], 0x49);
+    assert!(!reply.ends_with(&0u64.to_le_bytes()));
+
+    let slow = A2SResponder::new(ServerAttrs::new())
+        .challenge(false)
+        .latency(Duration::from_millis(100))
+        .start()?;
+    let sock = socket(&slow.local_addr().to_string())?;
+    let start = Instant::now();
+    request(&sock, INFO)?;
+    assert!(start.elapsed() >= Duration::from_millis(100));
+    Ok(())
+}
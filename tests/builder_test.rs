@@ -0,0 +1,179 @@
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol"))]
+use msq::RetryPolicy;
+#[cfg(any(feature = "async", feature = "non-async"))]
+use msq::{Filter, MockFault, MockMasterServer, QueryEvent, Region};
+#[cfg(feature = "non-async")]
+use msq::MSQClientBuilder;
+#[cfg(feature = "async")]
+use msq::MSQClient;
+#[cfg(any(feature = "async", feature = "non-async"))]
+use std::io::Result;
+#[cfg(any(feature = "async", feature = "non-async"))]
+use std::sync::{Arc, Mutex};
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol"))]
+use std::time::Duration;
+#[cfg(feature = "non-async")]
+use std::time::Instant;
+
+// Hook collecting every event of a client
+#[cfg(any(feature = "async", feature = "non-async"))]
+fn events() -> (Arc<Mutex<Vec<QueryEvent>>>, impl Fn(&QueryEvent) + Send + Sync + 'static) {
+    let events = Arc::new(Mutex::new(vec![]));
+    let sink = events.clone();
+    (events, move |event: &QueryEvent| sink.lock().unwrap().push(event.clone()))
+}
+
+#[test]
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol"))]
+fn test_retry_policy() {
+    let policy = RetryPolicy::new(3).backoff(Duration::from_millis(100));
+    assert_eq!(policy.attempts(), 3);
+    assert_eq!(policy.delay(1), Duration::from_millis(100));
+    assert_eq!(policy.delay(2), Duration::from_millis(200));
+    assert_eq!(policy.delay(3), Duration::from_millis(400));
+    assert_eq!(RetryPolicy::default().delay(1), Duration::ZERO);
+}
+
+#[test]
+#[cfg(feature = "non-async")]
+fn test_builder_block() -> Result<()> {
+    let mock = MockMasterServer::new()
+        .servers(&["10.0.0.1:27015", "10.0.0.2:27015", "10.0.0.3:27015"])
+        .start()?;
+    let (events, hook) = events();
+    let mut client = MSQClientBuilder::new()
+        .master(&mock.local_addr().to_string())
+        .max_servers(2)
+        .on_event(hook)
+        .build_block()?;
+    let servers = client.query(Region::All, Filter::new())?;
+    assert_eq!(servers, vec!["10.0.0.1:27015", "10.0.0.2:27015"]);
+
+    let master = mock.local_addr().to_string();
+    let events = events.lock().unwrap();
+    assert_eq!(
+        events[0],
+        QueryEvent::Request { master: master.clone(), region_code: 0xFF, seed: "0.0.0.0:0".to_string() }
+    );
+    assert_eq!(events[1], QueryEvent::Reply { master, servers: 4 });
+
+    // Without a master server to connect to
+    let err = MSQClientBuilder::new().master("not a master server").build_block();
+    assert!(err.is_err());
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "non-async")]
+fn test_builder_retry() -> Result<()> {
+    let mock = MockMasterServer::new()
+        .server("10.0.0.1:27015")
+        .fault(0, MockFault::Drop)
+        .start()?;
+    let (events, hook) = events();
+    let mut client = MSQClientBuilder::new()
+        .master(&mock.local_addr().to_string())
+        .timeout(Duration::from_millis(200))
+        .retry(RetryPolicy::new(2).backoff(Duration::from_millis(10)))
+        .on_event(hook)
+        .build_block()?;
+    assert_eq!(client.query(Region::All, Filter::new())?, vec!["10.0.0.1:27015"]);
+    assert_eq!(mock.requests().len(), 2);
+    assert!(events.lock().unwrap().iter().any(|event| matches!(event, QueryEvent::Retry { attempt: 1, .. })));
+
+    // Timing out without retries
+    let mock = MockMasterServer::new().fault_all(MockFault::Drop).start()?;
+    let mut client = MSQClientBuilder::new()
+        .master(&mock.local_addr().to_string())
+        .timeout(Duration::from_millis(100))
+        .build_block()?;
+    let err = client.query(Region::All, Filter::new()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+
+    // Duplicated replies are only counted once
+    let mock = MockMasterServer::new()
+        .servers(&["10.0.0.1:27015", "10.0.0.2:27015"])
+        .fault_all(MockFault::Duplicate)
+        .start()?;
+    let mut client = MSQClientBuilder::new()
+        .master(&mock.local_addr().to_string())
+        .timeout(Duration::from_millis(200))
+        .build_block()?;
+    assert_eq!(client.query(Region::All, Filter::new())?.len(), 2);
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "non-async")]
+fn test_builder_failover() -> Result<()> {
+    // Bound but silent master server
+    let dead = std::net::UdpSocket::bind("127.0.0.1:0")?;
+    let mock = MockMasterServer::new().server("10.0.0.1:27015").start()?;
+    let (events, hook) = events();
+    let mut client = MSQClientBuilder::new()
+        .masters(&[&dead.local_addr()?.to_string(), &mock.local_addr().to_string()])
+        .timeout(Duration::from_millis(100))
+        .on_event(hook)
+        .build_block()?;
+    assert_eq!(client.query(Region::All, Filter::new())?, vec!["10.0.0.1:27015"]);
+    assert!(events.lock().unwrap().contains(&QueryEvent::Failover {
+        from: dead.local_addr()?.to_string(),
+        to: mock.local_addr().to_string(),
+    }));
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "non-async")]
+fn test_builder_rate_limit() -> Result<()> {
+    let mock = MockMasterServer::new().server("10.0.0.1:27015").start()?;
+    let mut client = MSQClientBuilder::new()
+        .master(&mock.local_addr().to_string())
+        .rate_limit(Duration::from_millis(100))
+        .build_block()?;
+    let start = Instant::now();
+    for _ in 0..3 {
+        client.query(Region::All, Filter::new())?;
+    }
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "non-async")]
+fn test_builder_transport() -> Result<()> {
+    let transport = MockMasterServer::new().server("10.0.0.1:27015").channel()?;
+    let mut client = MSQClientBuilder::new()
+        .transport(transport)
+        .timeout(Duration::from_millis(200))
+        .build_block()?;
+    assert_eq!(client.query(Region::All, Filter::new())?, vec!["10.0.0.1:27015"]);
+    Ok(())
+}
+
+#[tokio::main]
+#[test]
+#[cfg(feature = "async")]
+async fn test_builder_async() -> Result<()> {
+    let dead = std::net::UdpSocket::bind("127.0.0.1:0")?;
+    let mock = MockMasterServer::new()
+        .server("10.0.0.1:27015")
+        .fault(0, MockFault::Drop)
+        .start()?;
+    let (events, hook) = events();
+    let mut client: MSQClient = msq::MSQClientBuilder::new()
+        .masters(&[&dead.local_addr()?.to_string(), &mock.local_addr().to_string()])
+        .timeout(Duration::from_millis(100))
+        .retry(RetryPolicy::new(1))
+        .on_event(hook)
+        .build()
+        .await?;
+    assert_eq!(client.query(Region::All, Filter::new()).await?, vec!["10.0.0.1:27015"]);
+
+    let events = events.lock().unwrap();
+    assert!(events.iter().any(|event| matches!(event, QueryEvent::Failover { .. })));
+    // The dead master server is retried once, then the mock drops the first reply
+    let retries = events.iter().filter(|event| matches!(event, QueryEvent::Retry { .. })).count();
+    assert_eq!(retries, 2);
+    Ok(())
+}
@@ -0,0 +1,223 @@
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol"))]
+use msq::CancelToken;
+#[cfg(any(feature = "async", feature = "non-async"))]
+use msq::{Filter, MockFault, MockMasterServer, PartialQuery, Region};
+#[cfg(feature = "async")]
+use msq::{MSQClient, ShardStrategy};
+#[cfg(feature = "non-async")]
+use msq::{CaptureFormat, MSQClientBlock, Recorder};
+#[cfg(any(feature = "async", feature = "non-async"))]
+use std::io::{ErrorKind, Result};
+#[cfg(any(feature = "async", feature = "non-async"))]
+use std::time::{Duration, Instant};
+
+// Two reply batches, 231 servers and 69 servers with the end of the list
+#[cfg(any(feature = "async", feature = "non-async"))]
+fn servers() -> Vec<String> {
+    (0..300).map(|i| format!("10.0.{}.{}:27015", i / 256, i % 256)).collect()
+}
+
+// Mock master server taking its time with the second batch
+#[cfg(any(feature = "async", feature = "non-async"))]
+fn slow_mock(delay: Duration) -> MockMasterServer {
+    let servers = servers();
+    let addrs: Vec<&str> = servers.iter().map(|addr| addr.as_str()).collect();
+    MockMasterServer::new()
+        .servers(&addrs)
+        .fault(1, MockFault::Delay(delay))
+}
+
+#[test]
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol"))]
+fn test_cancel_token() {
+    let cancel = CancelToken::new();
+    let other = cancel.clone();
+    assert!(!cancel.is_cancelled());
+    other.cancel();
+    assert!(cancel.is_cancelled());
+}
+
+#[test]
+#[cfg(feature = "non-async")]
+fn test_deadline_block() -> Result<()> {
+    let mock = slow_mock(Duration::from_millis(300)).start()?;
+    let mut client = MSQClientBlock::new()?;
+    client.connect(&mock.local_addr().to_string())?;
+    client.max_servers_on_query(1000);
+
+    let deadline = Instant::now() + Duration::from_millis(100);
+    let err = client.query_with_deadline(Region::All, Filter::new(), deadline).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TimedOut);
+    let partial = PartialQuery::from_error(&err).unwrap();
+    assert_eq!(partial.pages, 1);
+    assert_eq!(partial.servers, servers()[..231]);
+
+    // The late batch is discarded instead of starting the next query
+    std::thread::sleep(Duration::from_millis(300));
+    assert_eq!(client.query(Region::All, Filter::new())?, servers());
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "non-async")]
+fn test_deadline_block_late_reply() -> Result<()> {
+    // The first batch of the next query comes after the late batch
+    let mock = slow_mock(Duration::from_millis(300))
+        .fault(2, MockFault::Delay(Duration::from_millis(300)))
+        .start()?;
+    let mut client = MSQClientBlock::new()?;
+    client.connect(&mock.local_addr().to_string())?;
+    client.max_servers_on_query(1000);
+
+    let deadline = Instant::now() + Duration::from_millis(100);
+    let err = client.query_with_deadline(Region::All, Filter::new(), deadline).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TimedOut);
+
+    // The late batch arrives while the next query waits for its first one
+    assert_eq!(client.query(Region::All, Filter::new())?, servers());
+    assert_eq!(client.query(Region::All, Filter::new())?, servers());
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "non-async")]
+fn test_deadline_block_from_socket() -> Result<()> {
+    let mock = slow_mock(Duration::from_millis(300)).start()?;
+    let sock = std::net::UdpSocket::bind("127.0.0.1:0")?;
+    let local = sock.local_addr()?.to_string();
+    let path = std::env::temp_dir().join(format!("msq-{}-from-socket.txt", std::process::id()));
+    let mut client = MSQClientBlock::from_socket(sock);
+    client.connect(&mock.local_addr().to_string())?;
+    client.max_servers_on_query(1000);
+    client.record(Recorder::create(&path, CaptureFormat::Text)?);
+
+    let deadline = Instant::now() + Duration::from_millis(100);
+    let err = client.query_with_deadline(Region::All, Filter::new(), deadline).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TimedOut);
+    client.query(Region::All, Filter::new())?;
+    drop(client);
+
+    // The socket given to the client is kept, instead of one on another port
+    let capture = std::fs::read_to_string(&path)?;
+    let sent: Vec<&str> = capture.lines().filter(|line| line.contains(" send ")).collect();
+    assert_eq!(sent.len(), 4);
+    assert!(sent.iter().all(|line| line.split(' ').nth(2) == Some(local.as_str())));
+    std::fs::remove_file(path)
+}
+
+#[test]
+#[cfg(feature = "non-async")]
+fn test_cancel_block() -> Result<()> {
+    let mock = slow_mock(Duration::from_millis(300)).start()?;
+    let mut client = MSQClientBlock::new()?;
+    client.connect(&mock.local_addr().to_string())?;
+    client.max_servers_on_query(1000);
+
+    let cancel = CancelToken::new();
+    let stop = cancel.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        stop.cancel();
+    });
+    let start = Instant::now();
+    let err = client.query_with_cancel(Region::All, Filter::new(), &cancel).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Interrupted);
+    assert!(start.elapsed() < Duration::from_millis(300));
+    assert_eq!(PartialQuery::from_error(&err).unwrap().servers.len(), 231);
+
+    std::thread::sleep(Duration::from_millis(300));
+    assert_eq!(client.query(Region::All, Filter::new())?, servers());
+
+    // Cancelled before it starts
+    let err = client.query_with_cancel(Region::All, Filter::new(), &cancel).unwrap_err();
+    assert_eq!(PartialQuery::from_error(&err).unwrap().pages, 0);
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "non-async")]
+fn test_cancel_transport() -> Result<()> {
+    let transport = slow_mock(Duration::from_millis(300)).channel()?;
+    let mut client = MSQClientBlock::with_transport(transport);
+    client.max_servers_on_query(1000);
+
+    let cancel = CancelToken::new();
+    let stop = cancel.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        stop.cancel();
+    });
+    let err = client.query_with_cancel(Region::All, Filter::new(), &cancel).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Interrupted);
+    Ok(())
+}
+
+#[tokio::main]
+#[test]
+#[cfg(feature = "async")]
+async fn test_cancel_async() -> Result<()> {
+    let mock = slow_mock(Duration::from_millis(300)).start()?;
+    let mut client = MSQClient::new().await?;
+    client.connect(&mock.local_addr().to_string()).await?;
+    client.max_servers_on_query(1000);
+
+    // Dropping the future mid-pagination
+    let query = client.query(Region::All, Filter::new());
+    assert!(tokio::time::timeout(Duration::from_millis(100), query).await.is_err());
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(client.query(Region::All, Filter::new()).await?, servers());
+
+    // The late batch arrives while the next query waits for its first one
+    let mock = slow_mock(Duration::from_millis(300))
+        .fault(2, MockFault::Delay(Duration::from_millis(300)))
+        .start()?;
+    client.connect(&mock.local_addr().to_string()).await?;
+    let query = client.query(Region::All, Filter::new());
+    assert!(tokio::time::timeout(Duration::from_millis(100), query).await.is_err());
+    assert_eq!(client.query(Region::All, Filter::new()).await?, servers());
+    assert_eq!(client.query(Region::All, Filter::new()).await?, servers());
+
+    // Cancelling wakes up the query right away
+    let mock = slow_mock(Duration::from_millis(300)).start()?;
+    client.connect(&mock.local_addr().to_string()).await?;
+    let cancel = CancelToken::new();
+    let start = Instant::now();
+    let (result, _) = tokio::join!(client.query_with_cancel(Region::All, Filter::new(), &cancel), async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        cancel.cancel();
+    });
+    let err = result.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Interrupted);
+    assert!(start.elapsed() < Duration::from_millis(300));
+    assert_eq!(PartialQuery::from_error(&err).unwrap().pages, 1);
+
+    let mock = slow_mock(Duration::from_millis(300)).start()?;
+    client.connect(&mock.local_addr().to_string()).await?;
+    let deadline = Instant::now() + Duration::from_millis(100);
+    let err = client
+        .query_with_deadline(Region::All, Filter::new(), deadline)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TimedOut);
+    assert_eq!(PartialQuery::from_error(&err).unwrap().servers.len(), 231);
+    Ok(())
+}
+
+#[tokio::main]
+#[test]
+#[cfg(feature = "async")]
+async fn test_cancel_async_sharded() -> Result<()> {
+    let mock = slow_mock(Duration::from_millis(300)).start()?;
+    let mut client = MSQClient::new().await?;
+    client.connect(&mock.local_addr().to_string()).await?;
+    client.max_servers_on_query(1000);
+
+    // Dropped during the sub-query of the first region
+    let strategy = ShardStrategy::new().regions().shard_limit(10);
+    let query = client.query_sharded(Region::All, Filter::new(), &strategy);
+    assert!(tokio::time::timeout(Duration::from_millis(100), query).await.is_err());
+
+    // The shard limit does not stay on the client
+    assert_eq!(client.query(Region::All, Filter::new()).await?, servers());
+    Ok(())
+}
@@ -0,0 +1,157 @@
+#[cfg(feature = "non-async")]
+use msq::{CaptureFormat, Filter, MSQClientBlock, MockMasterServer, Recorder, Region, Replay};
+#[cfg(all(feature = "async", feature = "non-async"))]
+use msq::MSQClient;
+#[cfg(feature = "non-async")]
+use std::io::Result;
+#[cfg(feature = "non-async")]
+use std::path::PathBuf;
+
+#[cfg(feature = "non-async")]
+fn capture_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("msq-{}-{}", std::process::id(), name))
+}
+
+#[cfg(feature = "non-async")]
+fn mock_master(count: usize) -> MockMasterServer {
+    let mut mock = MockMasterServer::new();
+    for i in 0..count {
+        mock = mock.server(&format!("10.0.{}.{}:27015", i / 256, i % 256));
+    }
+    mock
+}
+
+// Records a paginated query, then replays it without the mock master
+#[cfg(feature = "non-async")]
+fn record_and_replay(format: CaptureFormat, name: &str) -> Result<()> {
+    let path = capture_path(name);
+    let mock = mock_master(500).start()?;
+    let mut client = MSQClientBlock::new()?;
+    client.connect(&mock.local_addr().to_string())?;
+    client.max_servers_on_query(1000);
+    client.record(Recorder::create(&path, format)?);
+    let servers = client.query(Region::All, Filter::new().appid(240))?;
+    assert_eq!(servers.len(), 500);
+    mock.stop();
+
+    let replay = Replay::open(&path)?;
+    // 3 requests and 3 replies
+    assert_eq!(replay.remaining(), 6);
+    let mut client = MSQClientBlock::new()?;
+    client.max_servers_on_query(1000);
+    client.replay(replay);
+    assert_eq!(client.query(Region::All, Filter::new().appid(240))?, servers);
+
+    // The session is over
+    assert!(client.query(Region::All, Filter::new().appid(240)).is_err());
+    std::fs::remove_file(path)
+}
+
+#[test]
+#[cfg(feature = "non-async")]
+fn test_capture_text() -> Result<()> {
+    record_and_replay(CaptureFormat::Text, "text.txt")
+}
+
+#[test]
+#[cfg(feature = "non-async")]
+fn test_capture_pcap() -> Result<()> {
+    record_and_replay(CaptureFormat::Pcap, "pcap.pcap")
+}
+
+#[test]
+#[cfg(feature = "non-async")]
+fn test_capture_pcap_channel() -> Result<()> {
+    // A ChannelTransport has no addresses, both ends are 0.0.0.0:0
+    let path = capture_path("channel.pcap");
+    let mut client = MSQClientBlock::with_transport(mock_master(500).channel()?);
+    client.max_servers_on_query(1000);
+    client.record(Recorder::create(&path, CaptureFormat::Pcap)?);
+    let servers = client.query(Region::All, Filter::new().appid(240))?;
+    assert_eq!(servers.len(), 500);
+
+    let mut client = MSQClientBlock::with_transport(Replay::open(&path)?);
+    client.max_servers_on_query(1000);
+    assert_eq!(client.query(Region::All, Filter::new().appid(240))?, servers);
+    std::fs::remove_file(path)
+}
+
+#[test]
+#[cfg(feature = "non-async")]
+fn test_capture_text_format() -> Result<()> {
+    let capture = "# msq capture v1\n\
+        1700000000000000 send 0.0.0.0:50000 10.0.0.1:27011 31ff302e302e302e303a30005c61707069645c32343000\n\
+        1700000000100000 recv 0.0.0.0:50000 10.0.0.1:27011 ffffffff660a0a0000016987000000000000\n";
+
+    let mut client = MSQClientBlock::new()?;
+    client.replay(Replay::from_reader(capture.as_bytes())?);
+    let servers = client.query(Region::All, Filter::new().appid(240))?;
+    assert_eq!(servers, vec!["10.0.0.1:27015"]);
+
+    // A different request diverges from the recorded session
+    let mut client = MSQClientBlock::new()?;
+    client.replay(Replay::from_reader(capture.as_bytes())?);
+    assert!(client.query(Region::Europe, Filter::new().appid(240)).is_err());
+    Ok(())
+}
+
+#[test]
+#[cfg(any(feature = "non-async", feature = "tokio", feature = "async-std", feature = "smol"))]
+fn test_capture_pcap_oversized() {
+    let mut capture = vec![];
+    capture.extend(0xA1B2C3D4u32.to_le_bytes()); // Magic number
+    capture.extend(2u16.to_le_bytes()); // Version 2.4
+    capture.extend(4u16.to_le_bytes());
+    capture.extend([0u8; 8]); // Time zone and timestamp accuracy
+    capture.extend(65535u32.to_le_bytes()); // Snapshot length
+    capture.extend(101u32.to_le_bytes()); // LINKTYPE_RAW
+    capture.extend([0u8; 8]); // Timestamp
+    capture.extend(u32::MAX.to_le_bytes()); // Included length
+    capture.extend(u32::MAX.to_le_bytes()); // Original length
+
+    let err = msq::Replay::from_reader(capture.as_slice()).err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[cfg(all(feature = "async", feature = "non-async"))]
+#[tokio::main]
+#[test]
+async fn test_capture_async() -> Result<()> {
+    let path = capture_path("async.txt");
+    let mock = mock_master(10).start()?;
+    let mut client = MSQClient::new().await?;
+    client.connect(&mock.local_addr().to_string()).await?;
+    client.record(Recorder::create(&path, CaptureFormat::Text)?);
+    let results = client
+        .query_regions(&[Region::Europe, Region::All], Filter::new())
+        .await?;
+    mock.stop();
+
+    let mut client = MSQClient::new().await?;
+    client.replay(Replay::open(&path)?);
+    let replayed = client
+        .query_regions(&[Region::Europe, Region::All], Filter::new())
+        .await?;
+    assert_eq!(replayed.counts(), results.counts());
+    assert_eq!(replayed.count(Region::All), Some(10));
+    std::fs::remove_file(path)
+}
+
+#[cfg(all(feature = "async", feature = "non-async"))]
+#[tokio::main]
+#[test]
+async fn test_capture_async_missing_reply() -> Result<()> {
+    // The recorded client did not get a reply
+    let capture = "# msq capture v1\n\
+        1700000000000000 send 0.0.0.0:50000 10.0.0.1:27011 31ff302e302e302e303a30005c61707069645c32343000\n";
+
+    let mut client = MSQClient::new().await?;
+    client.replay(Replay::from_reader(capture.as_bytes())?);
+    let query = client.query(Region::All, Filter::new().appid(240));
+    let err = tokio::time::timeout(std::time::Duration::from_secs(5), query)
+        .await
+        .expect("Replay without a reply did not give up")
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+    Ok(())
+}
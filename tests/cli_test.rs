@@ -0,0 +1,95 @@
+#[cfg(feature = "cli")]
+use msq::{A2SResponder, MockMasterServer, ServerAttrs};
+#[cfg(feature = "cli")]
+use std::io::Result;
+#[cfg(feature = "cli")]
+use std::process::Command;
+
+#[cfg(feature = "cli")]
+fn msq(args: &[&str]) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_msq"))
+        .args(args)
+        .output()
+        .expect("Failed to run msq");
+    (output.status.success(), String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[test]
+#[cfg(feature = "cli")]
+fn test_cli_query() -> Result<()> {
+    let mock = MockMasterServer::new()
+        .servers(&["10.0.0.1:27015", "10.0.0.2:27015"])
+        .start()?;
+    let master = mock.local_addr().to_string();
+
+    let (ok, out) = msq(&["query", "--master", &master, "--region", "eu", "--appid", "240", "--dedicated", "true"]);
+    assert!(ok);
+    assert_eq!(out, "");
+    let (ok, out) = msq(&["query", "--master", &master, "--appid", "240", "--dedicated", "true"]);
+    assert!(ok);
+    assert_eq!(out, "10.0.0.1:27015\n10.0.0.2:27015\n");
+
+    let requests = mock.requests();
+    assert_eq!(requests[1].filter, "\\appid\\240\\dedicated\\1");
+
+    let (ok, out) = msq(&["query", "--master", &master, "--filter", "\\map\\de_dust2", "-f", "json"]);
+    assert!(ok);
+    assert_eq!(out, "[\n  {\"address\": \"10.0.0.1:27015\"},\n  {\"address\": \"10.0.0.2:27015\"}\n]\n");
+    assert_eq!(mock.requests()[2].filter, "\\map\\de_dust2");
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "cli")]
+fn test_cli_probe() -> Result<()> {
+    let server = A2SResponder::new(
+        ServerAttrs::new()
+            .name("Test, Server")
+            .map("de_dust2")
+            .players(3, 24),
+    )
+    .start()?;
+    let addr = server.local_addr().to_string();
+    let mock = MockMasterServer::new().server(&addr).start()?;
+    let master = mock.local_addr().to_string();
+
+    let (ok, out) = msq(&["query", "--master", &master, "--probe", "--format", "csv"]);
+    assert!(ok);
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines[0], "address,name,map,players,bots,ping_ms,error");
+    assert!(lines[1].starts_with(&format!("{},\"Test, Server\",de_dust2,3/24,0,", addr)));
+
+    // An unreachable server fails its probe, but not the command
+    let (ok, out) = msq(&["probe", &addr, "127.0.0.1:9", "--probe-timeout", "200", "--rate", "10"]);
+    assert!(ok);
+    let lines: Vec<&str> = out.lines().collect();
+    assert!(lines[0].starts_with(&format!("{}\tTest, Server\tde_dust2\t3/24\t0\t", addr)));
+    assert!(lines[1].starts_with("127.0.0.1:9\terror: "));
+
+    // A rate past a billion per second does not make the interval zero
+    let (ok, out) = msq(&["probe", &addr, "--rate", "4000000000"]);
+    assert!(ok);
+    assert!(out.starts_with(&format!("{}\tTest, Server\t", addr)));
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "cli")]
+fn test_cli_invalid() {
+    // The raw filter conflicts with the filter flags
+    assert!(!msq(&["query", "--filter", "\\appid\\240", "--appid", "240"]).0);
+    assert!(!msq(&["query", "--region", "mars"]).0);
+}
+
+#[test]
+#[cfg(feature = "tui")]
+fn test_cli_browse_help() {
+    let (ok, out) = msq(&["browse", "--help"]);
+    assert!(ok);
+    for flag in ["--master", "--appid", "--probe-timeout", "--refresh"] {
+        assert!(out.contains(flag), "Missing {}", flag);
+    }
+    // Browsing has no output format
+    let (ok, _) = msq(&["browse", "--format", "json"]);
+    assert!(!ok);
+}
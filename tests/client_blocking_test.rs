@@ -1,30 +1,57 @@
 #[cfg(feature = "non-async")]
-use msq::{MSQClientBlock, Filter, Region};
+use msq::{MSQClientBlock, Filter, MockFault, MockMasterServer, Region, ServerAttrs, ShardStrategy};
 #[cfg(feature = "non-async")]
 use std::io::Result;
+#[cfg(feature = "non-async")]
+use std::time::Duration;
+
+// Seeds a mock master with `count` servers, spread over the 8 regions
+#[cfg(feature = "non-async")]
+fn mock_master(count: usize) -> MockMasterServer {
+    let mut mock = MockMasterServer::new();
+    for i in 0..count {
+        let addr = format!("10.{}.{}.{}:27015", i / 65536, (i / 256) % 256, i % 256);
+        let attrs = ServerAttrs::new()
+            .appid(240)
+            .region(Region::from((i % 8) as u8));
+        mock = mock.server_with(&addr, attrs);
+    }
+    mock
+}
 
 #[cfg(feature = "non-async")]
 #[test]
 fn test_lib_noasync_nt() -> Result<()> {
+    let mock = MockMasterServer::new()
+        .servers(&["216.52.143.114:27015", "216.52.143.114:27016"])
+        .start()?;
     let mut client = MSQClientBlock::new()?;
-    client.connect("hl2master.steampowered.com:27011")?;
+    client.connect(&mock.local_addr().to_string())?;
     let filter = Filter::new().appid(244630).gameaddr("216.52.143.114");
     println!("{}", filter.as_string());
 
     let servers = client.query(Region::All, filter)?;
 
     println!("Servers: {}", servers.len());
-    for server in servers {
+    for server in &servers {
         println!("{}", server);
     }
+    assert_eq!(servers, vec!["216.52.143.114:27015", "216.52.143.114:27016"]);
+
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].region, Region::All);
+    assert_eq!(requests[0].seed, "0.0.0.0:0");
+    assert_eq!(requests[0].filter, "\\appid\\244630\\gameaddr\\216.52.143.114");
     Ok(())
 }
 
 #[cfg(feature = "non-async")]
 #[test]
 fn test_lib_noasync_css() -> Result<()> {
+    let mock = mock_master(4096).start()?;
     let mut client = MSQClientBlock::new()?;
-    client.connect("hl2master.steampowered.com:27011")?;
+    client.connect(&mock.local_addr().to_string())?;
     client.max_servers_on_query(256);
 
     let filter = Filter::new()
@@ -38,35 +65,93 @@ fn test_lib_noasync_css() -> Result<()> {
     let servers = client.query(Region::Europe, filter)?;
 
     let len = servers.len();
-    /*
-    for server in servers {
-        println!("{}", server);
-    }
-    */
     println!("Servers: {}", len);
+    assert_eq!(len, 256);
+    assert_eq!(mock.requests().len(), 2);
     Ok(())
 }
 
 #[cfg(feature = "non-async")]
 #[test]
 fn test_lib_noasync_css_big_query() -> Result<()> {
+    let mock = mock_master(1000).start()?;
     let mut client = MSQClientBlock::new()?;
-    client.connect("hl2master.steampowered.com:27011")?;
+    client.connect(&mock.local_addr().to_string())?;
     client.max_servers_on_query(4096);
     let servers = client.query(Region::All, Filter::new().appid(240))?;
     let len = servers.len();
     println!("Servers: {}", len);
+    assert_eq!(len, 1000);
+
+    // Each following request is seeded with the last address of the previous batch
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 5);
+    assert_eq!(requests[1].seed, servers[230]);
     Ok(())
 }
 
 #[cfg(feature = "non-async")]
 #[test]
 fn test_lib_noasync_css_no_query() -> Result<()> {
+    let mock = mock_master(16).start()?;
     let mut client = MSQClientBlock::new()?;
-    client.connect("hl2master.steampowered.com:27011")?;
+    client.connect(&mock.local_addr().to_string())?;
     client.max_servers_on_query(0);
     let servers = client.query(Region::All, Filter::new().appid(240))?;
     assert_eq!(servers.len(), 0);
     Ok(())
 }
 
+#[cfg(feature = "non-async")]
+#[test]
+fn test_lib_noasync_css_sharded_query() -> Result<()> {
+    // Region::All is truncated at the shard limit, every region is not
+    let mock = mock_master(2000).start()?;
+    let mut client = MSQClientBlock::new()?;
+    client.connect(&mock.local_addr().to_string())?;
+    let strategy = ShardStrategy::new().regions().dedicated().shard_limit(512);
+    let servers = client
+        .query_sharded(Region::All, Filter::new().appid(240), &strategy)
+        ?;
+    let len = servers.len();
+    println!("Servers: {}", len);
+    assert_eq!(len, 2000);
+    Ok(())
+}
+
+#[cfg(feature = "non-async")]
+#[test]
+fn test_lib_noasync_css_query_regions() -> Result<()> {
+    let mock = mock_master(800).start()?;
+    let mut client = MSQClientBlock::new()?;
+    client.connect(&mock.local_addr().to_string())?;
+    client.max_servers_on_query(256);
+    let regions = [Region::Europe, Region::USEast, Region::Asia, Region::All];
+    let results = client
+        .query_regions(&regions, Filter::new().appid(240))
+        ?;
+    assert_eq!(results.counts().len() + results.errors().len(), regions.len());
+    assert_eq!(results.count(Region::Europe), Some(100));
+    assert_eq!(results.count(Region::All), Some(256));
+    // Region::All only adds the servers not already found in the other regions
+    assert_eq!(results.servers().len(), 300 + 256 - 96);
+    println!("Servers: {}", results.servers().len());
+    Ok(())
+}
+
+#[cfg(feature = "non-async")]
+#[test]
+fn test_lib_noasync_mock_faults() -> Result<()> {
+    let mock = mock_master(300)
+        .fault(0, MockFault::Delay(Duration::from_millis(50)))
+        .fault(1, MockFault::Corrupt)
+        .start()?;
+    let mut client = MSQClientBlock::new()?;
+    client.connect(&mock.local_addr().to_string())?;
+    client.max_servers_on_query(4096);
+
+    // First batch is delayed, the second one is corrupted
+    let result = client.query(Region::All, Filter::new());
+    assert!(result.is_err());
+    Ok(())
+}
@@ -0,0 +1,103 @@
+#[cfg(feature = "config")]
+use msq::{Queries, Region};
+#[cfg(all(feature = "config", any(feature = "async", feature = "non-async")))]
+use msq::{MockMasterServer, MockMasterHandle};
+#[cfg(feature = "config")]
+use std::io::{ErrorKind, Result};
+
+#[cfg(feature = "config")]
+const QUERIES: &str = r#"
+[masters.steam]
+address = "hl2master.steampowered.com:27011"
+max_servers = 1000
+
+[queries.tf2_eu]
+region = "eu"
+appid = 440
+empty = false
+tags = "payload,cp"
+
+[queries.css_dust]
+master = "steam"
+region = 1
+filter = '\appid\240\map\de_dust2'
+max_servers = 100
+"#;
+
+#[test]
+#[cfg(feature = "config")]
+fn test_queries_parse() -> Result<()> {
+    let queries: Queries = QUERIES.parse()?;
+    assert_eq!(queries.names().collect::<Vec<&str>>(), vec!["css_dust", "tf2_eu"]);
+    assert_eq!(queries.master("steam").unwrap().max_servers, 1000);
+
+    let tf2 = queries.get("tf2_eu").unwrap();
+    assert_eq!(tf2.master, "hl2master.steampowered.com:27011");
+    assert_eq!(tf2.region, Region::Europe);
+    assert_eq!(tf2.filter, "\\appid\\440\\gametype\\payload,cp\\empty\\1");
+    assert_eq!(tf2.max_servers, 1000);
+
+    let css = queries.get("css_dust").unwrap();
+    assert_eq!(css.region, Region::USWest);
+    assert_eq!(css.filter, "\\appid\\240\\map\\de_dust2");
+    assert_eq!(css.max_servers, 100);
+    assert!(queries.get("l4d2").is_none());
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "config")]
+fn test_queries_validation() {
+    let invalid = [
+        // Unknown key
+        "[queries.a]\nappid = 440\nmaps = \"x\"\n[masters.m]\naddress = \"m:1\"",
+        // Wrong type
+        "[masters.m]\naddress = \"m:1\"\n[queries.a]\nappid = \"440\"",
+        // Unknown master profile
+        "[masters.m]\naddress = \"m:1\"\n[queries.a]\nmaster = \"n\"",
+        // No master profile to default to
+        "[queries.a]\nappid = 440",
+        // Invalid region
+        "[masters.m]\naddress = \"m:1\"\n[queries.a]\nregion = \"mars\"",
+        // Raw filter and conditions
+        "[masters.m]\naddress = \"m:1\"\n[queries.a]\nfilter = '\\appid\\440'\nmap = \"x\"",
+        // Missing address
+        "[masters.m]\nmax_servers = 10",
+        // Not TOML
+        "[queries",
+    ];
+    for toml in invalid {
+        let err = toml.parse::<Queries>().expect_err(toml);
+        assert_eq!(err.kind(), ErrorKind::InvalidData, "{}", toml);
+    }
+}
+
+#[cfg(all(feature = "config", any(feature = "async", feature = "non-async")))]
+fn mock_queries() -> Result<(MockMasterHandle, Queries)> {
+    let mock = MockMasterServer::new()
+        .servers(&["10.0.0.1:27015", "10.0.0.2:27015", "10.0.0.3:27015"])
+        .start()?;
+    let toml = format!(
+        "[masters.default]\naddress = \"{}\"\n\n[queries.all]\nappid = 240\nmax_servers = 2\n",
+        mock.local_addr()
+    );
+    Ok((mock, toml.parse()?))
+}
+
+#[test]
+#[cfg(all(feature = "config", feature = "non-async"))]
+fn test_queries_run_block() -> Result<()> {
+    let (_mock, queries) = mock_queries()?;
+    assert_eq!(queries.run_block("all")?, vec!["10.0.0.1:27015", "10.0.0.2:27015"]);
+    assert_eq!(queries.run_block("other").unwrap_err().kind(), ErrorKind::NotFound);
+    Ok(())
+}
+
+#[tokio::main]
+#[test]
+#[cfg(all(feature = "config", feature = "async"))]
+async fn test_queries_run() -> Result<()> {
+    let (_mock, queries) = mock_queries()?;
+    assert_eq!(queries.run("all").await?, vec!["10.0.0.1:27015", "10.0.0.2:27015"]);
+    Ok(())
+}
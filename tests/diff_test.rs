@@ -0,0 +1,105 @@
+#[cfg(feature = "export")]
+use msq::{Region, ServerChange, ServerInfo, ServerRecord, Snapshot};
+#[cfg(feature = "export")]
+use std::time::Duration;
+
+#[cfg(feature = "export")]
+fn record(addr: &str, info: Option<(&str, u8, &str, &str)>) -> ServerRecord {
+    ServerRecord {
+        addr: String::from(addr),
+        region: Region::Europe,
+        filter: String::from("\\appid\\240"),
+        timestamp: 0,
+        info: info.map(|(map, players, name, version)| ServerInfo {
+            protocol: 17,
+            name: String::from(name),
+            map: String::from(map),
+            folder: String::from("cstrike"),
+            game: String::from("Counter-Strike: Source"),
+            appid: 240,
+            players,
+            max_players: 24,
+            bots: 0,
+            dedicated: true,
+            proxy: false,
+            linux: true,
+            password: false,
+            secure: true,
+            version: String::from(version),
+            port: Some(27015),
+            keywords: vec![],
+            ping: Duration::from_millis(30),
+        }),
+    }
+}
+
+#[cfg(feature = "export")]
+fn snapshot(records: Vec<ServerRecord>) -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    for record in records {
+        snapshot.push(record);
+    }
+    snapshot
+}
+
+#[test]
+#[cfg(feature = "export")]
+fn test_diff_added_removed_changed() {
+    let old = snapshot(vec![
+        record("10.0.0.1:27015", Some(("de_dust2", 10, "Alpha", "1.0"))),
+        record("10.0.0.2:27015", Some(("cs_office", 5, "Bravo", "1.0"))),
+        record("10.0.0.3:27015", Some(("de_nuke", 0, "Charlie", "1.0"))),
+        record("10.0.0.4:27015", None),
+    ]);
+    let new = snapshot(vec![
+        record("10.0.0.1:27015", Some(("de_inferno", 12, "Alpha", "1.1"))),
+        record("10.0.0.2:27015", Some(("cs_office", 5, "Bravo", "1.0"))),
+        record("10.0.0.4:27015", Some(("de_dust2", 3, "Delta", "1.0"))),
+        record("10.0.0.5:27015", None),
+    ]);
+    let diff = old.diff(&new);
+
+    assert_eq!(diff.added().len(), 1);
+    assert_eq!(diff.added()[0].addr, "10.0.0.5:27015");
+    assert_eq!(diff.removed().len(), 1);
+    assert_eq!(diff.removed()[0].addr, "10.0.0.3:27015");
+
+    // 10.0.0.4 has no info in the old snapshot, so it can't be compared
+    assert_eq!(diff.changed().len(), 1);
+    let changed = &diff.changed()[0];
+    assert_eq!(changed.addr, "10.0.0.1:27015");
+    assert_eq!(
+        changed.changes,
+        vec![
+            ServerChange::Map {
+                old: String::from("de_dust2"),
+                new: String::from("de_inferno"),
+            },
+            ServerChange::Players { old: 10, new: 12 },
+            ServerChange::Version {
+                old: String::from("1.0"),
+                new: String::from("1.1"),
+            },
+        ]
+    );
+
+    let summary = diff.summary();
+    assert_eq!((summary.added, summary.removed, summary.changed), (1, 1, 1));
+    assert_eq!(
+        summary.to_string(),
+        "1 added, 1 removed, 1 changed (1 map, 1 players, 0 name, 1 version)"
+    );
+}
+
+#[test]
+#[cfg(feature = "export")]
+fn test_diff_unchanged() {
+    let old = snapshot(vec![
+        record("10.0.0.1:27015", Some(("de_dust2", 10, "Alpha", "1.0"))),
+        // Queried in another region too, only the first record counts
+        record("10.0.0.1:27015", None),
+    ]);
+    let diff = old.diff(&old.clone());
+    assert!(diff.is_empty());
+    assert_eq!(diff.summary().to_string(), "0 added, 0 removed, 0 changed");
+}
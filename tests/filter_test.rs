@@ -82,3 +82,58 @@ fn test_filter_gametype2() {
     );
 }
 
+
+#[test]
+fn test_filter_and() {
+    let base = Filter::new().appid(240).nand().map("de_dust2");
+    let filter = Filter::and(base, Filter::new().empty(false));
+
+    assert_eq!(
+        filter.as_string(),
+        "\\appid\\240\\nand\\1\\map\\de_dust2\\empty\\1"
+    );
+}
+
+#[test]
+fn test_filter_not_all() {
+    let filter = Filter::new()
+        .appid(240)
+        .extend(Filter::not_all(Filter::new().map("de_dust2").empty(true)));
+
+    assert_eq!(
+        filter.as_string(),
+        "\\appid\\240\\nand\\2\\map\\de_dust2\\noplayers\\1"
+    );
+    assert_eq!(Filter::not_all(Filter::new()).as_string(), "");
+}
+
+#[test]
+fn test_filter_none_of() {
+    let filter = Filter::none_of([
+        Filter::new().map("de_dust2"),
+        Filter::new(),
+        Filter::new().map("cs_italy").empty(true),
+    ]);
+
+    assert_eq!(
+        filter.as_string(),
+        "\\nor\\1\\map\\de_dust2\\nand\\2\\map\\cs_italy\\noplayers\\1"
+    );
+}
+
+#[test]
+fn test_filter_extend_in_special() {
+    let base = Filter::new().map("de_dust2").gametype(&vec!["alltalk"]);
+    let filter = Filter::new()
+        .appid(240)
+        .nor()
+            .extend(base.clone())
+            .empty(true)
+        .end()
+        .extend(base);
+
+    assert_eq!(
+        filter.as_string(),
+        "\\appid\\240\\nor\\3\\map\\de_dust2\\gametype\\alltalk\\noplayers\\1\\map\\de_dust2\\gametype\\alltalk"
+    );
+}
@@ -0,0 +1,62 @@
+#[cfg(feature = "geo")]
+use msq::{GeoDatabase, Region};
+#[cfg(feature = "geo")]
+use std::net::IpAddr;
+
+#[cfg(feature = "geo")]
+const GEO_CSV: &str = "\
+# start_ip,end_ip,country,continent,longitude
+1.0.0.0,1.0.0.255,AU,OC,133.0
+2.16.0.0,2.16.255.255,FR,EU
+3.0.0.0,3.0.255.255,US,NA,-122.3
+4.0.0.0,4.0.255.255,US,NA,-77.0
+5.0.0.0,5.0.255.255,AE,AS
+2001:db8::,2001:db8::ffff,DE,EU
+";
+
+#[cfg(feature = "geo")]
+#[test]
+fn test_geo_lookup() {
+    let db = GeoDatabase::from_csv(GEO_CSV.as_bytes()).unwrap();
+
+    let geo = db.lookup("2.16.4.1".parse::<IpAddr>().unwrap()).unwrap();
+    assert_eq!(geo.country.as_deref(), Some("FR"));
+    assert_eq!(geo.continent.as_deref(), Some("EU"));
+    assert_eq!(geo.region, Some(Region::Europe));
+
+    let region = |ip: &str| db.lookup(ip.parse().unwrap()).and_then(|g| g.region);
+    assert_eq!(region("1.0.0.1"), Some(Region::Australia));
+    assert_eq!(region("3.0.1.1"), Some(Region::USWest));
+    assert_eq!(region("4.0.1.1"), Some(Region::USEast));
+    assert_eq!(region("5.0.0.9"), Some(Region::MiddleEast));
+    assert_eq!(region("2001:db8::1"), Some(Region::Europe));
+    assert!(db.lookup("9.9.9.9".parse().unwrap()).is_none());
+}
+
+#[cfg(feature = "geo")]
+#[test]
+fn test_geo_annotate() {
+    let db = GeoDatabase::from_csv(GEO_CSV.as_bytes()).unwrap();
+    let servers = vec![
+        String::from("2.16.4.1:27015"),
+        String::from("4.0.1.1:27015"),
+        String::from("9.9.9.9:27015"),
+    ];
+
+    let annotated = db.annotate(&servers, Region::Europe);
+    assert_eq!(annotated.len(), 3);
+    assert!(!annotated[0].mismatch);
+    assert!(annotated[1].mismatch);
+    assert!(annotated[2].geo.is_none());
+    assert!(!annotated[2].mismatch);
+
+    let annotated = db.annotate(&servers, Region::All);
+    assert!(annotated.iter().all(|s| !s.mismatch));
+}
+
+#[cfg(feature = "geo")]
+#[test]
+fn test_geo_invalid_csv() {
+    assert!(GeoDatabase::from_csv("1.0.0.0,AU,OC".as_bytes()).is_err());
+    assert!(GeoDatabase::from_csv("1.0.0.0,nope,AU,OC".as_bytes()).is_err());
+}
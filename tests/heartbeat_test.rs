@@ -0,0 +1,182 @@
+#[cfg(feature = "server")]
+use msq::{Heartbeat, HeartbeatVariant, MasterServer, Region, ServerAttrs};
+#[cfg(feature = "server")]
+use std::io::Result;
+#[cfg(feature = "server")]
+use std::net::SocketAddr;
+#[cfg(feature = "server")]
+use std::thread;
+#[cfg(feature = "server")]
+use std::time::Duration;
+
+#[cfg(feature = "server")]
+fn wait_for(master: &MasterServer, len: usize) {
+    for _ in 0..100 {
+        if master.len() == len {
+            return;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+#[cfg(feature = "server")]
+fn test_heartbeat_source() -> Result<()> {
+    let master = MasterServer::new();
+    let handle = master.start("127.0.0.1:0")?;
+
+    let mut heartbeat = Heartbeat::bind("127.0.0.1:0", HeartbeatVariant::Source)?;
+    heartbeat.connect(&handle.local_addr().to_string())?;
+    heartbeat.send(
+        &ServerAttrs::new()
+            .appid(240)
+            .gamedir("cstrike")
+            .map("de_dust2")
+            .players(3, 24)
+            .region(Region::Europe)
+            .dedicated(true)
+            .linux(true)
+            .gametype(&["alltalk", "friendlyfire"]),
+    )?;
+    wait_for(&master, 1);
+
+    let servers = master.query(Region::Europe, "\\appid\\240\\map\\de_dust2\\gametype\\alltalk")?;
+    assert_eq!(servers.len(), 1);
+    let attrs = master.get(&servers[0])?.unwrap();
+    assert_eq!(attrs.players, 3);
+    assert_eq!(attrs.max_players, 24);
+    assert!(attrs.dedicated);
+    assert!(attrs.linux);
+    assert!(!attrs.secure);
+
+    // Quitting removes the server
+    heartbeat.quit()?;
+    wait_for(&master, 0);
+    assert!(master.is_empty());
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "server")]
+fn test_heartbeat_goldsrc() -> Result<()> {
+    let master = MasterServer::new();
+    let handle = master.start("127.0.0.1:0")?;
+
+    let mut heartbeat = Heartbeat::new(HeartbeatVariant::GoldSrc)?;
+    heartbeat.connect(&handle.local_addr().to_string())?;
+    heartbeat.send(&ServerAttrs::new().appid(10).gamedir("cstrike").map("de_inferno"))?;
+    wait_for(&master, 1);
+
+    // GoldSrc heartbeats have no appid
+    assert_eq!(master.query(Region::All, "\\gamedir\\cstrike")?.len(), 1);
+    assert!(master.query(Region::All, "\\appid\\10")?.is_empty());
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "server")]
+fn test_heartbeat_bad_challenge() -> Result<()> {
+    let master = MasterServer::new();
+    let from = "127.0.0.1:27015".parse().unwrap();
+
+    // Without a challenge request
+    let info = b"0\n\\protocol\\7\\challenge\\1234\\appid\\240\n";
+    assert!(master.handle_packet(info, from).is_err());
+
+    // With a different challenge than the one handed out
+    let reply = master.handle_packet(b"q", from)?.unwrap();
+    assert_eq!(&reply[..6], &[0xFF, 0xFF, 0xFF, 0xFF, 0x73, 0x0A]);
+    let challenge = u32::from_le_bytes([reply[6], reply[7], reply[8], reply[9]]);
+    let info = format!("0\n\\protocol\\7\\challenge\\{}\\appid\\240\n", challenge.wrapping_add(1));
+    assert!(master.handle_packet(info.as_bytes(), from).is_err());
+
+    // From a different address than the challenge was handed out to
+    let info = format!("0\n\\protocol\\7\\challenge\\{}\\appid\\240\n", challenge);
+    let other = "127.0.0.1:27016".parse().unwrap();
+    assert!(master.handle_packet(info.as_bytes(), other).is_err());
+    assert!(master.is_empty());
+
+    assert_eq!(master.handle_packet(info.as_bytes(), from)?, None);
+    assert_eq!(master.query(Region::All, "\\appid\\240")?, vec!["127.0.0.1:27015"]);
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "server")]
+fn test_heartbeat_expire() -> Result<()> {
+    let master = MasterServer::new().heartbeat_timeout(Duration::from_millis(200));
+    master.register("10.0.0.1:27015", ServerAttrs::new())?;
+    let handle = master.start("127.0.0.1:0")?;
+
+    let mut heartbeat = Heartbeat::bind("127.0.0.1:0", HeartbeatVariant::Source)?;
+    heartbeat.connect(&handle.local_addr().to_string())?;
+    heartbeat.send(&ServerAttrs::new())?;
+    wait_for(&master, 2);
+    let servers = master.query(Region::All, "")?;
+    assert_eq!(servers.len(), 2);
+    let addr = servers.into_iter().find(|addr| addr != "10.0.0.1:27015").unwrap();
+    assert!(master.get(&addr)?.is_some());
+
+    // Only the heartbeat server expires
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(master.expire(), 1);
+    assert!(master.get(&addr)?.is_none());
+    assert_eq!(master.query(Region::All, "")?, vec!["10.0.0.1:27015"]);
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "server")]
+fn test_heartbeat_challenge_expire() -> Result<()> {
+    let master = MasterServer::new().challenge_timeout(Duration::from_millis(100));
+    let from: SocketAddr = "127.0.0.1:27015".parse().unwrap();
+    let challenge = |from: SocketAddr| -> Result<u32> {
+        let reply = master.handle_packet(b"q", from)?.unwrap();
+        Ok(u32::from_le_bytes([reply[6], reply[7], reply[8], reply[9]]))
+    };
+    let info = |challenge: u32| format!("0\n\\protocol\\7\\challenge\\{}\\appid\\240\n", challenge);
+
+    // Too late for the challenge
+    let late = challenge(from)?;
+    thread::sleep(Duration::from_millis(200));
+    assert!(master.handle_packet(info(late).as_bytes(), from).is_err());
+    assert!(master.is_empty());
+
+    // The oldest challenge makes room once too many are handed out
+    let oldest = challenge(from)?;
+    for port in 0..4096 {
+        challenge(SocketAddr::from(([127, 0, 0, 2], port)))?;
+    }
+    assert!(master.handle_packet(info(oldest).as_bytes(), from).is_err());
+    let fresh = challenge(from)?;
+    assert_eq!(master.handle_packet(info(fresh).as_bytes(), from)?, None);
+    assert_eq!(master.len(), 1);
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "server")]
+fn test_heartbeat_periodic() -> Result<()> {
+    let master = MasterServer::new().heartbeat_timeout(Duration::from_millis(300));
+    let handle = master.start("127.0.0.1:0")?;
+
+    let mut heartbeat = Heartbeat::bind("127.0.0.1:0", HeartbeatVariant::Source)?;
+    heartbeat.connect(&handle.local_addr().to_string())?;
+    let mut players = 0;
+    let periodic = heartbeat.start(Duration::from_millis(50), move || {
+        players += 1;
+        ServerAttrs::new().appid(240).players(players, 32)
+    })?;
+
+    // Kept registered by the heartbeats, longer than the timeout
+    thread::sleep(Duration::from_millis(500));
+    let servers = master.query(Region::All, "\\appid\\240")?;
+    assert_eq!(servers, vec![periodic.local_addr().to_string()]);
+    assert!(master.get(&servers[0])?.unwrap().players > 1);
+
+    // Expires once the heartbeats stop
+    periodic.stop();
+    thread::sleep(Duration::from_millis(400));
+    assert!(master.is_empty());
+    Ok(())
+}
@@ -0,0 +1,130 @@
+#[cfg(feature = "server")]
+use msq::{Filter, MasterServer, Region, ServerAttrs};
+#[cfg(feature = "server")]
+use std::io::Result;
+
+#[cfg(feature = "server")]
+fn master() -> Result<MasterServer> {
+    let master = MasterServer::new();
+    master.register(
+        "10.0.0.1:27015",
+        ServerAttrs::new()
+            .appid(240)
+            .region(Region::Europe)
+            .map("de_dust2")
+            .players(0, 24)
+            .dedicated(true)
+            .gametype(&["friendlyfire", "alltalk"]),
+    )?;
+    master.register(
+        "10.0.0.1:27016",
+        ServerAttrs::new()
+            .appid(240)
+            .region(Region::Europe)
+            .map("cs_italy")
+            .players(24, 24)
+            .name("Italy 24/7"),
+    )?;
+    master.register(
+        "10.0.0.2:27015",
+        ServerAttrs::new()
+            .appid(440)
+            .region(Region::USEast)
+            .map("cp_badlands")
+            .players(5, 24)
+            .secure(true)
+            .gametype(&["alltalk"]),
+    )?;
+    Ok(master)
+}
+
+#[cfg(feature = "server")]
+#[test]
+fn test_master_filter_eval() -> Result<()> {
+    let master = master()?;
+    let query = |region: Region, filter: Filter| master.query(region, &filter.as_string());
+
+    assert_eq!(query(Region::All, Filter::new())?.len(), 3);
+    assert_eq!(query(Region::Europe, Filter::new())?.len(), 2);
+    assert_eq!(query(Region::Asia, Filter::new())?.len(), 0);
+    assert_eq!(query(Region::All, Filter::new().appid(440))?, vec!["10.0.0.2:27015"]);
+    assert_eq!(query(Region::All, Filter::new().napp(440))?.len(), 2);
+    assert_eq!(query(Region::All, Filter::new().map("DE_DUST2"))?, vec!["10.0.0.1:27015"]);
+    assert_eq!(query(Region::All, Filter::new().empty(true))?, vec!["10.0.0.1:27015"]);
+    assert_eq!(query(Region::All, Filter::new().empty(false))?.len(), 2);
+    assert_eq!(query(Region::All, Filter::new().full(true))?, vec!["10.0.0.1:27016"]);
+    assert_eq!(query(Region::All, Filter::new().full(false))?.len(), 2);
+    assert_eq!(query(Region::All, Filter::new().secure(true))?, vec!["10.0.0.2:27015"]);
+    assert_eq!(query(Region::All, Filter::new().name_match("italy*"))?, vec!["10.0.0.1:27016"]);
+    assert_eq!(query(Region::All, Filter::new().gameaddr("10.0.0.1"))?.len(), 2);
+    assert_eq!(query(Region::All, Filter::new().gameaddr("10.0.0.1:27016"))?.len(), 1);
+    assert_eq!(query(Region::All, Filter::new().gameaddr("10.0.*"))?.len(), 3);
+    assert_eq!(query(Region::All, Filter::new().collapse_addr_hash(true))?.len(), 2);
+    assert_eq!(
+        query(Region::All, Filter::new().gametype(&vec!["friendlyfire", "alltalk"]))?,
+        vec!["10.0.0.1:27015"]
+    );
+    Ok(())
+}
+
+#[cfg(feature = "server")]
+#[test]
+fn test_master_special_filters() -> Result<()> {
+    let master = master()?;
+    let query = |filter: Filter| master.query(Region::All, &filter.as_string());
+
+    // Exclude servers on de_dust2 that are empty
+    let filter = Filter::new().nand().map("de_dust2").empty(true).end();
+    assert_eq!(query(filter)?.len(), 2);
+
+    // Exclude servers on de_dust2 or cp_badlands
+    let filter = Filter::new().nor().map("de_dust2").map("cp_badlands").end();
+    assert_eq!(query(filter)?, vec!["10.0.0.1:27016"]);
+
+    // Exclude servers of appid 440, and servers of appid 240 on cs_italy
+    let filter = Filter::new()
+        .nor()
+        .appid(440)
+        .end()
+        .nand()
+        .appid(240)
+        .map("cs_italy")
+        .end();
+    assert_eq!(query(filter)?, vec!["10.0.0.1:27015"]);
+
+    // The same with none_of, a NOR special filter followed by a NAND one
+    let filter = Filter::none_of([
+        Filter::new().appid(440),
+        Filter::new().appid(240).map("cs_italy"),
+    ]);
+    assert_eq!(query(filter)?, vec!["10.0.0.1:27015"]);
+
+    assert!(master.query(Region::All, "\\nor\\2\\map\\de_dust2").is_err());
+    Ok(())
+}
+
+#[cfg(all(feature = "server", feature = "non-async"))]
+#[test]
+fn test_master_client_pagination() -> Result<()> {
+    use msq::MSQClientBlock;
+
+    let master = MasterServer::new();
+    for i in 0..1000 {
+        let addr = format!("10.1.{}.{}:27015", i / 256, i % 256);
+        let attrs = ServerAttrs::new().appid(if i % 2 == 0 { 240 } else { 440 });
+        master.register(&addr, attrs)?;
+    }
+    let handle = master.start("127.0.0.1:0")?;
+
+    let mut client = MSQClientBlock::new()?;
+    client.connect(&handle.local_addr().to_string())?;
+    client.max_servers_on_query(4096);
+    let servers = client.query(Region::All, Filter::new().appid(240))?;
+    assert_eq!(servers.len(), 500);
+    assert_eq!(servers, master.query(Region::All, "\\appid\\240")?);
+
+    assert!(master.unregister("10.1.0.0:27015")?);
+    assert!(!master.unregister("10.1.0.0:27015")?);
+    assert_eq!(master.len(), 999);
+    Ok(())
+}
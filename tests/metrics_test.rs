@@ -0,0 +1,98 @@
+#[cfg(feature = "metrics")]
+use msq::{A2SResponder, Filter, MasterServer, MetricsExporter, Region, ServerAttrs};
+#[cfg(feature = "metrics")]
+use std::io::{Read, Result, Write};
+#[cfg(feature = "metrics")]
+use std::net::{SocketAddr, TcpStream};
+#[cfg(feature = "metrics")]
+use std::time::Duration;
+
+#[cfg(feature = "metrics")]
+async fn get(addr: SocketAddr, path: &str) -> Result<String> {
+    let path = String::from(path);
+    tokio::task::spawn_blocking(move || {
+        let mut stream = TcpStream::connect(addr)?;
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path)?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        Ok(response)
+    })
+    .await
+    .unwrap()
+}
+
+// Polls /metrics until the line shows up
+#[cfg(feature = "metrics")]
+async fn wait_for(addr: SocketAddr, line: &str) -> Result<String> {
+    for _ in 0..50 {
+        let response = get(addr, "/metrics").await?;
+        if response.lines().any(|l| l == line) {
+            return Ok(response);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("Missing metric: {}", line);
+}
+
+#[tokio::main]
+#[test]
+#[cfg(feature = "metrics")]
+async fn test_metrics_exporter() -> Result<()> {
+    let dust = A2SResponder::new(ServerAttrs::new().appid(240).map("de_dust2").players(3, 24).bots(1)).start()?;
+    let office = A2SResponder::new(ServerAttrs::new().appid(240).map("cs_office").players(5, 24)).start()?;
+    let master = MasterServer::new();
+    master.register(&dust.local_addr().to_string(), ServerAttrs::new().appid(240).region(Region::Europe))?;
+    master.register(&office.local_addr().to_string(), ServerAttrs::new().appid(240).region(Region::Europe))?;
+    // Registered, but nothing answers probes
+    master.register("127.0.0.1:9", ServerAttrs::new().appid(240).region(Region::Europe))?;
+    let handle = master.start("127.0.0.1:0")?;
+
+    let exporter = MetricsExporter::new(&handle.local_addr().to_string())
+        .query("css", Region::Europe, Filter::new().appid(240))
+        .probe(true)
+        .probe_timeout(Duration::from_millis(300))
+        .start("127.0.0.1:0")
+        .await?;
+
+    let response = wait_for(exporter.local_addr(), "msq_servers{query=\"css\",region=\"Europe\"} 3").await?;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("Content-Type: text/plain; version=0.0.4\r\n"));
+    assert!(response.contains("# TYPE msq_servers gauge\n"));
+    for line in [
+        "msq_players{query=\"css\",region=\"Europe\"} 8",
+        "msq_bots{query=\"css\",region=\"Europe\"} 1",
+        "msq_map_servers{query=\"css\",region=\"Europe\",map=\"cs_office\"} 1",
+        "msq_map_servers{query=\"css\",region=\"Europe\",map=\"de_dust2\"} 1",
+        "msq_probes_total{query=\"css\",region=\"Europe\",result=\"success\"} 2",
+        "msq_probes_total{query=\"css\",region=\"Europe\",result=\"failure\"} 1",
+        "msq_master_queries_total{query=\"css\",region=\"Europe\",result=\"success\"} 1",
+        "msq_master_throttled_total{query=\"css\",region=\"Europe\"} 0",
+    ] {
+        assert!(response.lines().any(|l| l == line), "Missing metric: {}", line);
+    }
+    assert!(response.contains("msq_probe_success_ratio{query=\"css\",region=\"Europe\"} 0.66"));
+    assert!(response.contains("msq_master_query_duration_seconds{query=\"css\",region=\"Europe\"} "));
+
+    assert!(get(exporter.local_addr(), "/").await?.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    exporter.stop();
+    Ok(())
+}
+
+#[tokio::main]
+#[test]
+#[cfg(feature = "metrics")]
+async fn test_metrics_throttled() -> Result<()> {
+    // A master server that never answers
+    let silent = std::net::UdpSocket::bind("127.0.0.1:0")?;
+    let exporter = MetricsExporter::new(&silent.local_addr()?.to_string())
+        .query("tf2", Region::All, Filter::new().appid(440))
+        .timeout(Duration::from_millis(200))
+        .start("127.0.0.1:0")
+        .await?;
+
+    let response = wait_for(exporter.local_addr(), "msq_master_throttled_total{query=\"tf2\",region=\"Rest of the world\"} 1").await?;
+    assert!(response.contains("msq_master_queries_total{query=\"tf2\",region=\"Rest of the world\",result=\"failure\"} 1\n"));
+    // No successful poll yet
+    assert!(!response.contains("msq_last_poll_timestamp_seconds{"));
+    Ok(())
+}
@@ -0,0 +1,199 @@
+#[cfg(all(feature = "server", feature = "async"))]
+use msq::{Filter, MSQClient, MasterProxy, MasterServer, MockFault, MockMasterServer, Region, ServerAttrs};
+#[cfg(all(feature = "server", feature = "async"))]
+use std::io::Result;
+#[cfg(all(feature = "server", feature = "async"))]
+use std::time::Duration;
+
+#[cfg(all(feature = "server", feature = "async"))]
+fn mock_master(count: usize) -> MockMasterServer {
+    let mut mock = MockMasterServer::new();
+    for i in 0..count {
+        let addr = format!("10.{}.{}.{}:27015", i / 65536, (i / 256) % 256, i % 256);
+        mock = mock.server_with(&addr, ServerAttrs::new().appid(240));
+    }
+    mock
+}
+
+#[cfg(all(feature = "server", feature = "async"))]
+async fn query(proxy: &str, filter: Filter) -> Result<Vec<String>> {
+    let mut client = MSQClient::new().await?;
+    client.connect(proxy).await?;
+    client.max_servers_on_query(1000);
+    client.query(Region::All, filter).await
+}
+
+#[cfg(all(feature = "server", feature = "async"))]
+#[tokio::main]
+#[test]
+async fn test_proxy_cache() -> Result<()> {
+    let mock = mock_master(500).start()?;
+    let proxy = MasterProxy::new(&mock.local_addr().to_string()).start("127.0.0.1:0")?;
+    let proxy_addr = proxy.local_addr().to_string();
+
+    let servers = query(&proxy_addr, Filter::new().appid(240).map("de_dust2")).await?;
+    assert_eq!(servers.len(), 500);
+    let upstream = mock.requests().len();
+
+    // Same query with its conditions in another order, answered from the cache
+    let cached = query(&proxy_addr, Filter::new().map("de_dust2").appid(240)).await?;
+    assert_eq!(cached, servers);
+    assert_eq!(mock.requests().len(), upstream);
+
+    // Another filter is a separate cache entry
+    query(&proxy_addr, Filter::new().appid(240)).await?;
+    assert!(mock.requests().len() > upstream);
+    Ok(())
+}
+
+#[cfg(all(feature = "server", feature = "async"))]
+#[tokio::main]
+#[test]
+async fn test_proxy_coalesce() -> Result<()> {
+    let mock = mock_master(10)
+        .fault_all(MockFault::Delay(Duration::from_millis(200)))
+        .start()?;
+    let proxy = MasterProxy::new(&mock.local_addr().to_string()).start("127.0.0.1:0")?;
+    let proxy_addr = proxy.local_addr().to_string();
+
+    let mut tasks = vec![];
+    for _ in 0..5 {
+        let proxy_addr = proxy_addr.clone();
+        tasks.push(tokio::spawn(async move {
+            query(&proxy_addr, Filter::new().appid(240)).await
+        }));
+    }
+    for task in tasks {
+        assert_eq!(task.await.unwrap()?.len(), 10);
+    }
+    assert_eq!(mock.requests().len(), 1);
+    Ok(())
+}
+
+#[cfg(all(feature = "server", feature = "async"))]
+#[tokio::main]
+#[test]
+async fn test_proxy_ttl() -> Result<()> {
+    let mock = mock_master(10).start()?;
+    let proxy = MasterProxy::new(&mock.local_addr().to_string())
+        .ttl(Duration::from_millis(100))
+        .start("127.0.0.1:0")?;
+    let proxy_addr = proxy.local_addr().to_string();
+
+    query(&proxy_addr, Filter::new().appid(240)).await?;
+    query(&proxy_addr, Filter::new().appid(240)).await?;
+    assert_eq!(mock.requests().len(), 1);
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    query(&proxy_addr, Filter::new().appid(240)).await?;
+    assert_eq!(mock.requests().len(), 2);
+    Ok(())
+}
+
+#[cfg(all(feature = "server", feature = "async"))]
+#[tokio::main]
+#[test]
+async fn test_proxy_stale() -> Result<()> {
+    let mock = mock_master(10).start()?;
+    let upstream = mock.local_addr().to_string();
+    let proxy = MasterProxy::new(&upstream)
+        .ttl(Duration::from_millis(50))
+        .upstream_timeout(Duration::from_millis(100))
+        .start("127.0.0.1:0")?;
+    let proxy_addr = proxy.local_addr().to_string();
+
+    assert_eq!(query(&proxy_addr, Filter::new().appid(240)).await?.len(), 10);
+    mock.stop();
+
+    // The expired entry is served while the upstream master is gone
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(query(&proxy_addr, Filter::new().appid(240)).await?.len(), 10);
+    Ok(())
+}
+
+#[cfg(all(feature = "server", feature = "async"))]
+#[tokio::main]
+#[test]
+async fn test_proxy_cache_size() -> Result<()> {
+    let mock = mock_master(10).start()?;
+    let proxy = MasterProxy::new(&mock.local_addr().to_string())
+        .cache_size(1)
+        .start("127.0.0.1:0")?;
+    let proxy_addr = proxy.local_addr().to_string();
+
+    query(&proxy_addr, Filter::new().appid(240)).await?;
+    query(&proxy_addr, Filter::new().appid(240).map("de_dust2")).await?;
+    assert_eq!(mock.requests().len(), 2);
+
+    // The first query made room for the second one
+    query(&proxy_addr, Filter::new().appid(240)).await?;
+    assert_eq!(mock.requests().len(), 3);
+    query(&proxy_addr, Filter::new().appid(240)).await?;
+    assert_eq!(mock.requests().len(), 3);
+    Ok(())
+}
+
+#[cfg(all(feature = "server", feature = "async"))]
+#[tokio::main]
+#[test]
+async fn test_proxy_bind_error() -> Result<()> {
+    let taken = std::net::UdpSocket::bind("127.0.0.1:0")?;
+    // Failing to bind within a runtime returns the error instead of panicking
+    let err = MasterProxy::new("127.0.0.1:27011").start(&taken.local_addr()?.to_string());
+    assert!(err.is_err());
+    Ok(())
+}
+
+// Send a request for every server to the proxy, returning the addresses of
+// the reply and whether it ends the list
+#[cfg(all(feature = "server", feature = "async"))]
+fn raw_page(sock: &std::net::UdpSocket, seed: &str) -> Result<(Vec<String>, bool)> {
+    let mut request = vec![0x31, 0xFF];
+    request.extend(seed.as_bytes());
+    request.extend([0x00, 0x00]);
+    sock.send(&request)?;
+    let mut buf = [0u8; 2048];
+    let len = sock.recv(&mut buf)?;
+    let mut addrs: Vec<String> = buf[6..len]
+        .chunks(6)
+        .map(|a| format!("{}.{}.{}.{}:{}", a[0], a[1], a[2], a[3], u16::from_be_bytes([a[4], a[5]])))
+        .collect();
+    let last = addrs.last().map(String::as_str) == Some("0.0.0.0:0");
+    if last {
+        addrs.pop();
+    }
+    Ok((addrs, last))
+}
+
+#[cfg(all(feature = "server", feature = "async"))]
+#[tokio::main]
+#[test]
+async fn test_proxy_refresh_between_pages() -> Result<()> {
+    let master = MasterServer::new();
+    for i in 0..300 {
+        master.register(&format!("10.0.{}.{}:27015", i / 256, i % 256), ServerAttrs::new())?;
+    }
+    let upstream = master.start("127.0.0.1:0")?;
+    let proxy = MasterProxy::new(&upstream.local_addr().to_string())
+        .ttl(Duration::from_millis(50))
+        .start("127.0.0.1:0")?;
+    let proxy_addr = proxy.local_addr().to_string();
+
+    let sock = std::net::UdpSocket::bind("127.0.0.1:0")?;
+    sock.connect(&proxy_addr)?;
+    sock.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let (first, last) = raw_page(&sock, "0.0.0.0:0")?;
+    assert_eq!((first.len(), last), (231, false));
+
+    // The seed of the next page is gone from the refreshed list
+    let seed = first.last().unwrap().clone();
+    master.unregister(&seed)?;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(query(&proxy_addr, Filter::new()).await?.len(), 299);
+
+    let (second, last) = raw_page(&sock, &seed)?;
+    assert!(last);
+    assert_eq!(second.len(), 69);
+    assert_eq!(second[0], "10.0.0.231:27015");
+    Ok(())
+}
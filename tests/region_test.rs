@@ -25,6 +25,59 @@ fn test_region_codes_from() {
     assert_eq!(Region::from_u8(0x07).unwrap(), Region::Africa);
     assert_eq!(Region::from_u8(0xFF).unwrap(), Region::All);
     for i in 0x08..=0xFE {
-        assert_eq!(Region::from_u8(i).is_err(), true);
+        assert_eq!(Region::from_u8(i).unwrap(), Region::Other(i));
+        assert_eq!(Region::from_u8(i).unwrap().as_u8(), i);
     }
 }
+
+#[test]
+fn test_region_other_eq() {
+    assert_eq!(Region::Other(0x03), Region::Europe);
+    assert_eq!(Region::from(0x2a), Region::Other(0x2a));
+    assert_eq!(u8::from(Region::Other(0x2a)), 0x2a);
+    assert!(Region::Other(0xFF).is_known());
+    assert!(!Region::Other(0x2a).is_known());
+}
+
+#[test]
+fn test_region_iter() {
+    let regions: Vec<Region> = Region::iter().collect();
+    assert_eq!(regions.len(), 9);
+    for (i, region) in regions.iter().take(8).enumerate() {
+        assert_eq!(region.as_u8(), i as u8);
+    }
+    assert_eq!(regions[8], Region::All);
+}
+
+#[test]
+fn test_region_parse() {
+    assert_eq!("Europe".parse::<Region>().unwrap(), Region::Europe);
+    assert_eq!("EU".parse::<Region>().unwrap(), Region::Europe);
+    assert_eq!("us-east".parse::<Region>().unwrap(), Region::USEast);
+    assert_eq!("US_West".parse::<Region>().unwrap(), Region::USWest);
+    assert_eq!("sa".parse::<Region>().unwrap(), Region::SouthAmerica);
+    assert_eq!("middle east".parse::<Region>().unwrap(), Region::MiddleEast);
+    assert_eq!("world".parse::<Region>().unwrap(), Region::All);
+    assert_eq!("0xff".parse::<Region>().unwrap(), Region::All);
+    assert_eq!("42".parse::<Region>().unwrap(), Region::Other(42));
+    assert!("atlantis".parse::<Region>().is_err());
+    assert!("256".parse::<Region>().is_err());
+}
+
+#[test]
+fn test_region_display_round_trip() {
+    for region in Region::iter() {
+        assert_eq!(region.to_string().parse::<Region>().unwrap(), region);
+    }
+    assert_eq!(Region::Other(0x2a).to_string(), "Unknown (0x2a)");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_region_serde() {
+    assert_eq!(serde_json::to_string(&Region::Europe).unwrap(), "3");
+    assert_eq!(serde_json::to_string(&Region::Other(0x2a)).unwrap(), "42");
+    assert_eq!(serde_json::from_str::<Region>("42").unwrap(), Region::Other(0x2a));
+    assert_eq!(serde_json::from_str::<Region>("\"us-west\"").unwrap(), Region::USWest);
+    assert!(serde_json::from_str::<Region>("300").is_err());
+}
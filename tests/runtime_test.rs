@@ -0,0 +1,47 @@
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+use msq::{AsyncUdpSocket, Filter, MSQClient, MockMasterServer, Region, ServerAttrs};
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+use std::io::Result;
+
+// Queries a single region and every region of a mock master server
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+async fn query_mock<S: AsyncUdpSocket>() -> Result<()> {
+    let mock = MockMasterServer::new()
+        .server("10.0.0.1:27015")
+        .server_with("10.0.0.2:27015", ServerAttrs::new().region(Region::Asia))
+        .start()?;
+    let mut client = MSQClient::<S>::create().await?;
+    client.connect(&mock.local_addr().to_string()).await?;
+    client.max_servers_on_query(256);
+    assert_eq!(
+        client.query(Region::All, Filter::new().appid(240)).await?,
+        vec!["10.0.0.1:27015", "10.0.0.2:27015"]
+    );
+
+    let results = client
+        .query_regions(&[Region::Europe, Region::Asia, Region::Africa], Filter::new().appid(240))
+        .await?;
+    assert_eq!(results.count(Region::Asia), Some(1));
+    assert_eq!(results.count(Region::Europe), Some(0));
+    assert!(results.errors().is_empty());
+    Ok(())
+}
+
+#[tokio::main]
+#[test]
+#[cfg(feature = "tokio")]
+async fn test_runtime_tokio() -> Result<()> {
+    query_mock::<msq::TokioUdpSocket>().await
+}
+
+#[test]
+#[cfg(feature = "async-std")]
+fn test_runtime_async_std() -> Result<()> {
+    async_std::task::block_on(query_mock::<msq::AsyncStdUdpSocket>())
+}
+
+#[test]
+#[cfg(feature = "smol")]
+fn test_runtime_smol() -> Result<()> {
+    smol::block_on(query_mock::<msq::SmolUdpSocket>())
+}
@@ -0,0 +1,195 @@
+#[cfg(all(feature = "non-async", feature = "server"))]
+use msq::{Filter, MSQClientBlock, MasterServer, PartialQuery, Region, ServerAttrs, ShardStrategy, Transport};
+#[cfg(all(feature = "non-async", feature = "server"))]
+use std::collections::VecDeque;
+#[cfg(all(feature = "non-async", feature = "server"))]
+use std::io::{Error, ErrorKind, Result};
+#[cfg(all(feature = "non-async", feature = "server"))]
+use std::net::SocketAddr;
+#[cfg(all(feature = "non-async", feature = "server"))]
+use std::sync::{Arc, Mutex};
+#[cfg(all(feature = "non-async", feature = "server"))]
+use std::time::Instant;
+
+// Region code and filter of each request
+#[cfg(all(feature = "non-async", feature = "server"))]
+type Requests = Arc<Mutex<Vec<(u8, String)>>>;
+
+// Transport answering with a MasterServer, which evaluates the filters,
+// recording the region and filter of every request
+#[cfg(all(feature = "non-async", feature = "server"))]
+struct MasterTransport {
+    master: Arc<MasterServer>,
+    requests: Requests,
+    replies: VecDeque<Vec<u8>>,
+    // Fail every request past this amount
+    fail_after: usize,
+}
+
+#[cfg(all(feature = "non-async", feature = "server"))]
+impl Transport for MasterTransport {
+    fn connect(&mut self, _addr: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn send(&mut self, buf: &[u8], _deadline: Option<Instant>) -> Result<usize> {
+        // 0x31, region code, seed and filter
+        let strings: Vec<&[u8]> = buf[2..].split(|b| *b == 0).collect();
+        let mut requests = self.requests.lock().unwrap();
+        if strings[0] == b"0.0.0.0:0" {
+            requests.push((buf[1], String::from_utf8_lossy(strings[1]).into_owned()));
+        }
+        if requests.len() > self.fail_after {
+            return Ok(buf.len());
+        }
+        if let Some(reply) = self.master.handle_packet(buf, self.local_addr()?)? {
+            self.replies.push_back(reply);
+        }
+        Ok(buf.len())
+    }
+
+    fn recv(&mut self, buf: &mut [u8], _deadline: Option<Instant>) -> Result<usize> {
+        let reply = self
+            .replies
+            .pop_front()
+            .ok_or_else(|| Error::new(ErrorKind::ConnectionReset, "Master server is gone"))?;
+        buf[..reply.len()].copy_from_slice(&reply);
+        Ok(reply.len())
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        Ok("127.0.0.1:27005".parse().unwrap())
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        Ok("127.0.0.1:27010".parse().unwrap())
+    }
+}
+
+#[cfg(all(feature = "non-async", feature = "server"))]
+fn sharded_client(servers: &[(&str, ServerAttrs)], fail_after: usize) -> Result<(MSQClientBlock, Requests)> {
+    let master = MasterServer::new();
+    for (addr, attrs) in servers {
+        master.register(addr, attrs.clone())?;
+    }
+    let requests = Arc::new(Mutex::new(vec![]));
+    let transport = MasterTransport {
+        master: Arc::new(master),
+        requests: requests.clone(),
+        replies: VecDeque::new(),
+        fail_after,
+    };
+    Ok((MSQClientBlock::with_transport(transport), requests))
+}
+
+#[cfg(all(feature = "non-async", feature = "server"))]
+#[test]
+fn test_shard_regions() -> Result<()> {
+    let europe = ServerAttrs::new().region(Region::Europe);
+    let servers = [
+        ("10.0.0.1:27015", europe.clone()),
+        ("10.0.0.2:27015", europe.clone().dedicated(true)),
+        ("10.0.0.3:27015", ServerAttrs::new().region(Region::Asia)),
+    ];
+    let strategy = ShardStrategy::new().regions().dedicated().shard_limit(2);
+
+    // Region::All fans out to every region, then Europe is split again
+    let (mut client, requests) = sharded_client(&servers, usize::MAX)?;
+    assert_eq!(client.query_sharded(Region::All, Filter::new(), &strategy)?.len(), 3);
+    let regions: Vec<u8> = requests.lock().unwrap().iter().map(|(code, _)| *code).collect();
+    assert_eq!(regions, vec![0xFF, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x03, 0x03]);
+
+    // A regional query skips to the next partition
+    let (mut client, requests) = sharded_client(&servers, usize::MAX)?;
+    assert_eq!(client.query_sharded(Region::Europe, Filter::new(), &strategy)?.len(), 2);
+    assert_eq!(
+        *requests.lock().unwrap(),
+        vec![
+            (0x03, String::new()),
+            (0x03, String::from("\\dedicated\\1")),
+            (0x03, String::from("\\nand\\1\\dedicated\\1")),
+        ]
+    );
+    Ok(())
+}
+
+#[cfg(all(feature = "non-async", feature = "server"))]
+#[test]
+fn test_shard_boolean_split() -> Result<()> {
+    let servers = [
+        ("10.0.0.1:27015", ServerAttrs::new()),
+        ("10.0.0.2:27015", ServerAttrs::new().dedicated(true)),
+        ("10.0.0.3:27015", ServerAttrs::new().secure(true)),
+        ("10.0.0.4:27015", ServerAttrs::new().dedicated(true).secure(true)),
+    ];
+    let strategy = ShardStrategy::new().dedicated().secure().shard_limit(2);
+    let (mut client, requests) = sharded_client(&servers, usize::MAX)?;
+    let mut found = client.query_sharded(Region::All, Filter::new(), &strategy)?;
+    found.sort();
+    assert_eq!(found, servers.iter().map(|(addr, _)| addr.to_string()).collect::<Vec<_>>());
+
+    // Each half of a split is truncated again and split by the next partition
+    let filters: Vec<String> = requests.lock().unwrap().iter().map(|(_, filter)| filter.clone()).collect();
+    assert_eq!(
+        filters,
+        vec![
+            "",
+            "\\dedicated\\1",
+            "\\nand\\1\\dedicated\\1",
+            "\\dedicated\\1\\secure\\1",
+            "\\dedicated\\1\\nand\\1\\secure\\1",
+            "\\nand\\1\\dedicated\\1\\secure\\1",
+            "\\nand\\1\\dedicated\\1\\nand\\1\\secure\\1",
+        ]
+    );
+    Ok(())
+}
+
+#[cfg(all(feature = "non-async", feature = "server"))]
+#[test]
+fn test_shard_gameaddr() -> Result<()> {
+    let servers = [
+        ("10.0.0.1:27015", ServerAttrs::new()),
+        ("10.0.0.1:27016", ServerAttrs::new().dedicated(true)),
+        ("10.0.1.1:27015", ServerAttrs::new()),
+    ];
+    let strategy = ShardStrategy::new().gameaddr().dedicated().shard_limit(2);
+    let (mut client, requests) = sharded_client(&servers, usize::MAX)?;
+    assert_eq!(client.query_sharded(Region::All, Filter::new(), &strategy)?.len(), 3);
+
+    // The prefix grows by an octet on each split: 10.*, 10.0.*, 10.0.0.*
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 1 + 256 * 3 + 2);
+    assert_eq!(requests[1].1, "\\gameaddr\\0.*");
+    assert_eq!(requests[1 + 256].1, "\\gameaddr\\10.0.*");
+    assert_eq!(requests[1 + 512].1, "\\gameaddr\\10.0.0.*");
+    // Past 3 octets, 10.0.0.* is split by the next partition instead
+    assert_eq!(
+        requests[1 + 768..],
+        [
+            (0xFF, String::from("\\dedicated\\1\\gameaddr\\10.0.0.*")),
+            (0xFF, String::from("\\nand\\1\\dedicated\\1\\gameaddr\\10.0.0.*")),
+        ]
+    );
+    Ok(())
+}
+
+#[cfg(all(feature = "non-async", feature = "server"))]
+#[test]
+fn test_shard_partial() -> Result<()> {
+    let servers = [
+        ("10.0.0.1:27015", ServerAttrs::new().region(Region::USEast)),
+        ("10.0.0.2:27015", ServerAttrs::new().region(Region::USWest)),
+        ("10.0.0.3:27015", ServerAttrs::new().region(Region::Europe)),
+    ];
+    let strategy = ShardStrategy::new().regions().shard_limit(2);
+    // The master server is gone after the USEast and USWest sub-queries,
+    // before the Europe server is found
+    let (mut client, _) = sharded_client(&servers, 3)?;
+    let err = client.query_sharded(Region::All, Filter::new(), &strategy).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ConnectionReset);
+    let partial = PartialQuery::from_error(&err).unwrap();
+    assert_eq!(partial.pages, 3);
+    assert_eq!(partial.servers, vec!["10.0.0.1:27015", "10.0.0.2:27015"]);
+    Ok(())
+}
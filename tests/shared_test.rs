@@ -0,0 +1,136 @@
+#[cfg(any(feature = "async", feature = "non-async"))]
+use msq::{Filter, MockMasterServer, MockMasterHandle, Region, ServerAttrs};
+#[cfg(feature = "async")]
+use msq::MSQClient;
+#[cfg(feature = "non-async")]
+use msq::{MSQClientBlock, MSQClientBuilder};
+#[cfg(any(feature = "async", feature = "non-async"))]
+use std::io::{ErrorKind, Result};
+#[cfg(feature = "non-async")]
+use std::time::{Duration, Instant};
+
+// Mock master server with a server in each region, 10.0.0.1 in region 0x00
+#[cfg(any(feature = "async", feature = "non-async"))]
+fn mock() -> Result<MockMasterHandle> {
+    let mut mock = MockMasterServer::new();
+    for code in 0..8u8 {
+        let addr = format!("10.0.0.{}:27015", code + 1);
+        mock = mock.server_with(&addr, ServerAttrs::new().region(Region::from(code)));
+    }
+    mock.start()
+}
+
+#[tokio::main]
+#[test]
+#[cfg(feature = "async")]
+async fn test_shared_async() -> Result<()> {
+    let mock = mock()?;
+    let mut client = MSQClient::new().await?;
+    client.connect(&mock.local_addr().to_string()).await?;
+    let client = client.into_shared()?;
+
+    let mut tasks = vec![];
+    for code in 0..8u8 {
+        let client = client.clone();
+        tasks.push(tokio::spawn(async move {
+            client.query(Region::from(code), Filter::new()).await
+        }));
+    }
+    for (code, task) in (0..8u8).zip(tasks) {
+        assert_eq!(task.await.unwrap()?, vec![format!("10.0.0.{}:27015", code + 1)]);
+    }
+    assert_eq!(mock.requests().len(), 8);
+    assert!(client.idle() >= 1);
+
+    // Queries of the same task reuse the idle sockets
+    let idle = client.idle();
+    let (a, b) = tokio::join!(
+        client.query(Region::from(0), Filter::new()),
+        client.query(Region::from(1), Filter::new()),
+    );
+    assert_eq!(a?, vec!["10.0.0.1:27015"]);
+    assert_eq!(b?, vec!["10.0.0.2:27015"]);
+    // One socket is enough if the first query is done before the second starts
+    assert!((idle..=idle.max(2)).contains(&client.idle()));
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "non-async")]
+fn test_shared_block() -> Result<()> {
+    let mock = mock()?;
+    let mut client = MSQClientBlock::new()?;
+    client.connect(&mock.local_addr().to_string())?;
+    let client = client.into_shared()?;
+
+    let threads: Vec<_> = (0..8u8)
+        .map(|code| {
+            let client = client.clone();
+            std::thread::spawn(move || client.query(Region::from(code), Filter::new()))
+        })
+        .collect();
+    for (code, thread) in (0..8u8).zip(threads) {
+        assert_eq!(thread.join().unwrap()?, vec![format!("10.0.0.{}:27015", code + 1)]);
+    }
+    assert!(client.idle() >= 1 && client.idle() <= 8);
+
+    // Not connected to a master server
+    assert!(MSQClientBlock::new()?.into_shared().is_err());
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "non-async")]
+fn test_shared_pool_size() -> Result<()> {
+    let mock = mock()?;
+    let client = MSQClientBuilder::new()
+        .master(&mock.local_addr().to_string())
+        .pool_size(2)
+        .rate_limit(Duration::from_millis(50))
+        .build_block()?
+        .into_shared()?;
+
+    let start = Instant::now();
+    let threads: Vec<_> = (0..4u8)
+        .map(|code| {
+            let client = client.clone();
+            std::thread::spawn(move || client.query(Region::from(code), Filter::new()))
+        })
+        .collect();
+    for thread in threads {
+        assert_eq!(thread.join().unwrap()?.len(), 1);
+    }
+    // The rate limit applies to the queries of all threads together
+    assert!(start.elapsed() >= Duration::from_millis(150));
+    assert!(client.idle() <= 2);
+    Ok(())
+}
+
+#[tokio::main]
+#[test]
+#[cfg(feature = "async")]
+async fn test_shared_transport_async() -> Result<()> {
+    let transport = MockMasterServer::new().servers(&["10.0.0.1:27015"]).channel()?;
+    let mut client = MSQClient::with_transport(transport);
+    client.connect("127.0.0.1:27010").await?;
+    // The next queries could not go through the transport
+    let err = client.into_shared().err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "non-async")]
+fn test_shared_transport_block() -> Result<()> {
+    let transport = MockMasterServer::new().servers(&["10.0.0.1:27015"]).channel()?;
+    let mut client = MSQClientBlock::with_transport(transport);
+    client.connect("127.0.0.1:27010")?;
+    let err = client.into_shared().err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+
+    // Nor through a transport given to the builder
+    let transport = MockMasterServer::new().channel()?;
+    let client = MSQClientBuilder::new().master("127.0.0.1:27010").transport(transport).build_block()?;
+    assert_eq!(client.into_shared().err().unwrap().kind(), ErrorKind::Unsupported);
+    Ok(())
+}
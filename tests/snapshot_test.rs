@@ -0,0 +1,107 @@
+#[cfg(feature = "export")]
+use msq::{Region, ServerInfo, ServerRecord, Snapshot, SnapshotFormat};
+#[cfg(feature = "export")]
+use std::io::Result;
+#[cfg(feature = "export")]
+use std::time::Duration;
+
+#[cfg(feature = "export")]
+fn sample() -> Snapshot {
+    let servers = vec![
+        String::from("10.0.0.1:27015"),
+        String::from("10.0.0.2:27016"),
+        String::from("10.0.0.3:27017"),
+    ];
+    let mut snapshot = Snapshot::from_query(&servers, Region::Europe, "\\appid\\240");
+    let info = ServerInfo {
+        protocol: 17,
+        name: String::from("My \"CS:S\" server, est. 2004\nwelcome"),
+        map: String::from("de_dust2"),
+        folder: String::from("cstrike"),
+        game: String::from("Counter-Strike: Source"),
+        appid: 240,
+        players: 12,
+        max_players: 24,
+        bots: 2,
+        dedicated: true,
+        proxy: false,
+        linux: true,
+        password: false,
+        secure: true,
+        version: String::from("1.0.0.71"),
+        port: Some(27015),
+        keywords: vec![String::from("alltalk"), String::from("increased_maxplayers")],
+        // Past what whole microseconds in a u32 can hold
+        ping: Duration::new(4_300, 123_456_789),
+    };
+    assert!(snapshot.set_info("10.0.0.1:27015", info.clone()));
+    assert!(snapshot.set_info(
+        "10.0.0.2:27016",
+        ServerInfo {
+            port: None,
+            keywords: vec![],
+            ..info
+        }
+    ));
+    snapshot.push(ServerRecord {
+        addr: String::from("10.0.1.1:27015"),
+        region: Region::All,
+        filter: String::from("\\map\\cs_office"),
+        timestamp: 1_700_000_000_000,
+        info: None,
+    });
+    snapshot
+}
+
+#[cfg(feature = "export")]
+fn round_trip(format: SnapshotFormat) -> Result<()> {
+    let snapshot = sample();
+    let mut buf = vec![];
+    snapshot.write(&mut buf, format)?;
+    assert_eq!(Snapshot::read(buf.as_slice(), format)?, snapshot);
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "export")]
+fn test_snapshot_json() -> Result<()> {
+    round_trip(SnapshotFormat::Json)
+}
+
+#[test]
+#[cfg(feature = "export")]
+fn test_snapshot_ndjson() -> Result<()> {
+    round_trip(SnapshotFormat::Ndjson)?;
+    let mut buf = vec![];
+    sample().write(&mut buf, SnapshotFormat::Ndjson)?;
+    assert_eq!(buf.iter().filter(|b| **b == b'\n').count(), 4);
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "export")]
+fn test_snapshot_csv() -> Result<()> {
+    round_trip(SnapshotFormat::Csv)
+}
+
+#[test]
+#[cfg(feature = "export")]
+fn test_snapshot_binary() -> Result<()> {
+    round_trip(SnapshotFormat::Binary)?;
+    assert!(Snapshot::read(&b"MSQX\x01"[..], SnapshotFormat::Binary).is_err());
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "export")]
+fn test_snapshot_save_load() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("msq-{}-snapshot.csv", std::process::id()));
+    let snapshot = sample();
+    snapshot.save(&path, SnapshotFormat::Csv)?;
+    let loaded = Snapshot::load(&path, SnapshotFormat::Csv)?;
+    assert_eq!(loaded.len(), 4);
+    assert_eq!(loaded.records()[0].region, Region::Europe);
+    assert_eq!(loaded.records()[3].info, None);
+    assert_eq!(loaded, snapshot);
+    std::fs::remove_file(path)
+}
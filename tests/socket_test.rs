@@ -0,0 +1,125 @@
+use msq::SocketOptions;
+#[cfg(any(feature = "async", feature = "non-async"))]
+use msq::{CaptureFormat, Filter, MockMasterServer, Recorder, Region};
+#[cfg(feature = "async")]
+use msq::MSQClient;
+#[cfg(feature = "non-async")]
+use msq::MSQClientBlock;
+use std::io::{ErrorKind, Result};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+#[cfg(any(feature = "async", feature = "non-async"))]
+use std::sync::{Arc, Mutex};
+
+#[cfg(any(feature = "async", feature = "non-async"))]
+#[derive(Clone)]
+struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+#[cfg(any(feature = "async", feature = "non-async"))]
+impl std::io::Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+// Recorder capturing into memory, to check the addresses a client used
+#[cfg(any(feature = "async", feature = "non-async"))]
+fn memory_recorder() -> Result<(Recorder, SharedBuf)> {
+    let buf = SharedBuf(Arc::new(Mutex::new(vec![])));
+    Ok((Recorder::new(buf.clone(), CaptureFormat::Text)?, buf))
+}
+
+// The local and peer address of the first captured datagram
+#[cfg(any(feature = "async", feature = "non-async"))]
+fn captured_addrs(buf: &SharedBuf) -> (String, String) {
+    let capture = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+    let fields: Vec<&str> = capture.lines().nth(1).unwrap().split(' ').collect();
+    (fields[2].to_string(), fields[3].to_string())
+}
+
+#[test]
+fn test_socket_options() -> Result<()> {
+    let sock = SocketOptions::new()
+        .ip(IpAddr::V4(Ipv4Addr::LOCALHOST))
+        .recv_buffer_size(1 << 16)
+        .ttl(32)
+        .dscp(46)
+        .bind()?;
+    assert_eq!(sock.ttl()?, 32);
+    assert_eq!(sock.local_addr()?.ip(), IpAddr::V4(Ipv4Addr::LOCALHOST));
+    assert_ne!(sock.local_addr()?.port(), 0);
+
+    let sock = SocketOptions::new().ip(IpAddr::V6(Ipv6Addr::LOCALHOST)).ttl(16).bind()?;
+    assert!(sock.local_addr()?.is_ipv6());
+
+    let err = SocketOptions::new().dscp(64).bind().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "non-async")]
+fn test_socket_block() -> Result<()> {
+    let mock = MockMasterServer::new().server("10.0.0.1:27015").start()?;
+
+    // Dual-stack IPv6 socket on a fixed port reaching an IPv4 master server
+    let port = std::net::UdpSocket::bind("[::]:0")?.local_addr()?.port();
+    let options = SocketOptions::new().ip(IpAddr::V6(Ipv6Addr::UNSPECIFIED)).port(port);
+    let mut client = MSQClientBlock::with_options(&options)?;
+    let (recorder, capture) = memory_recorder()?;
+    client.record(recorder);
+    client.connect(&mock.local_addr().to_string())?;
+    assert_eq!(client.query(Region::All, Filter::new())?, vec!["10.0.0.1:27015"]);
+    let (local, peer) = captured_addrs(&capture);
+    assert!(local.starts_with('[') && local.ends_with(&format!("]:{}", port)));
+    assert_eq!(peer, format!("[::ffff:127.0.0.1]:{}", mock.local_addr().port()));
+
+    // IPv6-only sockets cannot
+    let options = SocketOptions::new().ip(IpAddr::V6(Ipv6Addr::LOCALHOST)).dual_stack(false);
+    let mut client = MSQClientBlock::with_options(&options)?;
+    let result = client
+        .connect(&mock.local_addr().to_string())
+        .and_then(|_| client.query(Region::All, Filter::new()));
+    assert!(result.is_err());
+
+    // Existing socket
+    let sock = std::net::UdpSocket::bind("127.0.0.1:0")?;
+    let local = sock.local_addr()?;
+    let mut client = MSQClientBlock::from_socket(sock);
+    let (recorder, capture) = memory_recorder()?;
+    client.record(recorder);
+    client.connect(&mock.local_addr().to_string())?;
+    assert_eq!(client.query(Region::All, Filter::new())?, vec!["10.0.0.1:27015"]);
+    assert_eq!(captured_addrs(&capture).0, local.to_string());
+    Ok(())
+}
+
+#[tokio::main]
+#[test]
+#[cfg(feature = "async")]
+async fn test_socket_async() -> Result<()> {
+    let mock = MockMasterServer::new().server("10.0.0.1:27015").start()?;
+    let options = SocketOptions::new().ip(IpAddr::V6(Ipv6Addr::UNSPECIFIED)).recv_buffer_size(1 << 20);
+    let mut client = MSQClient::with_options(&options).await?;
+    client.connect(&mock.local_addr().to_string()).await?;
+    assert_eq!(client.query(Region::All, Filter::new()).await?, vec!["10.0.0.1:27015"]);
+
+    // Each region is queried on its own dual-stack socket
+    let results = client
+        .query_regions(&[Region::All, Region::Europe], Filter::new())
+        .await?;
+    assert!(results.errors().is_empty());
+    assert_eq!(results.count(Region::All), Some(1));
+
+    let mut client = MSQClient::from_socket(std::net::UdpSocket::bind("127.0.0.1:0")?).await?;
+    let (recorder, capture) = memory_recorder()?;
+    client.record(recorder);
+    client.connect(&mock.local_addr().to_string()).await?;
+    assert_eq!(client.query(Region::All, Filter::new()).await?, vec!["10.0.0.1:27015"]);
+    assert!(captured_addrs(&capture).0.starts_with("127.0.0.1:"));
+    Ok(())
+}
@@ -0,0 +1,195 @@
+#[cfg(any(feature = "async", feature = "non-async"))]
+use msq::{A2SResponder, Filter, MockMasterServer, Region, ServerAttrs, Socks5Proxy};
+#[cfg(feature = "async")]
+use msq::{A2SClient, MSQClient};
+#[cfg(feature = "non-async")]
+use msq::{A2SClientBlock, MSQClientBlock};
+#[cfg(any(feature = "async", feature = "non-async"))]
+use std::io::{Read, Result, Write};
+#[cfg(feature = "non-async")]
+use std::io::ErrorKind;
+#[cfg(any(feature = "async", feature = "non-async"))]
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+#[cfg(any(feature = "async", feature = "non-async"))]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(any(feature = "async", feature = "non-async"))]
+use std::sync::Arc;
+#[cfg(any(feature = "async", feature = "non-async"))]
+use std::thread;
+
+// Local SOCKS5 stand-in supporting UDP ASSOCIATE, counting relayed datagrams
+#[cfg(any(feature = "async", feature = "non-async"))]
+struct Socks5StandIn {
+    addr: SocketAddr,
+    relayed: Arc<AtomicUsize>,
+}
+
+#[cfg(any(feature = "async", feature = "non-async"))]
+impl Socks5StandIn {
+    fn start(auth: Option<(&'static str, &'static str)>) -> Result<Socks5StandIn> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let relayed = Arc::new(AtomicUsize::new(0));
+        let counter = relayed.clone();
+        thread::spawn(move || {
+            for control in listener.incoming().flatten() {
+                let counter = counter.clone();
+                thread::spawn(move || associate(control, auth, counter));
+            }
+        });
+        Ok(Socks5StandIn { addr, relayed })
+    }
+
+    fn proxy(&self) -> Socks5Proxy {
+        Socks5Proxy::new(&self.addr.to_string())
+    }
+
+    fn relayed(&self) -> usize {
+        self.relayed.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(any(feature = "async", feature = "non-async"))]
+fn associate(mut control: TcpStream, auth: Option<(&str, &str)>, relayed: Arc<AtomicUsize>) -> Result<()> {
+    let mut greeting = [0u8; 2];
+    control.read_exact(&mut greeting)?;
+    let mut methods = vec![0u8; greeting[1] as usize];
+    control.read_exact(&mut methods)?;
+    match auth {
+        Some((username, password)) => {
+            if !methods.contains(&0x02) {
+                return control.write_all(&[0x05, 0xFF]);
+            }
+            control.write_all(&[0x05, 0x02])?;
+            let mut header = [0u8; 2];
+            control.read_exact(&mut header)?;
+            let mut user = vec![0u8; header[1] as usize];
+            control.read_exact(&mut user)?;
+            let mut len = [0u8; 1];
+            control.read_exact(&mut len)?;
+            let mut pass = vec![0u8; len[0] as usize];
+            control.read_exact(&mut pass)?;
+            let ok = user == username.as_bytes() && pass == password.as_bytes();
+            control.write_all(&[0x01, if ok { 0x00 } else { 0x01 }])?;
+            if !ok {
+                return Ok(());
+            }
+        }
+        None => control.write_all(&[0x05, 0x00])?,
+    }
+
+    // UDP ASSOCIATE with an IPv4 client address
+    let mut request = [0u8; 10];
+    control.read_exact(&mut request)?;
+    assert_eq!(&request[..4], &[0x05, 0x03, 0x00, 0x01]);
+    let client = decode_addr(&request[3..]);
+
+    let relay = UdpSocket::bind("127.0.0.1:0")?;
+    let mut reply = vec![0x05, 0x00, 0x00];
+    reply.extend(encode_addr(relay.local_addr()?));
+    control.write_all(&reply)?;
+
+    let udp = relay.try_clone()?;
+    thread::spawn(move || {
+        let mut buf = [0u8; 4096];
+        while let Ok((len, from)) = udp.recv_from(&mut buf) {
+            relayed.fetch_add(1, Ordering::SeqCst);
+            if from == client {
+                let target = decode_addr(&buf[3..]);
+                let _ = udp.send_to(&buf[10..len], target);
+            } else {
+                let mut packet = vec![0x00, 0x00, 0x00];
+                packet.extend(encode_addr(from));
+                packet.extend(&buf[..len]);
+                let _ = udp.send_to(&packet, client);
+            }
+        }
+    });
+
+    // The association lasts until the client closes the control connection
+    let _ = control.read(&mut [0u8; 1]);
+    Ok(())
+}
+
+#[cfg(any(feature = "async", feature = "non-async"))]
+fn encode_addr(addr: SocketAddr) -> Vec<u8> {
+    let mut buf = vec![0x01];
+    match addr {
+        SocketAddr::V4(addr) => buf.extend(addr.ip().octets()),
+        SocketAddr::V6(_) => panic!("IPv4 only"),
+    }
+    buf.extend(addr.port().to_be_bytes());
+    buf
+}
+
+#[cfg(any(feature = "async", feature = "non-async"))]
+fn decode_addr(buf: &[u8]) -> SocketAddr {
+    assert_eq!(buf[0], 0x01);
+    let ip = [buf[1], buf[2], buf[3], buf[4]];
+    SocketAddr::from((ip, u16::from_be_bytes([buf[5], buf[6]])))
+}
+
+#[cfg(any(feature = "async", feature = "non-async"))]
+fn responder() -> A2SResponder {
+    A2SResponder::new(ServerAttrs::new().appid(240).name("Proxied").map("de_dust2").players(3, 24))
+}
+
+#[test]
+#[cfg(feature = "non-async")]
+fn test_socks5_block() -> Result<()> {
+    let proxy = Socks5StandIn::start(None)?;
+    let mock = MockMasterServer::new()
+        .servers(&["10.0.0.1:27015", "10.0.0.2:27015"])
+        .start()?;
+    let mut client = MSQClientBlock::with_transport(proxy.proxy().transport()?);
+    client.connect(&mock.local_addr().to_string())?;
+    let servers = client.query(Region::All, Filter::new().appid(240))?;
+    assert_eq!(servers, vec!["10.0.0.1:27015", "10.0.0.2:27015"]);
+    assert_eq!(mock.requests().len(), 1);
+    // The request and its reply
+    assert_eq!(proxy.relayed(), 2);
+
+    let a2s = responder().start()?;
+    let mut client = A2SClientBlock::with_proxy(&proxy.proxy())?;
+    let info = client.info(&a2s.local_addr().to_string())?;
+    assert_eq!(info.name, "Proxied");
+    assert_eq!(info.players, 3);
+    assert!(proxy.relayed() > 2);
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "non-async")]
+fn test_socks5_auth() -> Result<()> {
+    let proxy = Socks5StandIn::start(Some(("user", "secret")))?;
+    let mock = MockMasterServer::new().server("10.0.0.1:27015").start()?;
+
+    let mut client = MSQClientBlock::with_transport(proxy.proxy().auth("user", "secret").transport()?);
+    client.connect(&mock.local_addr().to_string())?;
+    assert_eq!(client.query(Region::All, Filter::new())?, vec!["10.0.0.1:27015"]);
+
+    let err = proxy.proxy().auth("user", "wrong").associate().err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+    let err = proxy.proxy().associate().err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+    Ok(())
+}
+
+#[tokio::main]
+#[test]
+#[cfg(feature = "async")]
+async fn test_socks5_async() -> Result<()> {
+    let proxy = Socks5StandIn::start(None)?;
+    let mock = MockMasterServer::new().server("10.0.0.1:27015").start()?;
+    let mut client = MSQClient::with_transport(proxy.proxy().transport()?);
+    client.connect(&mock.local_addr().to_string()).await?;
+    assert_eq!(client.query(Region::All, Filter::new()).await?, vec!["10.0.0.1:27015"]);
+    assert_eq!(proxy.relayed(), 2);
+
+    let a2s = responder().start()?;
+    let mut client = A2SClient::with_proxy(&proxy.proxy()).await?;
+    let info = client.info(&a2s.local_addr().to_string()).await?;
+    assert_eq!(info.map, "de_dust2");
+    assert!(proxy.relayed() > 2);
+    Ok(())
+}
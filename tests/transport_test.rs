@@ -0,0 +1,129 @@
+use msq::{ChannelTransport, Transport};
+#[cfg(any(feature = "async", feature = "non-async"))]
+use msq::{Filter, MockMasterServer, Region};
+#[cfg(feature = "non-async")]
+use msq::{CaptureFormat, MSQClientBlock, MockFault, Recorder, Replay, UdpTransport};
+#[cfg(feature = "async")]
+use msq::MSQClient;
+use std::io::{ErrorKind, Result};
+#[cfg(feature = "non-async")]
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_channel_transport() -> Result<()> {
+    let (mut a, mut b) = ChannelTransport::pair();
+    assert_eq!(a.send(b"ping", None)?, 4);
+    let mut buf = [0u8; 16];
+    let len = b.recv(&mut buf, None)?;
+    assert_eq!(&buf[..len], b"ping");
+
+    // Nothing arrives before the deadline
+    let deadline = Instant::now() + Duration::from_millis(50);
+    assert_eq!(b.recv(&mut buf, Some(deadline)).unwrap_err().kind(), ErrorKind::TimedOut);
+    assert_eq!(b.recv(&mut buf, Some(Instant::now())).unwrap_err().kind(), ErrorKind::TimedOut);
+
+    // Datagrams sent before the other end is dropped are still received
+    b.send(b"pong", None)?;
+    drop(b);
+    let len = a.recv(&mut buf, None)?;
+    assert_eq!(&buf[..len], b"pong");
+    assert_eq!(a.recv(&mut buf, None).unwrap_err().kind(), ErrorKind::BrokenPipe);
+    assert_eq!(a.send(b"ping", None).unwrap_err().kind(), ErrorKind::BrokenPipe);
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "non-async")]
+fn test_transport_block() -> Result<()> {
+    let mut mock = MockMasterServer::new().fault(1, MockFault::Delay(Duration::from_millis(50)));
+    for i in 0..500 {
+        mock = mock.server(&format!("10.0.{}.{}:27015", i / 256, i % 256));
+    }
+    let mut client = MSQClientBlock::with_transport(mock.channel()?);
+    client.max_servers_on_query(1000);
+    let servers = client.query(Region::All, Filter::new().appid(240))?;
+    assert_eq!(servers.len(), 500);
+    assert_eq!(servers[0], "10.0.0.0:27015");
+    Ok(())
+}
+
+// A session recorded over UDP replays through the same Transport interface
+#[test]
+#[cfg(feature = "non-async")]
+fn test_transport_udp_replay() -> Result<()> {
+    let mock = MockMasterServer::new().server("10.0.0.1:27015").start()?;
+    let capture = SharedBuf(Arc::new(Mutex::new(vec![])));
+    let mut client = MSQClientBlock::with_transport(UdpTransport::bind("127.0.0.1:0")?);
+    client.connect(&mock.local_addr().to_string())?;
+    client.record(Recorder::new(capture.clone(), CaptureFormat::Text)?);
+    assert_eq!(client.query(Region::All, Filter::new().appid(240))?, vec!["10.0.0.1:27015"]);
+
+    let capture = capture.0.lock().unwrap().clone();
+    let mut replay = Replay::from_reader(capture.as_slice())?;
+    assert_eq!(replay.remaining(), 2);
+    let mut buf = [0u8; 64];
+    assert_eq!(replay.recv(&mut buf, None).unwrap_err().kind(), ErrorKind::TimedOut);
+
+    let mut client = MSQClientBlock::with_transport(replay);
+    assert_eq!(client.query(Region::All, Filter::new().appid(240))?, vec!["10.0.0.1:27015"]);
+    Ok(())
+}
+
+#[cfg(feature = "non-async")]
+#[derive(Clone)]
+struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+#[cfg(feature = "non-async")]
+impl std::io::Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::main]
+#[test]
+#[cfg(feature = "async")]
+async fn test_transport_async() -> Result<()> {
+    let transport = MockMasterServer::new()
+        .servers(&["10.0.0.1:27015", "10.0.0.2:27015"])
+        .channel()?;
+    let mut client = MSQClient::with_transport(transport);
+    let results = client
+        .query_regions(&[Region::All, Region::Europe], Filter::new().appid(240))
+        .await?;
+    assert_eq!(results.count(Region::All), Some(2));
+    assert_eq!(results.count(Region::Europe), Some(0));
+    Ok(())
+}
+
+#[tokio::main(flavor = "current_thread")]
+#[test]
+#[cfg(feature = "async")]
+async fn test_transport_async_silent() -> Result<()> {
+    // The other end is kept but never replies
+    let (transport, _silent) = ChannelTransport::pair();
+    let mut client = MSQClient::with_transport(transport);
+    client.connect("127.0.0.1:27010").await?;
+
+    // Waiting on the transport leaves the runtime free to fire its timers
+    let start = Instant::now();
+    tokio::select! {
+        _ = client.query(Region::All, Filter::new()) => panic!("Query of a silent transport finished"),
+        _ = tokio::time::sleep(Duration::from_millis(100)) => (),
+    }
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    // The deadline of a query applies to the transport as to a socket
+    let err = client
+        .query_with_deadline(Region::All, Filter::new(), Instant::now() + Duration::from_millis(100))
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TimedOut);
+    Ok(())
+}
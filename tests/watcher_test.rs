@@ -0,0 +1,117 @@
+#[cfg(feature = "async")]
+use msq::{A2SResponder, Filter, MasterServer, Region, ServerAttrs, WatchEvent, WatchHandle, Watcher};
+#[cfg(feature = "async")]
+use std::io::Result;
+#[cfg(feature = "async")]
+use std::time::Duration;
+
+#[cfg(feature = "async")]
+async fn next(watch: &mut WatchHandle) -> WatchEvent {
+    tokio::time::timeout(Duration::from_secs(10), watch.recv())
+        .await
+        .expect("No event in time")
+        .expect("Watcher stopped")
+}
+
+#[cfg(feature = "async")]
+fn watcher(master: &str) -> Watcher {
+    Watcher::new(master, Region::All, Filter::new().appid(240))
+        .interval(Duration::from_secs(1))
+        .jitter(Duration::ZERO)
+}
+
+#[tokio::main]
+#[test]
+#[cfg(feature = "async")]
+async fn test_watcher_added_removed() -> Result<()> {
+    let master = MasterServer::new();
+    master.register("10.0.0.1:27015", ServerAttrs::new().appid(240))?;
+    master.register("10.0.0.2:27015", ServerAttrs::new().appid(240))?;
+    let handle = master.start("127.0.0.1:0")?;
+    let mut watch = watcher(&handle.local_addr().to_string()).start();
+
+    let mut added = vec![];
+    for _ in 0..2 {
+        match next(&mut watch).await {
+            WatchEvent::ServerAdded { addr, info: None } => added.push(addr),
+            event => panic!("Unexpected event: {:?}", event),
+        }
+    }
+    assert_eq!(added, vec!["10.0.0.1:27015", "10.0.0.2:27015"]);
+
+    master.unregister("10.0.0.1:27015")?;
+    master.register("10.0.0.3:27015", ServerAttrs::new().appid(240))?;
+    match next(&mut watch).await {
+        WatchEvent::ServerAdded { addr, .. } => assert_eq!(addr, "10.0.0.3:27015"),
+        event => panic!("Unexpected event: {:?}", event),
+    }
+    match next(&mut watch).await {
+        WatchEvent::ServerRemoved { addr } => assert_eq!(addr, "10.0.0.1:27015"),
+        event => panic!("Unexpected event: {:?}", event),
+    }
+    watch.stop();
+    Ok(())
+}
+
+#[tokio::main]
+#[test]
+#[cfg(feature = "async")]
+async fn test_watcher_probe_changes() -> Result<()> {
+    let attrs = ServerAttrs::new().appid(240).map("de_dust2").players(2, 24);
+    let game = A2SResponder::new(attrs.clone()).start()?;
+    let game_addr = game.local_addr().to_string();
+    let master = MasterServer::new();
+    master.register(&game_addr, attrs)?;
+    let handle = master.start("127.0.0.1:0")?;
+    let mut watch = watcher(&handle.local_addr().to_string())
+        .probe(true)
+        .probe_timeout(Duration::from_millis(500))
+        .start();
+
+    match next(&mut watch).await {
+        WatchEvent::ServerAdded { addr, info: Some(info) } => {
+            assert_eq!(addr, game_addr);
+            assert_eq!((info.map.as_str(), info.players), ("de_dust2", 2));
+        }
+        event => panic!("Unexpected event: {:?}", event),
+    }
+
+    // The game server changes map with more players on the same port
+    game.stop();
+    let attrs = ServerAttrs::new().appid(240).map("cs_office").players(5, 24);
+    let _game = A2SResponder::new(attrs).bind(&game_addr)?;
+    match next(&mut watch).await {
+        WatchEvent::MapChanged { addr, old, new } => {
+            assert_eq!(addr, game_addr);
+            assert_eq!((old.as_str(), new.as_str()), ("de_dust2", "cs_office"));
+        }
+        event => panic!("Unexpected event: {:?}", event),
+    }
+    match next(&mut watch).await {
+        WatchEvent::PlayersChanged { old, new, .. } => assert_eq!((old, new), (2, 5)),
+        event => panic!("Unexpected event: {:?}", event),
+    }
+    Ok(())
+}
+
+#[tokio::main]
+#[test]
+#[cfg(feature = "async")]
+async fn test_watcher_backoff() -> Result<()> {
+    // A master server that never answers
+    let silent = std::net::UdpSocket::bind("127.0.0.1:0")?;
+    let mut watch = watcher(&silent.local_addr()?.to_string())
+        .timeout(Duration::from_millis(200))
+        .max_backoff(Duration::from_secs(3))
+        .start();
+
+    for expected in [2, 3] {
+        match next(&mut watch).await {
+            WatchEvent::QueryFailed { retry_in, .. } => {
+                assert_eq!(retry_in, Duration::from_secs(expected))
+            }
+            event => panic!("Unexpected event: {:?}", event),
+        }
+    }
+    Ok(())
+}